@@ -0,0 +1,202 @@
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use super::{request, ConnectionInfo, IntoKeys, Sender};
+use crate::resp::convert::FromResp;
+use crate::resp::value::*;
+
+/// How much longer than the server side timeout [`BlockingClient`] waits for
+/// a reply by default.
+pub const DEFAULT_BLOCKING_MARGIN: Duration = Duration::from_secs(1);
+
+/// Either end of a list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListEnd {
+    Left,
+    Right,
+}
+
+impl ListEnd {
+    fn arg(self) -> RespValue {
+        match self {
+            ListEnd::Left => bulk("LEFT"),
+            ListEnd::Right => bulk("RIGHT"),
+        }
+    }
+}
+
+/// Runs blocking commands such as BLPOP, each on a connection of its own.
+///
+/// A blocking command holds its connection until it returns, which would
+/// stall every other user of a shared [`super::Connection`]. Connections are
+/// opened on demand and kept for reuse once their command completes.
+///
+/// The client side read timeout is the server side timeout plus a margin,
+/// so a reply that never comes is noticed. Connections that time out are
+/// dropped, as their reply may still arrive later. A zero timeout blocks
+/// forever on both sides.
+///
+/// The handle is cheap to clone, and every clone shares the same
+/// connections.
+#[derive(Clone)]
+pub struct BlockingClient {
+    info: ConnectionInfo,
+    margin: Duration,
+    idle: Arc<Mutex<Vec<Sender>>>,
+}
+
+impl BlockingClient {
+    pub fn new(info: ConnectionInfo) -> Self {
+        Self {
+            info,
+            margin: DEFAULT_BLOCKING_MARGIN,
+            idle: Arc::default(),
+        }
+    }
+
+    /// Sets how much longer than the server side timeout to wait for a
+    /// reply.
+    pub fn set_timeout_margin(&mut self, margin: Duration) {
+        self.margin = margin;
+    }
+
+    /// Runs a blocking command whose server side timeout is `timeout`,
+    /// returning None if it expired.
+    pub async fn command<T: FromResp>(
+        &self,
+        cmd: RespValue,
+        timeout: Duration,
+    ) -> io::Result<Option<T>> {
+        let idle = self.idle.lock().unwrap().pop();
+        let mut conn = match idle {
+            Some(conn) => conn,
+            None => Sender::connect(&self.info).await?,
+        };
+
+        let read_timeout = (!timeout.is_zero()).then(|| timeout + self.margin);
+        let reply = request(&mut conn.f_conn, cmd, read_timeout).await;
+
+        // error replies leave the connection usable, anything else may not
+        match &reply {
+            Ok(_) => self.idle.lock().unwrap().push(conn),
+            Err(e) if e.kind() == io::ErrorKind::Other => self.idle.lock().unwrap().push(conn),
+            Err(_) => {}
+        }
+
+        Option::from_resp(reply?)
+    }
+
+    /// Pops from the head of the first non-empty list, returning the key
+    /// and the element.
+    pub async fn blpop(
+        &self,
+        keys: impl IntoKeys,
+        timeout: Duration,
+    ) -> io::Result<Option<(Bytes, Bytes)>> {
+        self.pop("BLPOP", keys, timeout).await
+    }
+
+    /// Like [`BlockingClient::blpop`], popping from the tail.
+    pub async fn brpop(
+        &self,
+        keys: impl IntoKeys,
+        timeout: Duration,
+    ) -> io::Result<Option<(Bytes, Bytes)>> {
+        self.pop("BRPOP", keys, timeout).await
+    }
+
+    async fn pop(
+        &self,
+        name: &str,
+        keys: impl IntoKeys,
+        timeout: Duration,
+    ) -> io::Result<Option<(Bytes, Bytes)>> {
+        let reply: Option<Vec<Bytes>> = self.command(keyed(name, keys, timeout), timeout).await?;
+
+        reply
+            .map(|reply| match <[Bytes; 2]>::try_from(reply) {
+                Ok([key, element]) => Ok((key, element)),
+                Err(_) => Err(io::Error::from(io::ErrorKind::InvalidData)),
+            })
+            .transpose()
+    }
+
+    /// Moves an element from one end of `source` to one end of
+    /// `destination`, returning it.
+    pub async fn blmove(
+        &self,
+        source: impl AsRef<[u8]>,
+        destination: impl AsRef<[u8]>,
+        from: ListEnd,
+        to: ListEnd,
+        timeout: Duration,
+    ) -> io::Result<Option<Bytes>> {
+        let cmd = vec![
+            bulk("BLMOVE"),
+            bulk(source),
+            bulk(destination),
+            from.arg(),
+            to.arg(),
+            seconds(timeout),
+        ];
+
+        self.command(cmd.into(), timeout).await
+    }
+
+    /// Pops the member with the lowest score from the first non-empty sorted
+    /// set, returning the key, the member and its score.
+    pub async fn bzpopmin(
+        &self,
+        keys: impl IntoKeys,
+        timeout: Duration,
+    ) -> io::Result<Option<(Bytes, Bytes, f64)>> {
+        self.zpop("BZPOPMIN", keys, timeout).await
+    }
+
+    /// Like [`BlockingClient::bzpopmin`], for the highest score.
+    pub async fn bzpopmax(
+        &self,
+        keys: impl IntoKeys,
+        timeout: Duration,
+    ) -> io::Result<Option<(Bytes, Bytes, f64)>> {
+        self.zpop("BZPOPMAX", keys, timeout).await
+    }
+
+    async fn zpop(
+        &self,
+        name: &str,
+        keys: impl IntoKeys,
+        timeout: Duration,
+    ) -> io::Result<Option<(Bytes, Bytes, f64)>> {
+        let reply: Option<Vec<RespValue>> =
+            self.command(keyed(name, keys, timeout), timeout).await?;
+
+        reply
+            .map(|reply| match <[RespValue; 3]>::try_from(reply) {
+                Ok([key, member, score]) => Ok((
+                    Bytes::from_resp(key)?,
+                    Bytes::from_resp(member)?,
+                    f64::from_resp(score)?,
+                )),
+                Err(_) => Err(io::Error::from(io::ErrorKind::InvalidData)),
+            })
+            .transpose()
+    }
+}
+
+/// The timeout argument, in seconds. Fractions are accepted since Redis 6.
+fn seconds(timeout: Duration) -> RespValue {
+    bulk(timeout.as_secs_f64().to_string())
+}
+
+/// Builds `<name> keys... <timeout>`.
+fn keyed(name: &str, keys: impl IntoKeys, timeout: Duration) -> RespValue {
+    let mut cmd = vec![bulk(name)];
+    cmd.extend(keys.into_keys().iter().map(bulk));
+    cmd.push(seconds(timeout));
+
+    cmd.into()
+}
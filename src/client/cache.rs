@@ -0,0 +1,354 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+
+use super::message::into_bytes;
+use super::{Connection, Receiver, Sender};
+use crate::resp::value::*;
+
+/// The channel invalidations are published on when they are redirected to
+/// another connection.
+pub const INVALIDATE_CHANNEL: &str = "__redis__:invalidate";
+
+/// Which keys the server sends invalidations for.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum TrackingMode {
+    /// Keys read by this connection.
+    #[default]
+    Default,
+    /// Every key starting with one of the prefixes, or every key if there
+    /// are none, whether this connection read it or not.
+    Broadcast(Vec<Bytes>),
+}
+
+impl TrackingMode {
+    /// Builds `CLIENT TRACKING ON`, with the options for this mode.
+    fn command(&self, redirect: Option<i64>) -> RespValue {
+        let mut cmd = vec![bulk("CLIENT"), bulk("TRACKING"), bulk("ON")];
+
+        if let Some(id) = redirect {
+            cmd.extend([bulk("REDIRECT"), bulk(id.to_string())]);
+        }
+
+        if let TrackingMode::Broadcast(prefixes) = self {
+            cmd.push(bulk("BCAST"));
+            for prefix in prefixes {
+                cmd.extend([bulk("PREFIX"), bulk(prefix)]);
+            }
+        }
+
+        cmd.into()
+    }
+}
+
+/// Keys the server says may have changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Invalidation {
+    Keys(Vec<Bytes>),
+    /// Every key, e.g. after FLUSHALL.
+    All,
+}
+
+impl Invalidation {
+    /// Parses a RESP3 `invalidate` push, or a message on
+    /// [`INVALIDATE_CHANNEL`] as delivered to a redirect connection.
+    pub(crate) fn from_frame(frame: &RespValue) -> Option<Self> {
+        let (push, items) = match frame {
+            RespValue::Push(items) => (true, items),
+            RespValue::Array(Some(items)) => (false, items),
+            _ => return None,
+        };
+
+        let keys = match &items[..] {
+            [ty, keys] if push && ty.as_str() == Some("invalidate") => keys,
+            [ty, channel, keys]
+                if ty.as_str() == Some("message")
+                    && channel.as_str() == Some(INVALIDATE_CHANNEL) =>
+            {
+                keys
+            }
+            _ => return None,
+        };
+
+        match keys {
+            RespValue::Array(Some(keys)) | RespValue::Set(keys) => Some(Self::Keys(
+                keys.iter().cloned().filter_map(into_bytes).collect(),
+            )),
+            _ => Some(Self::All),
+        }
+    }
+}
+
+/// A connection [`ClientCache`] can read through.
+pub trait CacheConnection {
+    fn command(&mut self, cmd: RespValue) -> impl Future<Output = io::Result<RespValue>>;
+}
+
+impl CacheConnection for Sender {
+    fn command(&mut self, cmd: RespValue) -> impl Future<Output = io::Result<RespValue>> {
+        Sender::command(self, cmd)
+    }
+}
+
+impl CacheConnection for Connection {
+    fn command(&mut self, cmd: RespValue) -> impl Future<Output = io::Result<RespValue>> {
+        Connection::command(self, cmd)
+    }
+}
+
+struct Entry {
+    key: Bytes,
+    reply: RespValue,
+    used: u64,
+}
+
+#[derive(Default)]
+struct Lru {
+    capacity: usize,
+    entries: HashMap<Vec<Bytes>, Entry>,
+    /// Commands by when they were last used, oldest first.
+    order: BTreeMap<u64, Vec<Bytes>>,
+    /// Cached commands by the key they read.
+    by_key: HashMap<Bytes, HashSet<Vec<Bytes>>>,
+    tick: u64,
+    /// Bumped by every invalidation, so replies fetched across one aren't
+    /// cached.
+    epoch: u64,
+}
+
+impl Lru {
+    fn get(&mut self, cmd: &[Bytes]) -> Option<RespValue> {
+        let entry = self.entries.get_mut(cmd)?;
+
+        self.tick += 1;
+        let cmd = self.order.remove(&entry.used)?;
+        entry.used = self.tick;
+        self.order.insert(self.tick, cmd);
+
+        Some(entry.reply.clone())
+    }
+
+    fn insert(&mut self, key: Bytes, cmd: Vec<Bytes>, reply: RespValue) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.remove(&cmd);
+
+        while self.entries.len() >= self.capacity {
+            let Some((_, oldest)) = self.order.pop_first() else {
+                break;
+            };
+            self.remove(&oldest);
+        }
+
+        self.tick += 1;
+        self.order.insert(self.tick, cmd.clone());
+        self.by_key
+            .entry(key.clone())
+            .or_default()
+            .insert(cmd.clone());
+        self.entries.insert(
+            cmd,
+            Entry {
+                key,
+                reply,
+                used: self.tick,
+            },
+        );
+    }
+
+    fn remove(&mut self, cmd: &[Bytes]) {
+        let Some(entry) = self.entries.remove(cmd) else {
+            return;
+        };
+
+        self.order.remove(&entry.used);
+
+        if let Some(cmds) = self.by_key.get_mut(&entry.key) {
+            cmds.remove(cmd);
+            if cmds.is_empty() {
+                self.by_key.remove(&entry.key);
+            }
+        }
+    }
+
+    fn invalidate(&mut self, invalidation: &Invalidation) {
+        self.epoch += 1;
+
+        match invalidation {
+            Invalidation::Keys(keys) => {
+                for key in keys {
+                    for cmd in self.by_key.remove(key).unwrap_or_default() {
+                        if let Some(entry) = self.entries.remove(&cmd) {
+                            self.order.remove(&entry.used);
+                        }
+                    }
+                }
+            }
+            Invalidation::All => self.clear(),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.epoch += 1;
+        self.entries.clear();
+        self.order.clear();
+        self.by_key.clear();
+    }
+}
+
+/// A local cache of read replies, kept up to date by the server with client
+/// side caching (`CLIENT TRACKING`).
+///
+/// Turn tracking on with [`ClientCache::track`] for a RESP3 [`Connection`],
+/// which receives invalidations as push frames, or with
+/// [`ClientCache::track_redirect`] for a RESP2 [`Sender`], whose
+/// invalidations are sent to a [`Receiver`]. Then read through the cache with
+/// [`ClientCache::get`] and friends. The cache holds up to a fixed number of
+/// replies, dropping the least recently used ones first.
+///
+/// The cache is emptied when the connection receiving invalidations closes,
+/// since changes may be missed from then on.
+///
+/// The handle is cheap to clone, and every clone shares the same cache.
+#[derive(Clone)]
+pub struct ClientCache {
+    lru: Arc<Mutex<Lru>>,
+}
+
+impl ClientCache {
+    /// A cache holding up to `capacity` replies.
+    pub fn new(capacity: usize) -> Self {
+        let lru = Lru {
+            capacity,
+            ..Lru::default()
+        };
+
+        Self {
+            lru: Arc::new(Mutex::new(lru)),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lru.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        self.lru.lock().unwrap().clear();
+    }
+
+    /// Drops the replies for the invalidated keys.
+    pub fn invalidate(&self, invalidation: &Invalidation) {
+        self.lru.lock().unwrap().invalidate(invalidation);
+    }
+
+    /// Turns on tracking for a RESP3 connection, applying the invalidations
+    /// it receives to this cache.
+    pub async fn track(&self, conn: &Connection, mode: &TrackingMode) -> io::Result<()> {
+        expect_ok(conn.command(mode.command(None)).await?)?;
+        conn.set_cache(self.clone())
+    }
+
+    /// Turns on tracking for `sender`, redirecting invalidations to
+    /// `receiver`, which subscribes to [`INVALIDATE_CHANNEL`] and applies them
+    /// to this cache. This is how tracking works under RESP2.
+    ///
+    /// The receiver must not be subscribed to anything yet, and must keep
+    /// being read for the invalidations to be applied. Invalidations travel
+    /// on a separate connection, so they may arrive slightly after the
+    /// change.
+    pub async fn track_redirect(
+        &self,
+        sender: &mut Sender,
+        receiver: &mut Receiver,
+        mode: &TrackingMode,
+    ) -> io::Result<()> {
+        let id = receiver.client_id().await?;
+
+        receiver.subscribe(INVALIDATE_CHANNEL).await?;
+        expect_ok(sender.command(mode.command(Some(id))).await?)?;
+
+        receiver.set_cache(self.clone());
+        Ok(())
+    }
+
+    pub async fn get(
+        &self,
+        conn: &mut impl CacheConnection,
+        key: impl AsRef<[u8]>,
+    ) -> io::Result<Option<Bytes>> {
+        let key = key.as_ref();
+        let reply = self.read(conn, key, [&b"GET"[..], key]).await?;
+
+        Ok(into_bytes(reply))
+    }
+
+    pub async fn hget(
+        &self,
+        conn: &mut impl CacheConnection,
+        key: impl AsRef<[u8]>,
+        field: impl AsRef<[u8]>,
+    ) -> io::Result<Option<Bytes>> {
+        let key = key.as_ref();
+        let reply = self
+            .read(conn, key, [&b"HGET"[..], key, field.as_ref()])
+            .await?;
+
+        Ok(into_bytes(reply))
+    }
+
+    /// Runs a read-only command that only reads `key`, caching its reply.
+    /// Commands reading several keys must not be cached, as only one of them
+    /// would invalidate the reply.
+    pub async fn read<I>(
+        &self,
+        conn: &mut impl CacheConnection,
+        key: impl AsRef<[u8]>,
+        cmd: I,
+    ) -> io::Result<RespValue>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        let cmd = cmd
+            .into_iter()
+            .map(|arg| Bytes::copy_from_slice(arg.as_ref()))
+            .collect::<Vec<_>>();
+
+        let epoch = {
+            let mut lru = self.lru.lock().unwrap();
+            if let Some(reply) = lru.get(&cmd) {
+                return Ok(reply);
+            }
+            lru.epoch
+        };
+
+        let reply = conn
+            .command(cmd.iter().map(bulk).collect::<Vec<_>>().into())
+            .await?;
+
+        let mut lru = self.lru.lock().unwrap();
+        // the reply may be stale if an invalidation came in meanwhile
+        if lru.epoch == epoch {
+            let key = Bytes::copy_from_slice(key.as_ref());
+            lru.insert(key, cmd, reply.clone());
+        }
+
+        Ok(reply)
+    }
+}
+
+fn expect_ok(reply: RespValue) -> io::Result<()> {
+    match reply {
+        RespValue::SimpleString(s) if &*s == "OK" => Ok(()),
+        _ => Err(io::Error::from(io::ErrorKind::InvalidData)),
+    }
+}
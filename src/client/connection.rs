@@ -0,0 +1,546 @@
+use std::collections::VecDeque;
+use std::future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::stream::{SplitSink, SplitStream};
+use futures::{SinkExt, Stream, StreamExt};
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio::sync::{mpsc, oneshot};
+
+use super::subscription::{Confirmation, SubscriptionKind};
+use super::{
+    error_reply, framed, non_empty, pong_payload, request, with_timeout, ClientCache,
+    ConnectionInfo, FramedConnection, IntoChannels, Invalidation, Message, MessageKind, Pong,
+    Protocol, Subscriptions, Transport,
+};
+use crate::resp::value::*;
+
+enum Request {
+    Command {
+        cmd: RespValue,
+        reply: oneshot::Sender<io::Result<RespValue>>,
+    },
+    Subscription {
+        kind: SubscriptionKind,
+        channels: Vec<Bytes>,
+        done: oneshot::Sender<io::Result<i64>>,
+    },
+    /// Applies invalidation pushes to the cache from then on.
+    Cache(ClientCache),
+}
+
+/// A RESP3 connection that carries commands and pub/sub on one socket.
+///
+/// Under RESP3 the server delivers pub/sub events as out-of-band push frames,
+/// so a subscribed connection can keep issuing regular commands. A background
+/// task owns the socket: replies are matched to commands in the order they
+/// were sent, and pushed messages are routed to the [`Messages`] stream
+/// returned alongside the connection.
+///
+/// The handle is cheap to clone, and every clone shares the same socket.
+/// Must be created from within a tokio runtime.
+#[derive(Clone)]
+pub struct Connection {
+    requests: mpsc::UnboundedSender<Request>,
+    subscriptions: Arc<Mutex<Subscriptions>>,
+    response_timeout: Option<Duration>,
+}
+
+impl Connection {
+    pub async fn new(addr: impl ToSocketAddrs) -> io::Result<(Self, Messages)> {
+        Self::from_stream(TcpStream::connect(addr).await?).await
+    }
+
+    #[cfg(unix)]
+    pub async fn connect_unix(path: impl AsRef<std::path::Path>) -> io::Result<(Self, Messages)> {
+        Self::from_stream(tokio::net::UnixStream::connect(path).await?).await
+    }
+
+    /// Switches `stream` to RESP3 with `HELLO 3` before handing it to the
+    /// background task.
+    pub async fn from_stream(stream: impl Transport) -> io::Result<(Self, Messages)> {
+        let mut framed = framed(stream);
+
+        match request(&mut framed, vec![bulk("HELLO"), bulk("3")].into(), None).await? {
+            RespValue::Map(_) => Ok(Self::spawn(framed, None)),
+            _ => Err(io::Error::from(io::ErrorKind::InvalidData)),
+        }
+    }
+
+    /// Connects using `info`, always asking for RESP3 regardless of its
+    /// [`Protocol`].
+    pub async fn connect(info: &ConnectionInfo) -> io::Result<(Self, Messages)> {
+        let info = info.clone().with_protocol(Protocol::Resp3);
+        let framed = super::connect(&info).await?;
+
+        Ok(Self::spawn(framed, info.response_timeout))
+    }
+
+    fn spawn(framed: FramedConnection, response_timeout: Option<Duration>) -> (Self, Messages) {
+        let (requests, requests_rx) = mpsc::unbounded_channel();
+        let (messages, messages_rx) = mpsc::unbounded_channel();
+        let subscriptions = Arc::new(Mutex::new(Subscriptions::default()));
+
+        let (tx, rx) = framed.split();
+        let driver = Driver {
+            tx,
+            rx,
+            outbox: VecDeque::new(),
+            unflushed: false,
+            pending: VecDeque::new(),
+            subscriptions: subscriptions.clone(),
+            messages,
+            cache: None,
+        };
+
+        tokio::spawn(driver.run(requests_rx));
+
+        let messages = Messages {
+            rx: messages_rx,
+            _requests: requests.clone(),
+        };
+
+        let conn = Self {
+            requests,
+            subscriptions,
+            response_timeout,
+        };
+
+        (conn, messages)
+    }
+
+    /// The channels and patterns the server has confirmed.
+    pub fn subscriptions(&self) -> Subscriptions {
+        self.subscriptions.lock().unwrap().clone()
+    }
+
+    /// Sends an arbitrary command and returns the reply.
+    pub async fn command(&self, cmd: RespValue) -> io::Result<RespValue> {
+        let (reply, reply_rx) = oneshot::channel();
+        self.send(Request::Command { cmd, reply })?;
+
+        with_timeout(self.response_timeout, async {
+            reply_rx.await.map_err(|_| io::ErrorKind::BrokenPipe)?
+        })
+        .await
+    }
+
+    /// Sends a PING carrying `payload` and waits for the server to echo it,
+    /// measuring the round trip. Under RESP3 the reply is the same whether
+    /// or not the connection is subscribed.
+    pub async fn ping(&self, payload: impl AsRef<[u8]>) -> io::Result<Pong> {
+        let sent = Instant::now();
+        let reply = self
+            .command(vec![bulk("PING"), bulk(&payload)].into())
+            .await?;
+        let latency = sent.elapsed();
+
+        let payload =
+            pong_payload(&reply, Some(payload.as_ref())).ok_or(io::ErrorKind::InvalidData)?;
+        Ok(Pong { payload, latency })
+    }
+
+    pub async fn publish(
+        &self,
+        channel: impl AsRef<[u8]>,
+        mesg: impl AsRef<[u8]>,
+    ) -> io::Result<i64> {
+        let resp = vec![bulk("PUBLISH"), bulk(channel), bulk(mesg)].into();
+
+        match self.command(resp).await? {
+            RespValue::Integer(i) => Ok(i),
+            _ => Err(io::Error::from(io::ErrorKind::InvalidData)),
+        }
+    }
+
+    pub async fn spublish(
+        &self,
+        channel: impl AsRef<[u8]>,
+        mesg: impl AsRef<[u8]>,
+    ) -> io::Result<i64> {
+        let resp = vec![bulk("SPUBLISH"), bulk(channel), bulk(mesg)].into();
+
+        match self.command(resp).await? {
+            RespValue::Integer(i) => Ok(i),
+            _ => Err(io::Error::from(io::ErrorKind::InvalidData)),
+        }
+    }
+
+    pub async fn subscribe(&self, channels: impl IntoChannels) -> io::Result<i64> {
+        let channels = non_empty(channels)?;
+        self.send_subscription(SubscriptionKind::Subscribe, channels)
+            .await
+    }
+
+    pub async fn unsubscribe(&self, channels: impl IntoChannels) -> io::Result<i64> {
+        let channels = non_empty(channels)?;
+        self.send_subscription(SubscriptionKind::Unsubscribe, channels)
+            .await
+    }
+
+    pub async fn unsubscribe_all(&self) -> io::Result<i64> {
+        self.send_subscription(SubscriptionKind::Unsubscribe, vec![])
+            .await
+    }
+
+    pub async fn psubscribe(&self, patterns: impl IntoChannels) -> io::Result<i64> {
+        let patterns = non_empty(patterns)?;
+        self.send_subscription(SubscriptionKind::PSubscribe, patterns)
+            .await
+    }
+
+    pub async fn punsubscribe(&self, patterns: impl IntoChannels) -> io::Result<i64> {
+        let patterns = non_empty(patterns)?;
+        self.send_subscription(SubscriptionKind::PUnsubscribe, patterns)
+            .await
+    }
+
+    pub async fn punsubscribe_all(&self) -> io::Result<i64> {
+        self.send_subscription(SubscriptionKind::PUnsubscribe, vec![])
+            .await
+    }
+
+    pub async fn ssubscribe(&self, channels: impl IntoChannels) -> io::Result<i64> {
+        let channels = non_empty(channels)?;
+        self.send_subscription(SubscriptionKind::SSubscribe, channels)
+            .await
+    }
+
+    pub async fn sunsubscribe(&self, channels: impl IntoChannels) -> io::Result<i64> {
+        let channels = non_empty(channels)?;
+        self.send_subscription(SubscriptionKind::SUnsubscribe, channels)
+            .await
+    }
+
+    pub async fn sunsubscribe_all(&self) -> io::Result<i64> {
+        self.send_subscription(SubscriptionKind::SUnsubscribe, vec![])
+            .await
+    }
+
+    async fn send_subscription(
+        &self,
+        kind: SubscriptionKind,
+        channels: Vec<Bytes>,
+    ) -> io::Result<i64> {
+        let (done, done_rx) = oneshot::channel();
+        self.send(Request::Subscription {
+            kind,
+            channels,
+            done,
+        })?;
+
+        with_timeout(self.response_timeout, async {
+            done_rx.await.map_err(|_| io::ErrorKind::BrokenPipe)?
+        })
+        .await
+    }
+
+    pub(crate) fn set_cache(&self, cache: ClientCache) -> io::Result<()> {
+        self.send(Request::Cache(cache))
+    }
+
+    fn send(&self, req: Request) -> io::Result<()> {
+        self.requests
+            .send(req)
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))
+    }
+}
+
+/// The pub/sub messages pushed to a [`Connection`]. The stream ends when the
+/// connection is closed, after yielding the error that closed it, if any.
+pub struct Messages {
+    rx: mpsc::UnboundedReceiver<io::Result<Message>>,
+    // keeps the connection open while only the stream is held
+    _requests: mpsc::UnboundedSender<Request>,
+}
+
+impl Stream for Messages {
+    type Item = io::Result<Message>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().rx.poll_recv(cx)
+    }
+}
+
+/// A command waiting on the server, kept in the order they were written.
+enum Pending {
+    Reply(oneshot::Sender<io::Result<RespValue>>),
+    Confirm(PendingConfirm),
+}
+
+/// A (un)subscribe command waiting for the server to confirm its channels.
+struct PendingConfirm {
+    kind: SubscriptionKind,
+    /// Waits for a single confirmation when empty.
+    expected: Vec<Bytes>,
+    any: bool,
+    done: oneshot::Sender<io::Result<i64>>,
+}
+
+/// The background task behind a [`Connection`].
+struct Driver {
+    tx: SplitSink<FramedConnection, RespValue>,
+    rx: SplitStream<FramedConnection>,
+    /// Frames waiting to be written, so a slow write never holds up reads.
+    outbox: VecDeque<RespValue>,
+    unflushed: bool,
+    pending: VecDeque<Pending>,
+    subscriptions: Arc<Mutex<Subscriptions>>,
+    messages: mpsc::UnboundedSender<io::Result<Message>>,
+    cache: Option<ClientCache>,
+}
+
+impl Driver {
+    async fn run(mut self, mut requests: mpsc::UnboundedReceiver<Request>) {
+        let res = loop {
+            let res = tokio::select! {
+                req = requests.recv() => match req {
+                    Some(req) => {
+                        self.send(req);
+                        Ok(())
+                    }
+                    // every handle is gone
+                    None => return,
+                },
+                res = future::poll_fn(|cx| poll_write(&mut self.tx, &mut self.outbox, cx)),
+                    if self.unflushed =>
+                {
+                    self.unflushed = false;
+                    res
+                }
+                frame = self.rx.next() => match frame {
+                    Some(Ok(frame)) => self.dispatch(frame),
+                    Some(Err(e)) => Err(e),
+                    None => break Ok(()),
+                },
+            };
+
+            if let Err(e) = res {
+                break Err(e);
+            }
+        };
+
+        self.close(res.err());
+    }
+
+    /// Queues `req` for writing, in the order its reply will come back.
+    fn send(&mut self, req: Request) {
+        match req {
+            Request::Command { cmd, reply } => {
+                self.queue(cmd);
+                self.pending.push_back(Pending::Reply(reply));
+            }
+            Request::Subscription {
+                kind,
+                channels,
+                done,
+            } => {
+                // an empty list means every current subscription of that kind
+                let expected = if channels.is_empty() {
+                    match self.subscribed(kind) {
+                        Some(subscribed) => subscribed,
+                        None => {
+                            let _ = done.send(Err(io::ErrorKind::InvalidInput.into()));
+                            return;
+                        }
+                    }
+                } else {
+                    channels.clone()
+                };
+
+                let mut cmd = vec![bulk(kind.command())];
+                cmd.extend(channels.iter().map(bulk));
+                self.queue(cmd.into());
+
+                self.pending.push_back(Pending::Confirm(PendingConfirm {
+                    kind,
+                    any: expected.is_empty(),
+                    expected,
+                    done,
+                }));
+            }
+            Request::Cache(cache) => self.cache = Some(cache),
+        }
+    }
+
+    fn queue(&mut self, frame: RespValue) {
+        self.outbox.push_back(frame);
+        self.unflushed = true;
+    }
+
+    /// What an unsubscribe of `kind` from everything will be confirmed for:
+    /// the confirmed subscriptions, updated by the (un)subscribes still
+    /// waiting on the server, which it handles first.
+    fn subscribed(&self, kind: SubscriptionKind) -> Option<Vec<Bytes>> {
+        let subscriptions = self.subscriptions.lock().unwrap();
+
+        let (subscribe, confirmed) = match kind {
+            SubscriptionKind::Unsubscribe => {
+                (SubscriptionKind::Subscribe, subscriptions.channels())
+            }
+            SubscriptionKind::PUnsubscribe => {
+                (SubscriptionKind::PSubscribe, subscriptions.patterns())
+            }
+            SubscriptionKind::SUnsubscribe => {
+                (SubscriptionKind::SSubscribe, subscriptions.shard_channels())
+            }
+            _ => return None,
+        };
+
+        let mut subscribed = confirmed.clone();
+
+        for pending in &self.pending {
+            match pending {
+                Pending::Confirm(pending) if pending.kind == subscribe => {
+                    subscribed.extend(pending.expected.iter().cloned());
+                }
+                Pending::Confirm(pending) if pending.kind == kind => {
+                    for channel in &pending.expected {
+                        subscribed.remove(channel);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Some(subscribed.into_iter().collect())
+    }
+
+    fn dispatch(&mut self, frame: RespValue) -> io::Result<()> {
+        // only pushes, a reply could look like a redirected invalidation
+        let invalidation = match &frame {
+            RespValue::Push(_) => Invalidation::from_frame(&frame),
+            _ => None,
+        };
+
+        // without a cache they are ordinary messages
+        if let (Some(cache), Some(invalidation)) = (&self.cache, invalidation) {
+            cache.invalidate(&invalidation);
+            return Ok(());
+        }
+
+        let items = match frame {
+            RespValue::Push(items) => items,
+            reply => return self.reply(reply),
+        };
+
+        let ty = items.first().and_then(RespValue::as_str);
+
+        if let Some(kind) = ty.and_then(SubscriptionKind::from_frame_type) {
+            let confirmation = Confirmation::from_items(kind, items.into_iter().skip(1))?;
+            self.subscriptions.lock().unwrap().apply(&confirmation);
+            self.confirmed(confirmation);
+            return Ok(());
+        }
+
+        // ignore other pushes
+        let Some(kind) = ty.and_then(MessageKind::from_frame_type) else {
+            return Ok(());
+        };
+
+        let mesg = Message::from_items(kind, items.into_iter().skip(1))?;
+        let _ = self.messages.send(Ok(mesg));
+
+        Ok(())
+    }
+
+    fn reply(&mut self, reply: RespValue) -> io::Result<()> {
+        match self.pending.pop_front() {
+            Some(Pending::Reply(tx)) => {
+                let _ = tx.send(error_reply(reply));
+                Ok(())
+            }
+            // (un)subscribe commands only get a regular reply when they fail
+            Some(Pending::Confirm(pending)) => match error_reply(reply) {
+                Err(e) => {
+                    let _ = pending.done.send(Err(e));
+                    Ok(())
+                }
+                Ok(_) => Err(io::Error::from(io::ErrorKind::InvalidData)),
+            },
+            None => Err(io::Error::from(io::ErrorKind::InvalidData)),
+        }
+    }
+
+    fn confirmed(&mut self, confirmation: Confirmation) {
+        // the server may also unsubscribe on its own, e.g. when a shard
+        // channel's slot moves, in which case nothing is waiting
+        let Some((i, pending)) = self
+            .pending
+            .iter_mut()
+            .enumerate()
+            .find_map(|(i, p)| match p {
+                Pending::Confirm(pending) if pending.kind == confirmation.kind => {
+                    Some((i, pending))
+                }
+                _ => None,
+            })
+        else {
+            return;
+        };
+
+        match &confirmation.channel {
+            Some(channel) => {
+                if let Some(j) = pending.expected.iter().position(|c| c == channel) {
+                    pending.expected.swap_remove(j);
+                }
+            }
+            // nothing was subscribed, so this is the only reply
+            None => pending.expected.clear(),
+        }
+
+        if pending.any || pending.expected.is_empty() {
+            if let Some(Pending::Confirm(pending)) = self.pending.remove(i) {
+                let _ = pending.done.send(Ok(confirmation.count));
+            }
+        }
+    }
+
+    /// Fails everything still waiting on the connection.
+    fn close(self, err: Option<io::Error>) {
+        // invalidations would be missed from now on
+        if let Some(cache) = &self.cache {
+            cache.clear();
+        }
+
+        let copy = |e: &Option<io::Error>| match e {
+            Some(e) => io::Error::new(e.kind(), e.to_string()),
+            None => io::Error::from(io::ErrorKind::BrokenPipe),
+        };
+
+        for pending in self.pending {
+            match pending {
+                Pending::Reply(tx) => {
+                    let _ = tx.send(Err(copy(&err)));
+                }
+                Pending::Confirm(pending) => {
+                    let _ = pending.done.send(Err(copy(&err)));
+                }
+            }
+        }
+
+        if let Some(e) = err {
+            let _ = self.messages.send(Err(e));
+        }
+    }
+}
+
+/// Writes out the queued frames, then flushes them. Frames stay queued
+/// until the sink takes them, so this can be dropped between polls.
+fn poll_write(
+    tx: &mut SplitSink<FramedConnection, RespValue>,
+    outbox: &mut VecDeque<RespValue>,
+    cx: &mut Context<'_>,
+) -> Poll<io::Result<()>> {
+    while !outbox.is_empty() {
+        ready!(tx.poll_ready_unpin(cx))?;
+        tx.start_send_unpin(outbox.pop_front().unwrap())?;
+    }
+
+    tx.poll_flush_unpin(cx)
+}
@@ -0,0 +1,56 @@
+use bytes::Bytes;
+
+/// One or more key names, as taken by WATCH, transactions and the blocking
+/// pops. Implemented for single strings as well as arrays, slices and
+/// vectors of anything byte-like.
+pub trait IntoKeys {
+    fn into_keys(self) -> Vec<Bytes>;
+}
+
+/// One or more channel names or patterns, as accepted by the subscribe and
+/// unsubscribe methods. These are taken the same way as keys.
+pub use IntoKeys as IntoChannels;
+
+impl IntoKeys for &str {
+    fn into_keys(self) -> Vec<Bytes> {
+        vec![Bytes::copy_from_slice(self.as_bytes())]
+    }
+}
+
+impl IntoKeys for &String {
+    fn into_keys(self) -> Vec<Bytes> {
+        self.as_str().into_keys()
+    }
+}
+
+impl IntoKeys for String {
+    fn into_keys(self) -> Vec<Bytes> {
+        vec![Bytes::from(self)]
+    }
+}
+
+impl IntoKeys for Bytes {
+    fn into_keys(self) -> Vec<Bytes> {
+        vec![self]
+    }
+}
+
+impl<T: AsRef<[u8]>, const N: usize> IntoKeys for [T; N] {
+    fn into_keys(self) -> Vec<Bytes> {
+        self.as_slice().into_keys()
+    }
+}
+
+impl<T: AsRef<[u8]>> IntoKeys for &[T] {
+    fn into_keys(self) -> Vec<Bytes> {
+        self.iter()
+            .map(|k| Bytes::copy_from_slice(k.as_ref()))
+            .collect()
+    }
+}
+
+impl<T: AsRef<[u8]>> IntoKeys for Vec<T> {
+    fn into_keys(self) -> Vec<Bytes> {
+        self.as_slice().into_keys()
+    }
+}
@@ -0,0 +1,283 @@
+use std::io;
+use std::str;
+
+use bytes::Bytes;
+
+use super::{Message, Receiver, Sender};
+use crate::resp::value::*;
+
+/// The operation that triggered a keyspace notification. Event names not
+/// listed here are kept as [`KeyEvent::Other`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum KeyEvent {
+    Set,
+    SetRange,
+    Append,
+    IncrBy,
+    IncrByFloat,
+    Del,
+    /// The old name of a renamed key.
+    RenameFrom,
+    /// The new name of a renamed key.
+    RenameTo,
+    MoveFrom,
+    MoveTo,
+    CopyTo,
+    Restore,
+    /// A timeout was set on the key.
+    Expire,
+    /// The timeout of the key was removed.
+    Persist,
+    /// The key expired and was removed.
+    Expired,
+    /// The key was removed to free memory.
+    Evicted,
+    /// A key was created, only sent when the `n` class is enabled.
+    New,
+    LPush,
+    RPush,
+    LPop,
+    RPop,
+    LInsert,
+    LSet,
+    LRem,
+    LTrim,
+    HSet,
+    HDel,
+    HIncrBy,
+    HIncrByFloat,
+    SAdd,
+    SRem,
+    SPop,
+    ZAdd,
+    ZIncr,
+    ZRem,
+    XAdd,
+    XDel,
+    XTrim,
+    Other(String),
+}
+
+impl KeyEvent {
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "set" => Self::Set,
+            "setrange" => Self::SetRange,
+            "append" => Self::Append,
+            "incrby" => Self::IncrBy,
+            "incrbyfloat" => Self::IncrByFloat,
+            "del" => Self::Del,
+            "rename_from" => Self::RenameFrom,
+            "rename_to" => Self::RenameTo,
+            "move_from" => Self::MoveFrom,
+            "move_to" => Self::MoveTo,
+            "copy_to" => Self::CopyTo,
+            "restore" => Self::Restore,
+            "expire" => Self::Expire,
+            "persist" => Self::Persist,
+            "expired" => Self::Expired,
+            "evicted" => Self::Evicted,
+            "new" => Self::New,
+            "lpush" => Self::LPush,
+            "rpush" => Self::RPush,
+            "lpop" => Self::LPop,
+            "rpop" => Self::RPop,
+            "linsert" => Self::LInsert,
+            "lset" => Self::LSet,
+            "lrem" => Self::LRem,
+            "ltrim" => Self::LTrim,
+            "hset" => Self::HSet,
+            "hdel" => Self::HDel,
+            "hincrby" => Self::HIncrBy,
+            "hincrbyfloat" => Self::HIncrByFloat,
+            "sadd" => Self::SAdd,
+            "srem" => Self::SRem,
+            "spop" => Self::SPop,
+            "zadd" => Self::ZAdd,
+            "zincr" => Self::ZIncr,
+            "zrem" => Self::ZRem,
+            "xadd" => Self::XAdd,
+            "xdel" => Self::XDel,
+            "xtrim" => Self::XTrim,
+            name => Self::Other(name.into()),
+        }
+    }
+
+    /// The name used by the server.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Set => "set",
+            Self::SetRange => "setrange",
+            Self::Append => "append",
+            Self::IncrBy => "incrby",
+            Self::IncrByFloat => "incrbyfloat",
+            Self::Del => "del",
+            Self::RenameFrom => "rename_from",
+            Self::RenameTo => "rename_to",
+            Self::MoveFrom => "move_from",
+            Self::MoveTo => "move_to",
+            Self::CopyTo => "copy_to",
+            Self::Restore => "restore",
+            Self::Expire => "expire",
+            Self::Persist => "persist",
+            Self::Expired => "expired",
+            Self::Evicted => "evicted",
+            Self::New => "new",
+            Self::LPush => "lpush",
+            Self::RPush => "rpush",
+            Self::LPop => "lpop",
+            Self::RPop => "rpop",
+            Self::LInsert => "linsert",
+            Self::LSet => "lset",
+            Self::LRem => "lrem",
+            Self::LTrim => "ltrim",
+            Self::HSet => "hset",
+            Self::HDel => "hdel",
+            Self::HIncrBy => "hincrby",
+            Self::HIncrByFloat => "hincrbyfloat",
+            Self::SAdd => "sadd",
+            Self::SRem => "srem",
+            Self::SPop => "spop",
+            Self::ZAdd => "zadd",
+            Self::ZIncr => "zincr",
+            Self::ZRem => "zrem",
+            Self::XAdd => "xadd",
+            Self::XDel => "xdel",
+            Self::XTrim => "xtrim",
+            Self::Other(name) => name,
+        }
+    }
+}
+
+/// A keyspace notification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyspaceEvent {
+    pub db: i64,
+    pub key: Bytes,
+    pub event: KeyEvent,
+}
+
+impl KeyspaceEvent {
+    /// Parses a message published on a `__keyspace@<db>__:<key>` or
+    /// `__keyevent@<db>__:<event>` channel. Returns None for other messages.
+    pub fn from_message(mesg: &Message) -> Option<Self> {
+        let rest = mesg.channel.strip_prefix(b"__key")?;
+
+        let (keyspace, rest) = match rest.strip_prefix(b"space@") {
+            Some(rest) => (true, rest),
+            None => (false, rest.strip_prefix(b"event@")?),
+        };
+
+        let end = rest.windows(3).position(|w| w == b"__:")?;
+        let db = str::from_utf8(&rest[..end]).ok()?.parse().ok()?;
+        let suffix = mesg.channel.slice_ref(&rest[end + 3..]);
+
+        let (key, event) = if keyspace {
+            (suffix, &mesg.payload)
+        } else {
+            (mesg.payload.clone(), &suffix)
+        };
+
+        Some(Self {
+            db,
+            key,
+            event: KeyEvent::from_name(str::from_utf8(event).ok()?),
+        })
+    }
+}
+
+/// Which notification channels to listen on. Every event is published on
+/// both, so listening on both delivers each event twice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyspaceChannels {
+    /// `__keyspace@<db>__:<key>`, enabled by the `K` flag.
+    Keyspace,
+    /// `__keyevent@<db>__:<event>`, enabled by the `E` flag.
+    Keyevent,
+    Both,
+}
+
+/// Subscribes a [`Receiver`] to keyspace notifications and parses them into
+/// [`KeyspaceEvent`]s.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyspaceNotifications {
+    db: Option<i64>,
+    channels: KeyspaceChannels,
+}
+
+impl KeyspaceNotifications {
+    /// Notifications for database `db`, or for every database if None.
+    pub fn new(db: Option<i64>, channels: KeyspaceChannels) -> Self {
+        Self { db, channels }
+    }
+
+    /// The patterns passed to PSUBSCRIBE.
+    pub fn patterns(&self) -> Vec<Bytes> {
+        let db = match self.db {
+            Some(db) => db.to_string(),
+            None => "*".into(),
+        };
+
+        let mut patterns = Vec::new();
+
+        if self.channels != KeyspaceChannels::Keyevent {
+            patterns.push(format!("__keyspace@{}__:*", db).into());
+        }
+
+        if self.channels != KeyspaceChannels::Keyspace {
+            patterns.push(format!("__keyevent@{}__:*", db).into());
+        }
+
+        patterns
+    }
+
+    /// Enables notifications on the server with CONFIG SET. `classes` are
+    /// the event class flags, e.g. `A` for all events or `g$x` for generic
+    /// and string commands and expirations. The `K` and `E` flags matching
+    /// the channels are added.
+    ///
+    /// This replaces the server's current setting, and affects every client.
+    pub async fn enable(&self, sender: &mut Sender, classes: &str) -> io::Result<()> {
+        let mut flags = String::new();
+
+        if self.channels != KeyspaceChannels::Keyevent {
+            flags.push('K');
+        }
+
+        if self.channels != KeyspaceChannels::Keyspace {
+            flags.push('E');
+        }
+
+        flags.push_str(classes);
+
+        let cmd = vec![
+            bulk("CONFIG"),
+            bulk("SET"),
+            bulk("notify-keyspace-events"),
+            bulk(flags),
+        ];
+
+        match sender.command(cmd.into()).await? {
+            RespValue::SimpleString(s) if &*s == "OK" => Ok(()),
+            _ => Err(io::Error::from(io::ErrorKind::InvalidData)),
+        }
+    }
+
+    pub async fn subscribe(&self, receiver: &mut Receiver) -> io::Result<i64> {
+        receiver.psubscribe(self.patterns()).await
+    }
+
+    pub async fn unsubscribe(&self, receiver: &mut Receiver) -> io::Result<i64> {
+        receiver.punsubscribe(self.patterns()).await
+    }
+
+    /// Waits for the next notification. Other messages the receiver gets
+    /// are discarded.
+    pub async fn next(&self, receiver: &mut Receiver) -> io::Result<KeyspaceEvent> {
+        loop {
+            if let Some(event) = KeyspaceEvent::from_message(&receiver.next().await?) {
+                return Ok(event);
+            }
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::io;
+use std::str;
+
+use bytes::{Bytes, BytesMut};
+
+use crate::resp::encoder::resp_encode;
+use crate::resp::value::RespValue;
+
+/// Which kind of pub/sub frame a [`Message`] was delivered in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessageKind {
+    /// Published to a channel subscribed with SUBSCRIBE.
+    Message,
+    /// Published to a channel matching a PSUBSCRIBE pattern.
+    PMessage,
+    /// Published with SPUBLISH to a shard channel subscribed with SSUBSCRIBE.
+    SMessage,
+}
+
+impl MessageKind {
+    /// Maps the first item of a pub/sub frame to a kind, if it is a message.
+    pub(crate) fn from_frame_type(ty: &str) -> Option<Self> {
+        match ty {
+            "message" => Some(Self::Message),
+            "pmessage" => Some(Self::PMessage),
+            "smessage" => Some(Self::SMessage),
+            _ => None,
+        }
+    }
+}
+
+/// A message received on a subscribed connection. Channel names and
+/// payloads are kept as raw bytes, since Redis doesn't require them to be
+/// valid UTF-8.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub kind: MessageKind,
+    /// The pattern that matched the channel, for [`MessageKind::PMessage`].
+    pub pattern: Option<Bytes>,
+    pub channel: Bytes,
+    /// A payload that isn't a string, like the keys of a redirected
+    /// invalidation, is kept RESP-encoded.
+    pub payload: Bytes,
+}
+
+impl Message {
+    /// Builds a message from the items of a pub/sub frame, excluding the
+    /// leading frame type.
+    pub(crate) fn from_items(
+        kind: MessageKind,
+        items: impl IntoIterator<Item = RespValue>,
+    ) -> io::Result<Self> {
+        let mut items = items.into_iter();
+
+        let mut next = || {
+            items
+                .next()
+                .and_then(into_bytes)
+                .ok_or(io::ErrorKind::InvalidData)
+        };
+
+        let pattern = match kind {
+            MessageKind::PMessage => Some(next()?),
+            MessageKind::Message | MessageKind::SMessage => None,
+        };
+        let channel = next()?;
+
+        let payload = match items.next().ok_or(io::ErrorKind::InvalidData)? {
+            RespValue::BulkString(Some(buf)) => Bytes::from(Vec::from(buf)),
+            RespValue::SimpleString(s) => Bytes::from(String::from(s)),
+            payload => {
+                let mut buf = BytesMut::new();
+                resp_encode(payload, &mut buf);
+                buf.freeze()
+            }
+        };
+
+        Ok(Self {
+            kind,
+            pattern,
+            channel,
+            payload,
+        })
+    }
+
+    pub fn channel_str(&self) -> Option<&str> {
+        str::from_utf8(&self.channel).ok()
+    }
+
+    pub fn payload_str(&self) -> Option<&str> {
+        str::from_utf8(&self.payload).ok()
+    }
+
+    pub fn pattern_str(&self) -> Option<&str> {
+        self.pattern
+            .as_ref()
+            .and_then(|pattern| str::from_utf8(pattern).ok())
+    }
+}
+
+/// Takes the contents of a bulk or simple string without copying.
+pub(crate) fn into_bytes(val: RespValue) -> Option<Bytes> {
+    match val {
+        RespValue::BulkString(Some(buf)) => Some(Bytes::from(Vec::from(buf))),
+        RespValue::SimpleString(s) => Some(Bytes::from(String::from(s))),
+        _ => None,
+    }
+}
@@ -0,0 +1,1001 @@
+use std::collections::VecDeque;
+use std::future::{self, Future};
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::stream::{SplitSink, SplitStream};
+use futures::{SinkExt, Stream, StreamExt};
+use tokio::io::DuplexStream;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio::time::{self, Instant, Interval, MissedTickBehavior, Sleep};
+use tokio_util::codec::Framed;
+
+use crate::resp::{value::*, RespCodec};
+
+mod blocking;
+mod cache;
+mod config;
+mod connection;
+mod keys;
+mod keyspace;
+mod message;
+mod pubsub;
+mod script;
+mod sentinel;
+mod streams;
+mod subscription;
+#[cfg(feature = "tls")]
+mod tls;
+mod transaction;
+mod transport;
+mod typed;
+mod worker;
+
+pub use blocking::{BlockingClient, ListEnd, DEFAULT_BLOCKING_MARGIN};
+pub use cache::{CacheConnection, ClientCache, Invalidation, TrackingMode, INVALIDATE_CHANNEL};
+pub use config::{ConnectionAddr, ConnectionInfo, Protocol, TlsConfig, DEFAULT_PORT};
+pub use connection::{Connection, Messages};
+pub use keys::{IntoChannels, IntoKeys};
+pub use keyspace::{KeyEvent, KeyspaceChannels, KeyspaceEvent, KeyspaceNotifications};
+pub use message::{Message, MessageKind};
+pub use pubsub::{PubSub, Subscription};
+pub use script::{Script, ScriptArgs};
+pub use sentinel::{Failovers, Sentinel, SentinelReceiver, SentinelSender, SWITCH_MASTER_CHANNEL};
+pub use streams::{
+    AutoClaim, PendingEntry, PendingRange, PendingSummary, StreamEntry, StreamRead,
+    StreamReadOptions, StreamTrim,
+};
+pub use subscription::{Channels, Subscriptions};
+
+use subscription::{Confirmation, SubscriptionKind};
+pub use transaction::{Pipeline, TransactionResults, DEFAULT_TRANSACTION_RETRIES};
+pub use transport::{BoxedTransport, Transport};
+#[cfg(feature = "json")]
+pub use typed::JsonCodec;
+#[cfg(feature = "msgpack")]
+pub use typed::MsgPackCodec;
+pub use typed::{BytesCodec, CodecError, DecodeError, PayloadCodec, TypedChannel, Utf8Codec};
+pub use worker::{Delivery, Worker, WorkerConfig};
+
+type FramedConnection = Framed<BoxedTransport, RespCodec>;
+
+fn framed(stream: impl Transport) -> FramedConnection {
+    Framed::new(Box::new(stream), RespCodec::default())
+}
+
+pub struct Sender {
+    f_conn: FramedConnection,
+    response_timeout: Option<Duration>,
+    transaction_retries: usize,
+}
+
+/// Runs `fut`, failing with [`io::ErrorKind::TimedOut`] if it takes longer
+/// than `timeout`.
+async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    fut: impl Future<Output = io::Result<T>>,
+) -> io::Result<T> {
+    match timeout {
+        Some(timeout) => time::timeout(timeout, fut)
+            .await
+            .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))?,
+        None => fut.await,
+    }
+}
+
+/// Sends a command and waits for its reply. Error replies from the server
+/// are returned as errors, see [`error_reply`].
+async fn request<T>(
+    conn: &mut Framed<T, RespCodec>,
+    cmd: RespValue,
+    timeout: Option<Duration>,
+) -> io::Result<RespValue>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    let reply = with_timeout(timeout, async {
+        conn.send(cmd).await?;
+        conn.next().await.ok_or(io::ErrorKind::BrokenPipe)?
+    })
+    .await?;
+
+    error_reply(reply)
+}
+
+/// Turns an error reply into an [`io::ErrorKind::Other`] error carrying the
+/// message.
+fn error_reply(reply: RespValue) -> io::Result<RespValue> {
+    match reply {
+        RespValue::SimpleError(err) => Err(io::Error::other(String::from(err))),
+        RespValue::BlobError(err) => {
+            Err(io::Error::other(String::from_utf8_lossy(&err).into_owned()))
+        }
+        reply => Ok(reply),
+    }
+}
+
+/// Opens a connection and runs the handshake described by `info`.
+async fn connect(info: &ConnectionInfo) -> io::Result<FramedConnection> {
+    let stream = transport::open(info).await?;
+
+    let mut framed = framed(stream);
+
+    for cmd in info.handshake() {
+        request(&mut framed, cmd, info.response_timeout).await?;
+    }
+
+    Ok(framed)
+}
+
+/// The reply to a PING sent with one of the `ping` methods.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pong {
+    /// The payload echoed back by the server.
+    pub payload: Bytes,
+    /// Time from sending the PING to reading its reply.
+    pub latency: Duration,
+}
+
+/// Extracts the payload from a reply to a PING that carried `sent`. Outside
+/// of subscribed mode the payload is echoed as a bulk string, or `+PONG`
+/// without one, so only that exact reply is taken for a PONG. A RESP2
+/// subscribed connection replies with a `["pong", payload]` array instead.
+fn pong_payload(frame: &RespValue, sent: Option<&[u8]>) -> Option<Bytes> {
+    match frame {
+        RespValue::SimpleString(s) if &**s == "PONG" && sent.is_none() => {
+            Some(Bytes::from_static(b"PONG"))
+        }
+        RespValue::BulkString(Some(buf)) if Some(&buf[..]) == sent => {
+            Some(Bytes::copy_from_slice(buf))
+        }
+        RespValue::Array(Some(items)) | RespValue::Push(items) => match &items[..] {
+            [ty, payload] if ty.as_str() == Some("pong") => match payload {
+                RespValue::BulkString(Some(buf)) => Some(Bytes::copy_from_slice(buf)),
+                _ => Some(Bytes::new()),
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Controls how a [`Receiver`] checks that the server is still alive.
+///
+/// A PING is sent every `interval`, regardless of how much traffic the
+/// connection is carrying. If the matching PONG has not arrived within
+/// `timeout` of the PING being sent, [`Receiver::next`] fails with
+/// [`io::ErrorKind::TimedOut`]. The timer is driven by reading from the
+/// receiver, through either [`Receiver::next`] or its [`Stream`] impl.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keepalive {
+    pub interval: Duration,
+    pub timeout: Duration,
+    /// Optional argument sent along with the PING, echoed back by the server.
+    pub payload: Option<Box<[u8]>>,
+}
+
+impl Default for Keepalive {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(10),
+            timeout: Duration::from_secs(10),
+            payload: None,
+        }
+    }
+}
+
+impl Keepalive {
+    fn ping(&self) -> RespValue {
+        match &self.payload {
+            Some(payload) => vec![bulk("PING"), bulk(payload)].into(),
+            None => vec![bulk("PING")].into(),
+        }
+    }
+
+    fn timer(&self) -> Interval {
+        let mut timer = time::interval_at(Instant::now() + self.interval, self.interval);
+        timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        timer
+    }
+}
+
+pub struct Receiver {
+    tx: SplitSink<FramedConnection, RespValue>,
+    rx: SplitStream<FramedConnection>,
+    keepalive: Keepalive,
+    ping_timer: Interval,
+    // the timer fired but the PING couldn't be queued yet
+    ping_due: bool,
+    // a PING was queued and the sink still needs flushing
+    ping_flush: bool,
+    // when the outstanding PING was sent, if any
+    ping_sent: Option<Instant>,
+    pong_deadline: Option<Pin<Box<Sleep>>>,
+    latency: Option<Duration>,
+    // the server closed the connection
+    closed: bool,
+    response_timeout: Option<Duration>,
+    subscriptions: Subscriptions,
+    // messages that arrived while waiting for a confirmation
+    pending: VecDeque<Message>,
+    // commands to run again after a RESET
+    handshake: Vec<RespValue>,
+    // PINGs waiting for a PONG, in the order they were sent
+    pings: VecDeque<PendingPing>,
+    // gets the invalidations redirected here, if any
+    cache: Option<ClientCache>,
+}
+
+enum PendingPing {
+    /// A keepalive PING, with the payload it carried.
+    Keepalive(Option<Bytes>),
+    /// Sent by [`Receiver::ping`] at the given instant, with its payload.
+    User(Instant, Bytes),
+}
+
+/// Rejects an empty channel list, which the server would take to mean
+/// every channel.
+fn non_empty(channels: impl IntoChannels) -> io::Result<Vec<Bytes>> {
+    let channels = channels.into_keys();
+
+    if channels.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "no channels given",
+        ));
+    }
+
+    Ok(channels)
+}
+
+/// A frame read by a [`Receiver`].
+enum Event {
+    Message(Message),
+    Confirmation(Confirmation),
+    /// The reply to a [`Receiver::ping`].
+    Pong(Pong),
+    /// Anything else, e.g. the reply to QUIT.
+    Reply(RespValue),
+}
+
+impl Sender {
+    pub async fn new(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Self::from_stream(TcpStream::connect(addr).await?))
+    }
+
+    #[cfg(unix)]
+    pub async fn connect_unix(path: impl AsRef<std::path::Path>) -> io::Result<Self> {
+        Ok(Self::from_stream(
+            tokio::net::UnixStream::connect(path).await?,
+        ))
+    }
+
+    /// Wraps an already established stream. No handshake is performed.
+    pub fn from_stream(stream: impl Transport) -> Self {
+        Self {
+            f_conn: framed(stream),
+            response_timeout: None,
+            transaction_retries: DEFAULT_TRANSACTION_RETRIES,
+        }
+    }
+
+    /// Creates a sender talking over an in-memory pipe, returning the other
+    /// end of the pipe for the caller to play the part of the server.
+    pub fn pipe(max_buf_size: usize) -> (Self, DuplexStream) {
+        let (client, server) = tokio::io::duplex(max_buf_size);
+        (Self::from_stream(client), server)
+    }
+
+    pub async fn connect(info: &ConnectionInfo) -> io::Result<Self> {
+        Ok(Self {
+            f_conn: connect(info).await?,
+            response_timeout: info.response_timeout,
+            transaction_retries: DEFAULT_TRANSACTION_RETRIES,
+        })
+    }
+
+    /// Sends an arbitrary command and returns the reply.
+    pub async fn command(&mut self, cmd: RespValue) -> io::Result<RespValue> {
+        request(&mut self.f_conn, cmd, self.response_timeout).await
+    }
+
+    pub async fn publish(
+        &mut self,
+        channel: impl AsRef<[u8]>,
+        mesg: impl AsRef<[u8]>,
+    ) -> io::Result<i64> {
+        let resp = vec![bulk("PUBLISH"), bulk(channel), bulk(mesg)].into();
+
+        match self.command(resp).await? {
+            RespValue::Integer(i) => Ok(i),
+            _ => Err(io::Error::from(io::ErrorKind::InvalidData)),
+        }
+    }
+
+    /// Sends a PING carrying `payload` and waits for the server to echo it,
+    /// measuring the round trip.
+    pub async fn ping(&mut self, payload: impl AsRef<[u8]>) -> io::Result<Pong> {
+        let sent = Instant::now();
+        let reply = self
+            .command(vec![bulk("PING"), bulk(&payload)].into())
+            .await?;
+        let latency = sent.elapsed();
+
+        let payload =
+            pong_payload(&reply, Some(payload.as_ref())).ok_or(io::ErrorKind::InvalidData)?;
+        Ok(Pong { payload, latency })
+    }
+
+    /// Sends QUIT and waits for the server to acknowledge it.
+    pub async fn quit(&mut self) -> io::Result<()> {
+        match self.command(vec![bulk("QUIT")].into()).await? {
+            RespValue::SimpleString(s) if &*s == "OK" => Ok(()),
+            _ => Err(io::Error::from(io::ErrorKind::InvalidData)),
+        }
+    }
+
+    /// Publishes to a shard channel, returning the number of subscribers
+    /// on the shard that received the message.
+    pub async fn spublish(
+        &mut self,
+        channel: impl AsRef<[u8]>,
+        mesg: impl AsRef<[u8]>,
+    ) -> io::Result<i64> {
+        let resp = vec![bulk("SPUBLISH"), bulk(channel), bulk(mesg)].into();
+
+        match self.command(resp).await? {
+            RespValue::Integer(i) => Ok(i),
+            _ => Err(io::Error::from(io::ErrorKind::InvalidData)),
+        }
+    }
+}
+
+impl Receiver {
+    pub async fn new(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Self::from_stream(TcpStream::connect(addr).await?))
+    }
+
+    #[cfg(unix)]
+    pub async fn connect_unix(path: impl AsRef<std::path::Path>) -> io::Result<Self> {
+        Ok(Self::from_stream(
+            tokio::net::UnixStream::connect(path).await?,
+        ))
+    }
+
+    /// Wraps an already established stream. No handshake is performed.
+    pub fn from_stream(stream: impl Transport) -> Self {
+        Self::from_framed(framed(stream))
+    }
+
+    /// Creates a receiver listening on an in-memory pipe, returning the other
+    /// end of the pipe for the caller to play the part of the server.
+    pub fn pipe(max_buf_size: usize) -> (Self, DuplexStream) {
+        let (client, server) = tokio::io::duplex(max_buf_size);
+        (Self::from_stream(client), server)
+    }
+
+    pub async fn connect(info: &ConnectionInfo) -> io::Result<Self> {
+        let mut receiver = Self::from_framed(connect(info).await?);
+        receiver.response_timeout = info.response_timeout;
+        receiver.handshake = info.handshake();
+
+        Ok(receiver)
+    }
+
+    fn from_framed(framed: FramedConnection) -> Self {
+        let (tx, rx) = framed.split();
+
+        let keepalive = Keepalive::default();
+        let ping_timer = keepalive.timer();
+
+        Self {
+            rx,
+            tx,
+            keepalive,
+            ping_timer,
+            ping_due: false,
+            ping_flush: false,
+            ping_sent: None,
+            pong_deadline: None,
+            latency: None,
+            closed: false,
+            response_timeout: None,
+            subscriptions: Subscriptions::default(),
+            pending: VecDeque::new(),
+            handshake: Vec::new(),
+            pings: VecDeque::new(),
+            cache: None,
+        }
+    }
+
+    /// The channels and patterns the server has confirmed.
+    pub fn subscriptions(&self) -> &Subscriptions {
+        &self.subscriptions
+    }
+
+    /// Replaces the keepalive settings, restarting the PING timer.
+    pub fn set_keepalive(&mut self, keepalive: Keepalive) {
+        self.ping_timer = keepalive.timer();
+        self.ping_due = false;
+        self.ping_sent = None;
+        self.pong_deadline = None;
+        self.keepalive = keepalive;
+    }
+
+    pub fn keepalive(&self) -> &Keepalive {
+        &self.keepalive
+    }
+
+    /// Round-trip time of the most recently answered keepalive PING.
+    pub fn latency(&self) -> Option<Duration> {
+        self.latency
+    }
+
+    fn received_pong(&mut self) {
+        if let Some(sent) = self.ping_sent.take() {
+            self.latency = Some(sent.elapsed());
+        }
+        self.pong_deadline = None;
+    }
+
+    /// Subscribes to one or more channels with a single command. Returns
+    /// the number of subscriptions active on the connection once the server
+    /// has confirmed every channel.
+    pub async fn subscribe(&mut self, channels: impl IntoChannels) -> io::Result<i64> {
+        let channels = non_empty(channels)?;
+        self.send_subscription(SubscriptionKind::Subscribe, channels)
+            .await
+    }
+
+    pub async fn unsubscribe(&mut self, channels: impl IntoChannels) -> io::Result<i64> {
+        let channels = non_empty(channels)?;
+        self.send_subscription(SubscriptionKind::Unsubscribe, channels)
+            .await
+    }
+
+    pub async fn unsubscribe_all(&mut self) -> io::Result<i64> {
+        self.send_subscription(SubscriptionKind::Unsubscribe, vec![])
+            .await
+    }
+
+    pub async fn psubscribe(&mut self, patterns: impl IntoChannels) -> io::Result<i64> {
+        let patterns = non_empty(patterns)?;
+        self.send_subscription(SubscriptionKind::PSubscribe, patterns)
+            .await
+    }
+
+    pub async fn punsubscribe(&mut self, patterns: impl IntoChannels) -> io::Result<i64> {
+        let patterns = non_empty(patterns)?;
+        self.send_subscription(SubscriptionKind::PUnsubscribe, patterns)
+            .await
+    }
+
+    pub async fn punsubscribe_all(&mut self) -> io::Result<i64> {
+        self.send_subscription(SubscriptionKind::PUnsubscribe, vec![])
+            .await
+    }
+
+    /// Subscribes to shard channels. In a cluster, every channel in a single
+    /// call must hash to the same slot.
+    pub async fn ssubscribe(&mut self, channels: impl IntoChannels) -> io::Result<i64> {
+        let channels = non_empty(channels)?;
+        self.send_subscription(SubscriptionKind::SSubscribe, channels)
+            .await
+    }
+
+    pub async fn sunsubscribe(&mut self, channels: impl IntoChannels) -> io::Result<i64> {
+        let channels = non_empty(channels)?;
+        self.send_subscription(SubscriptionKind::SUnsubscribe, channels)
+            .await
+    }
+
+    pub async fn sunsubscribe_all(&mut self) -> io::Result<i64> {
+        self.send_subscription(SubscriptionKind::SUnsubscribe, vec![])
+            .await
+    }
+
+    /// Sends a (un)subscribe command and waits for the server to confirm
+    /// every channel in it. An empty list means every current subscription
+    /// of that kind, as sent by the `_all` methods.
+    async fn send_subscription(
+        &mut self,
+        kind: SubscriptionKind,
+        channels: Vec<Bytes>,
+    ) -> io::Result<i64> {
+        let mut expected = channels.clone();
+
+        if expected.is_empty() {
+            expected = match kind {
+                SubscriptionKind::Unsubscribe => self.subscriptions.channels(),
+                SubscriptionKind::PUnsubscribe => self.subscriptions.patterns(),
+                SubscriptionKind::SUnsubscribe => self.subscriptions.shard_channels(),
+                _ => return Err(io::Error::from(io::ErrorKind::InvalidInput)),
+            }
+            .iter()
+            .cloned()
+            .collect();
+        }
+
+        let mut cmd = vec![bulk(kind.command())];
+        cmd.extend(channels.iter().map(bulk));
+        self.tx.send(cmd.into()).await?;
+
+        with_timeout(self.response_timeout, self.confirm(kind, expected)).await
+    }
+
+    /// Reads frames until each channel in `expected` has been confirmed,
+    /// queueing any messages that arrive in the meantime. An empty list
+    /// waits for a single confirmation of `kind`.
+    async fn confirm(
+        &mut self,
+        kind: SubscriptionKind,
+        mut expected: Vec<Bytes>,
+    ) -> io::Result<i64> {
+        let any = expected.is_empty();
+
+        loop {
+            let confirmation = match self.read_event().await? {
+                Event::Message(mesg) => {
+                    self.pending.push_back(mesg);
+                    continue;
+                }
+                Event::Confirmation(confirmation) => confirmation,
+                Event::Pong(_) => continue,
+                Event::Reply(reply) => {
+                    error_reply(reply)?;
+                    return Err(io::Error::from(io::ErrorKind::InvalidData));
+                }
+            };
+
+            if confirmation.kind != kind {
+                continue;
+            }
+
+            match &confirmation.channel {
+                Some(channel) => {
+                    if let Some(i) = expected.iter().position(|c| c == channel) {
+                        expected.swap_remove(i);
+                    }
+                }
+                // nothing was subscribed, so this is the only reply
+                None => expected.clear(),
+            }
+
+            if any || expected.is_empty() {
+                return Ok(confirmation.count);
+            }
+        }
+    }
+
+    /// Sends a PING carrying `payload` and waits for the server to echo it,
+    /// measuring the round trip. Works both before and after subscribing.
+    /// Messages that arrive in the meantime are still returned by
+    /// [`Receiver::next`].
+    pub async fn ping(&mut self, payload: impl AsRef<[u8]>) -> io::Result<Pong> {
+        let payload = Bytes::copy_from_slice(payload.as_ref());
+        self.tx
+            .send(vec![bulk("PING"), bulk(&payload)].into())
+            .await?;
+        self.pings
+            .push_back(PendingPing::User(Instant::now(), payload));
+
+        with_timeout(self.response_timeout, async {
+            loop {
+                match self.read_event().await? {
+                    Event::Message(mesg) => self.pending.push_back(mesg),
+                    Event::Confirmation(_) => continue,
+                    Event::Pong(pong) => return Ok(pong),
+                    Event::Reply(reply) => {
+                        // an error reply means no PONG is coming for this PING
+                        self.pings.pop_front();
+                        error_reply(reply)?;
+                        return Err(io::Error::from(io::ErrorKind::InvalidData));
+                    }
+                }
+            }
+        })
+        .await
+    }
+
+    /// Sends QUIT and waits for the server to acknowledge it. Messages that
+    /// arrived beforehand are still returned by [`Receiver::next`], after
+    /// which the stream ends.
+    pub async fn quit(&mut self) -> io::Result<()> {
+        self.tx.send(vec![bulk("QUIT")].into()).await?;
+
+        with_timeout(self.response_timeout, self.expect_reply("OK")).await
+    }
+
+    /// Sends RESET and waits for `+RESET`, leaving subscribed mode. The
+    /// server drops every subscription, but messages that arrived beforehand
+    /// are still returned by [`Receiver::next`].
+    ///
+    /// RESET also reverts authentication, the selected database and the
+    /// client name, so a receiver opened with [`Receiver::connect`] runs its
+    /// handshake again afterwards.
+    pub async fn reset(&mut self) -> io::Result<()> {
+        self.tx.send(vec![bulk("RESET")].into()).await?;
+
+        with_timeout(self.response_timeout, async {
+            self.expect_reply("RESET").await?;
+            self.subscriptions = Subscriptions::default();
+
+            // no longer subscribed, so the replies are read as raw frames like
+            // in connect, a RESP2 HELLO reply would look like a pub/sub event
+            for cmd in self.handshake.clone() {
+                self.tx.send(cmd).await?;
+
+                let reply = self.rx.next().await.ok_or(io::ErrorKind::BrokenPipe)??;
+                error_reply(reply)?;
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
+    /// Asks for the connection's ID with CLIENT ID, e.g. to redirect client
+    /// tracking invalidations here. Under RESP2 this only works before
+    /// subscribing.
+    pub async fn client_id(&mut self) -> io::Result<i64> {
+        self.tx
+            .send(vec![bulk("CLIENT"), bulk("ID")].into())
+            .await?;
+
+        with_timeout(self.response_timeout, async {
+            loop {
+                match self.read_event().await? {
+                    Event::Message(mesg) => self.pending.push_back(mesg),
+                    Event::Confirmation(_) | Event::Pong(_) => continue,
+                    Event::Reply(RespValue::Integer(id)) => return Ok(id),
+                    Event::Reply(reply) => {
+                        error_reply(reply)?;
+                        return Err(io::Error::from(io::ErrorKind::InvalidData));
+                    }
+                }
+            }
+        })
+        .await
+    }
+
+    /// Applies the invalidations received from now on to `cache`.
+    pub(crate) fn set_cache(&mut self, cache: ClientCache) {
+        self.cache = Some(cache);
+    }
+
+    /// Reads frames until the simple string `expected`, queueing any
+    /// messages that arrive in the meantime.
+    async fn expect_reply(&mut self, expected: &str) -> io::Result<()> {
+        loop {
+            match self.read_event().await? {
+                Event::Message(mesg) => self.pending.push_back(mesg),
+                Event::Confirmation(_) | Event::Pong(_) => continue,
+                Event::Reply(RespValue::SimpleString(s)) if &*s == expected => return Ok(()),
+                Event::Reply(reply) => {
+                    error_reply(reply)?;
+                    return Err(io::Error::from(io::ErrorKind::InvalidData));
+                }
+            }
+        }
+    }
+
+    /// Returns the next message. Fails with [`io::ErrorKind::BrokenPipe`]
+    /// once the connection is closed.
+    pub async fn next(&mut self) -> io::Result<Message> {
+        future::poll_fn(|cx| self.poll_next_unpin(cx))
+            .await
+            .ok_or(io::ErrorKind::BrokenPipe)?
+    }
+
+    async fn read_event(&mut self) -> io::Result<Event> {
+        future::poll_fn(|cx| self.poll_event(cx))
+            .await
+            .ok_or(io::ErrorKind::BrokenPipe)?
+    }
+
+    /// Sends a keepalive PING when one is due, without waiting on a slow
+    /// socket: anything left unfinished is picked up on the next poll.
+    fn poll_keepalive(&mut self, cx: &mut Context<'_>) -> io::Result<()> {
+        // don't stack PINGs, the deadline covers the outstanding one
+        if self.ping_timer.poll_tick(cx).is_ready() && self.ping_sent.is_none() {
+            self.ping_due = true;
+        }
+
+        if self.ping_due {
+            if let Poll::Ready(res) = self.tx.poll_ready_unpin(cx) {
+                res?;
+                self.tx.start_send_unpin(self.keepalive.ping())?;
+
+                let now = Instant::now();
+                self.ping_sent = Some(now);
+                let payload = self.keepalive.payload.as_deref();
+                self.pings
+                    .push_back(PendingPing::Keepalive(payload.map(Bytes::copy_from_slice)));
+                self.pong_deadline =
+                    Some(Box::pin(time::sleep_until(now + self.keepalive.timeout)));
+                self.ping_due = false;
+                self.ping_flush = true;
+            }
+        }
+
+        if self.ping_flush {
+            if let Poll::Ready(res) = self.tx.poll_flush_unpin(cx) {
+                res?;
+                self.ping_flush = false;
+            }
+        }
+
+        if let Some(deadline) = &mut self.pong_deadline {
+            if deadline.as_mut().poll(cx).is_ready() {
+                return Err(io::Error::from(io::ErrorKind::TimedOut));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Polls for the next message, confirmation or reply, answering
+    /// keepalive PONGs along the way. Returns None once the connection is
+    /// closed.
+    fn poll_event(&mut self, cx: &mut Context<'_>) -> Poll<Option<io::Result<Event>>> {
+        loop {
+            if self.closed {
+                return Poll::Ready(None);
+            }
+
+            if let Err(e) = self.poll_keepalive(cx) {
+                self.drop_cache();
+                return Poll::Ready(Some(Err(e)));
+            }
+
+            let frame = match ready!(self.rx.poll_next_unpin(cx)) {
+                Some(Ok(frame)) => frame,
+                Some(Err(e)) => {
+                    self.drop_cache();
+                    return Poll::Ready(Some(Err(e)));
+                }
+                None => {
+                    self.drop_cache();
+                    self.closed = true;
+                    return Poll::Ready(None);
+                }
+            };
+
+            // without a cache they are ordinary messages
+            if let Some(cache) = &self.cache {
+                if let Some(invalidation) = Invalidation::from_frame(&frame) {
+                    cache.invalidate(&invalidation);
+                    continue;
+                }
+            }
+
+            // a PONG can only answer the oldest outstanding PING
+            let pong = match self.pings.front() {
+                Some(PendingPing::Keepalive(sent)) => pong_payload(&frame, sent.as_deref()),
+                Some(PendingPing::User(_, sent)) => pong_payload(&frame, Some(sent)),
+                None => None,
+            };
+
+            if let Some(payload) = pong {
+                match self.pings.pop_front() {
+                    Some(PendingPing::User(sent, _)) => {
+                        let latency = sent.elapsed();
+                        return Poll::Ready(Some(Ok(Event::Pong(Pong { payload, latency }))));
+                    }
+                    _ => self.received_pong(),
+                }
+                continue;
+            }
+
+            return Poll::Ready(Some(self.parse_event(frame)));
+        }
+    }
+
+    /// Empties the cache once invalidations may have been missed, and
+    /// detaches it.
+    fn drop_cache(&mut self) {
+        if let Some(cache) = self.cache.take() {
+            cache.clear();
+        }
+    }
+
+    /// Turns a frame into an event.
+    fn parse_event(&mut self, frame: RespValue) -> io::Result<Event> {
+        // RESP3 connections deliver pub/sub events as push frames
+        let items = match frame {
+            RespValue::Array(Some(items)) | RespValue::Push(items) => items,
+            frame => return Ok(Event::Reply(frame)),
+        };
+
+        let ty = items.first().and_then(RespValue::as_str);
+
+        if let Some(kind) = ty.and_then(SubscriptionKind::from_frame_type) {
+            let confirmation = Confirmation::from_items(kind, items.into_iter().skip(1))?;
+            self.subscriptions.apply(&confirmation);
+
+            return Ok(Event::Confirmation(confirmation));
+        }
+
+        let kind = ty
+            .and_then(MessageKind::from_frame_type)
+            .ok_or(io::ErrorKind::InvalidData)?;
+
+        Ok(Event::Message(Message::from_items(
+            kind,
+            items.into_iter().skip(1),
+        )?))
+    }
+}
+
+/// Yields pub/sub messages, skipping subscription confirmations. The stream
+/// ends when the server closes the connection, e.g. after [`Receiver::quit`].
+impl Stream for Receiver {
+    type Item = io::Result<Message>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        if let Some(mesg) = this.pending.pop_front() {
+            return Poll::Ready(Some(Ok(mesg)));
+        }
+
+        loop {
+            let event = match ready!(this.poll_event(cx)) {
+                Some(Ok(event)) => event,
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => return Poll::Ready(None),
+            };
+
+            match event {
+                Event::Message(mesg) => return Poll::Ready(Some(Ok(mesg))),
+                // replies to a ping() that was given up on
+                Event::Confirmation(_) | Event::Pong(_) => continue,
+                Event::Reply(_) => {
+                    return Poll::Ready(Some(Err(io::Error::from(io::ErrorKind::InvalidData))))
+                }
+            }
+        }
+    }
+}
+
+pub struct Client {
+    sender: Sender,
+    receiver: Receiver,
+}
+
+impl Client {
+    pub async fn new(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let (sender, receiver) = tokio::join!(Sender::new(&addr), Receiver::new(&addr));
+
+        Ok(Self {
+            sender: sender?,
+            receiver: receiver?,
+        })
+    }
+
+    #[cfg(unix)]
+    pub async fn connect_unix(path: impl AsRef<std::path::Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let (sender, receiver) =
+            tokio::join!(Sender::connect_unix(path), Receiver::connect_unix(path));
+
+        Ok(Self {
+            sender: sender?,
+            receiver: receiver?,
+        })
+    }
+
+    pub async fn connect(info: &ConnectionInfo) -> io::Result<Self> {
+        let (sender, receiver) = tokio::join!(Sender::connect(info), Receiver::connect(info));
+
+        Ok(Self {
+            sender: sender?,
+            receiver: receiver?,
+        })
+    }
+
+    pub async fn publish(
+        &mut self,
+        channel: impl AsRef<[u8]>,
+        mesg: impl AsRef<[u8]>,
+    ) -> io::Result<i64> {
+        self.sender.publish(channel, mesg).await
+    }
+
+    pub async fn spublish(
+        &mut self,
+        channel: impl AsRef<[u8]>,
+        mesg: impl AsRef<[u8]>,
+    ) -> io::Result<i64> {
+        self.sender.spublish(channel, mesg).await
+    }
+
+    pub async fn subscribe(&mut self, channels: impl IntoChannels) -> io::Result<i64> {
+        self.receiver.subscribe(channels).await
+    }
+
+    pub async fn unsubscribe(&mut self, channels: impl IntoChannels) -> io::Result<i64> {
+        self.receiver.unsubscribe(channels).await
+    }
+
+    pub async fn unsubscribe_all(&mut self) -> io::Result<i64> {
+        self.receiver.unsubscribe_all().await
+    }
+
+    pub async fn psubscribe(&mut self, patterns: impl IntoChannels) -> io::Result<i64> {
+        self.receiver.psubscribe(patterns).await
+    }
+
+    pub async fn punsubscribe(&mut self, patterns: impl IntoChannels) -> io::Result<i64> {
+        self.receiver.punsubscribe(patterns).await
+    }
+
+    pub async fn punsubscribe_all(&mut self) -> io::Result<i64> {
+        self.receiver.punsubscribe_all().await
+    }
+
+    pub async fn ssubscribe(&mut self, channels: impl IntoChannels) -> io::Result<i64> {
+        self.receiver.ssubscribe(channels).await
+    }
+
+    pub async fn sunsubscribe(&mut self, channels: impl IntoChannels) -> io::Result<i64> {
+        self.receiver.sunsubscribe(channels).await
+    }
+
+    pub async fn sunsubscribe_all(&mut self) -> io::Result<i64> {
+        self.receiver.sunsubscribe_all().await
+    }
+
+    pub async fn next(&mut self) -> io::Result<Message> {
+        self.receiver.next().await
+    }
+
+    pub fn set_keepalive(&mut self, keepalive: Keepalive) {
+        self.receiver.set_keepalive(keepalive)
+    }
+
+    pub fn latency(&self) -> Option<Duration> {
+        self.receiver.latency()
+    }
+
+    pub fn subscriptions(&self) -> &Subscriptions {
+        self.receiver.subscriptions()
+    }
+
+    /// Closes both connections. Messages already received are still
+    /// returned by [`Client::next`], after which the stream ends.
+    pub async fn quit(&mut self) -> io::Result<()> {
+        let (sender, receiver) = tokio::join!(self.sender.quit(), self.receiver.quit());
+        sender.and(receiver)
+    }
+
+    /// Pings over the command connection, see [`Sender::ping`].
+    pub async fn ping(&mut self, payload: impl AsRef<[u8]>) -> io::Result<Pong> {
+        self.sender.ping(payload).await
+    }
+
+    /// Leaves subscribed mode, see [`Receiver::reset`].
+    pub async fn reset(&mut self) -> io::Result<()> {
+        self.receiver.reset().await
+    }
+
+    pub fn split(self) -> (Sender, Receiver) {
+        (self.sender, self.receiver)
+    }
+
+    pub fn join(sender: Sender, receiver: Receiver) -> Self {
+        Self { sender, receiver }
+    }
+}
+
+impl Stream for Client {
+    type Item = io::Result<Message>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.get_mut().receiver).poll_next(cx)
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use futures::Stream;
+use tokio::sync::{mpsc, oneshot};
+
+use super::{ConnectionInfo, Message, MessageKind, Receiver};
+
+/// What a [`Subscription`] listens to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Target {
+    Channel,
+    Pattern,
+    ShardChannel,
+}
+
+type Key = (Target, Bytes);
+
+type Handle = mpsc::UnboundedSender<io::Result<Message>>;
+
+enum Command {
+    Subscribe {
+        key: Key,
+        id: u64,
+        tx: Handle,
+        done: oneshot::Sender<io::Result<()>>,
+    },
+    /// Sent when a [`Subscription`] is dropped.
+    Release { key: Key, id: u64 },
+}
+
+/// Shares one subscribed connection between many tasks.
+///
+/// A background task owns the [`Receiver`] and routes every message to the
+/// [`Subscription`] handles listening on its channel or pattern. The server
+/// is only asked to subscribe when the first handle for a channel is
+/// created, and to unsubscribe once the last one is dropped.
+///
+/// Must be created from within a tokio runtime.
+#[derive(Clone)]
+pub struct PubSub {
+    cmd: mpsc::UnboundedSender<Command>,
+}
+
+impl PubSub {
+    pub fn new(receiver: Receiver) -> Self {
+        let (cmd, cmd_rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(Dispatcher::new(receiver).run(cmd_rx));
+
+        Self { cmd }
+    }
+
+    pub async fn connect(info: &ConnectionInfo) -> io::Result<Self> {
+        Ok(Self::new(Receiver::connect(info).await?))
+    }
+
+    pub async fn subscribe(&self, channel: impl AsRef<[u8]>) -> io::Result<Subscription> {
+        self.add(Target::Channel, channel.as_ref()).await
+    }
+
+    pub async fn psubscribe(&self, pattern: impl AsRef<[u8]>) -> io::Result<Subscription> {
+        self.add(Target::Pattern, pattern.as_ref()).await
+    }
+
+    pub async fn ssubscribe(&self, channel: impl AsRef<[u8]>) -> io::Result<Subscription> {
+        self.add(Target::ShardChannel, channel.as_ref()).await
+    }
+
+    async fn add(&self, target: Target, channel: &[u8]) -> io::Result<Subscription> {
+        let key = (target, Bytes::copy_from_slice(channel));
+        let id = next_id();
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let (done, done_rx) = oneshot::channel();
+
+        self.cmd
+            .send(Command::Subscribe {
+                key: key.clone(),
+                id,
+                tx,
+                done,
+            })
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+
+        // create the handle first, so it is released even if the caller
+        // gives up waiting for the confirmation
+        let subscription = Subscription {
+            key,
+            id,
+            rx,
+            cmd: self.cmd.clone(),
+        };
+
+        done_rx.await.map_err(|_| io::ErrorKind::BrokenPipe)??;
+
+        Ok(subscription)
+    }
+}
+
+fn next_id() -> u64 {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+/// A stream of the messages for one channel, pattern or shard channel of a
+/// [`PubSub`]. The stream ends if the shared connection is lost, after
+/// yielding the error that caused it.
+pub struct Subscription {
+    key: Key,
+    id: u64,
+    rx: mpsc::UnboundedReceiver<io::Result<Message>>,
+    cmd: mpsc::UnboundedSender<Command>,
+}
+
+impl Subscription {
+    /// The channel or pattern this handle was created for.
+    pub fn channel(&self) -> &Bytes {
+        &self.key.1
+    }
+}
+
+impl Stream for Subscription {
+    type Item = io::Result<Message>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().rx.poll_recv(cx)
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let _ = self.cmd.send(Command::Release {
+            key: self.key.clone(),
+            id: self.id,
+        });
+    }
+}
+
+/// The background task behind a [`PubSub`].
+struct Dispatcher {
+    receiver: Receiver,
+    handles: HashMap<Key, Vec<(u64, Handle)>>,
+}
+
+impl Dispatcher {
+    fn new(receiver: Receiver) -> Self {
+        Self {
+            receiver,
+            handles: HashMap::new(),
+        }
+    }
+
+    async fn run(mut self, mut cmd_rx: mpsc::UnboundedReceiver<Command>) {
+        loop {
+            let res = tokio::select! {
+                cmd = cmd_rx.recv() => match cmd {
+                    Some(cmd) => self.handle(cmd).await,
+                    // the PubSub and every Subscription are gone
+                    None => return,
+                },
+                mesg = self.receiver.next() => mesg.map(|mesg| self.route(mesg)),
+            };
+
+            // only transport errors get here, server error replies go to the
+            // request that caused them
+            if let Err(e) = res {
+                self.fail(e);
+                return;
+            }
+        }
+    }
+
+    async fn handle(&mut self, cmd: Command) -> io::Result<()> {
+        match cmd {
+            Command::Subscribe { key, id, tx, done } => {
+                if !self.handles.contains_key(&key) {
+                    match self.server_subscribe(&key).await {
+                        Ok(_) => {}
+                        // the server refused this channel, the connection is fine
+                        Err(e) if e.kind() == io::ErrorKind::Other => {
+                            let _ = done.send(Err(e));
+                            return Ok(());
+                        }
+                        Err(e) => {
+                            let _ = done.send(Err(copy_error(&e)));
+                            return Err(e);
+                        }
+                    }
+                }
+
+                self.handles.entry(key).or_default().push((id, tx));
+                let _ = done.send(Ok(()));
+            }
+            Command::Release { key, id } => {
+                let Some(handles) = self.handles.get_mut(&key) else {
+                    return Ok(());
+                };
+
+                handles.retain(|(handle, _)| *handle != id);
+
+                if handles.is_empty() {
+                    self.handles.remove(&key);
+                    match self.server_unsubscribe(&key).await {
+                        Err(e) if e.kind() != io::ErrorKind::Other => return Err(e),
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn server_subscribe(&mut self, (target, channel): &Key) -> io::Result<i64> {
+        let channel = channel.clone();
+
+        match target {
+            Target::Channel => self.receiver.subscribe(channel).await,
+            Target::Pattern => self.receiver.psubscribe(channel).await,
+            Target::ShardChannel => self.receiver.ssubscribe(channel).await,
+        }
+    }
+
+    async fn server_unsubscribe(&mut self, (target, channel): &Key) -> io::Result<i64> {
+        let channel = channel.clone();
+
+        match target {
+            Target::Channel => self.receiver.unsubscribe(channel).await,
+            Target::Pattern => self.receiver.punsubscribe(channel).await,
+            Target::ShardChannel => self.receiver.sunsubscribe(channel).await,
+        }
+    }
+
+    fn route(&mut self, mesg: Message) {
+        let key = match (mesg.kind, &mesg.pattern) {
+            // a publish matching several patterns arrives once for each of
+            // them, so every copy only goes to the pattern it names
+            (MessageKind::PMessage, Some(pattern)) => (Target::Pattern, pattern.clone()),
+            (MessageKind::SMessage, _) => (Target::ShardChannel, mesg.channel.clone()),
+            _ => (Target::Channel, mesg.channel.clone()),
+        };
+
+        // handles that were dropped are cleaned up by their Release command
+        for (_, tx) in self.handles.get(&key).into_iter().flatten() {
+            let _ = tx.send(Ok(mesg.clone()));
+        }
+    }
+
+    /// Hands a fatal connection error to every handle, ending their streams.
+    fn fail(&mut self, e: io::Error) {
+        for (_, tx) in self.handles.drain().flat_map(|(_, handles)| handles) {
+            let _ = tx.send(Err(copy_error(&e)));
+        }
+    }
+}
+
+fn copy_error(e: &io::Error) -> io::Error {
+    io::Error::new(e.kind(), e.to_string())
+}
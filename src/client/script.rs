@@ -0,0 +1,124 @@
+use std::fmt::Write;
+use std::io;
+
+use sha1::{Digest, Sha1};
+
+use super::Sender;
+use crate::resp::convert::{FromResp, ToArg};
+use crate::resp::value::*;
+
+/// The keys and arguments passed to a [`Script`] or a function called with
+/// [`Sender::fcall`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScriptArgs {
+    keys: Vec<RespValue>,
+    args: Vec<RespValue>,
+}
+
+impl ScriptArgs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a key, available to the script as `KEYS[n]`.
+    pub fn key(mut self, key: impl ToArg) -> Self {
+        self.keys.push(key.to_arg());
+        self
+    }
+
+    /// Adds an argument, available to the script as `ARGV[n]`.
+    pub fn arg(mut self, arg: impl ToArg) -> Self {
+        self.args.push(arg.to_arg());
+        self
+    }
+
+    /// Builds `<command> <target> <numkeys> keys... args...`.
+    fn command(&self, name: &str, target: impl AsRef<[u8]>) -> RespValue {
+        let mut cmd = Vec::with_capacity(3 + self.keys.len() + self.args.len());
+
+        cmd.push(bulk(name));
+        cmd.push(bulk(target));
+        cmd.push(bulk(self.keys.len().to_string()));
+        cmd.extend(self.keys.iter().cloned());
+        cmd.extend(self.args.iter().cloned());
+
+        cmd.into()
+    }
+}
+
+/// A Lua script run with EVALSHA, so its body is only sent to the server
+/// when the server doesn't have it cached yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Script {
+    code: String,
+    hash: String,
+}
+
+impl Script {
+    pub fn new(code: impl Into<String>) -> Self {
+        let code = code.into();
+
+        let mut hash = String::with_capacity(40);
+        for byte in Sha1::digest(code.as_bytes()) {
+            let _ = write!(hash, "{:02x}", byte);
+        }
+
+        Self { code, hash }
+    }
+
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    /// The hex SHA1 of the body, as used by EVALSHA.
+    pub fn hash(&self) -> &str {
+        &self.hash
+    }
+
+    /// Loads the script into the server's cache with SCRIPT LOAD.
+    pub async fn load(&self, conn: &mut Sender) -> io::Result<()> {
+        let cmd = vec![bulk("SCRIPT"), bulk("LOAD"), bulk(&self.code)].into();
+
+        match conn.command(cmd).await? {
+            reply if reply.as_str() == Some(&self.hash) => Ok(()),
+            _ => Err(io::Error::from(io::ErrorKind::InvalidData)),
+        }
+    }
+
+    /// Runs the script with EVALSHA. If the server replies with NOSCRIPT, e.g.
+    /// after a restart or SCRIPT FLUSH, the script is loaded and run again.
+    pub async fn invoke<T: FromResp>(&self, conn: &mut Sender, args: &ScriptArgs) -> io::Result<T> {
+        let cmd = args.command("EVALSHA", &self.hash);
+
+        let reply = match conn.command(cmd.clone()).await {
+            Err(e) if is_noscript(&e) => {
+                self.load(conn).await?;
+                conn.command(cmd).await?
+            }
+            reply => reply?,
+        };
+
+        T::from_resp(reply)
+    }
+}
+
+fn is_noscript(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::Other && e.to_string().starts_with("NOSCRIPT")
+}
+
+impl Sender {
+    /// Calls a Redis 7 function loaded with FUNCTION LOAD.
+    pub async fn fcall<T: FromResp>(&mut self, function: &str, args: &ScriptArgs) -> io::Result<T> {
+        T::from_resp(self.command(args.command("FCALL", function)).await?)
+    }
+
+    /// Calls a function flagged `no-writes` with FCALL_RO, which replicas
+    /// accept as well.
+    pub async fn fcall_ro<T: FromResp>(
+        &mut self,
+        function: &str,
+        args: &ScriptArgs,
+    ) -> io::Result<T> {
+        T::from_resp(self.command(args.command("FCALL_RO", function)).await?)
+    }
+}
@@ -0,0 +1,427 @@
+use std::io;
+use std::time::Duration;
+
+use tokio::sync::watch;
+use tokio::time;
+
+use super::{ConnectionAddr, ConnectionInfo, Message, Receiver, Sender};
+use crate::resp::convert::FromResp;
+use crate::resp::value::*;
+
+/// The sentinel channel announcing that a new primary was promoted.
+pub const SWITCH_MASTER_CHANNEL: &str = "+switch-master";
+
+/// How long to wait before trying the sentinels again once the connection
+/// watching for failovers is lost.
+const WATCH_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Finds the primary of a service monitored by Redis Sentinel.
+///
+/// The sentinels are asked in order with `SENTINEL get-master-addr-by-name`,
+/// and the address reported is only trusted once the server there confirms
+/// with ROLE that it is a primary. Otherwise the next sentinel is asked.
+///
+/// [`Sentinel::sender`] and [`Sentinel::receiver`] return connections that
+/// follow failovers, as announced on [`SWITCH_MASTER_CHANNEL`].
+#[derive(Debug, Clone)]
+pub struct Sentinel {
+    service: String,
+    sentinels: Vec<ConnectionInfo>,
+    info: ConnectionInfo,
+}
+
+impl Sentinel {
+    /// Finds the primary of `service` through `sentinels`. The primary is
+    /// connected to with the settings in `info`, such as credentials and
+    /// TLS, with its address replaced.
+    pub fn new(
+        service: impl Into<String>,
+        sentinels: Vec<ConnectionInfo>,
+        info: ConnectionInfo,
+    ) -> Self {
+        Self {
+            service: service.into(),
+            sentinels,
+            info,
+        }
+    }
+
+    pub fn service(&self) -> &str {
+        &self.service
+    }
+
+    /// The primary's address according to the first sentinel that knows
+    /// the service.
+    pub async fn primary_addr(&self) -> io::Result<(String, u16)> {
+        let mut last_err = no_sentinel();
+
+        for sentinel in &self.sentinels {
+            match self.ask(sentinel).await {
+                Ok(addr) => return Ok(addr),
+                Err(e) => last_err = e,
+            }
+        }
+
+        Err(last_err)
+    }
+
+    /// Connects a [`Sender`] to the primary.
+    pub async fn connect_sender(&self) -> io::Result<Sender> {
+        self.connect_primary(async |info| {
+            let mut sender = Sender::connect(&info).await?;
+            expect_primary(sender.command(role()).await?)?;
+            Ok(sender)
+        })
+        .await
+    }
+
+    /// Connects a [`Receiver`] to the primary.
+    pub async fn connect_receiver(&self) -> io::Result<Receiver> {
+        self.connect_primary(async |info| {
+            // a receiver can't read the ROLE reply, so ask on the side
+            let mut sender = Sender::connect(&info).await?;
+            expect_primary(sender.command(role()).await?)?;
+            Receiver::connect(&info).await
+        })
+        .await
+    }
+
+    /// Starts watching the sentinels for failovers of the service. The
+    /// watch stops once every [`Failovers`] handle is dropped.
+    pub async fn watch(&self) -> io::Result<Failovers> {
+        let receiver = self.subscribe_switch().await?;
+        let (tx, rx) = watch::channel(None);
+
+        tokio::spawn(self.clone().run_watch(receiver, tx));
+
+        Ok(Failovers { rx })
+    }
+
+    /// A [`Sender`] to the primary that reconnects after failovers.
+    pub async fn sender(&self) -> io::Result<SentinelSender> {
+        let failovers = self.watch().await?;
+        let sender = self.connect_sender().await?;
+
+        Ok(SentinelSender {
+            sentinel: self.clone(),
+            sender: Some(sender),
+            failovers,
+        })
+    }
+
+    /// A [`Receiver`] to the primary that reconnects and subscribes again
+    /// after failovers.
+    pub async fn receiver(&self) -> io::Result<SentinelReceiver> {
+        let failovers = self.watch().await?;
+        let receiver = self.connect_receiver().await?;
+
+        Ok(SentinelReceiver {
+            sentinel: self.clone(),
+            receiver,
+            stale: false,
+            failovers,
+        })
+    }
+
+    async fn ask(&self, sentinel: &ConnectionInfo) -> io::Result<(String, u16)> {
+        let mut conn = Sender::connect(sentinel).await?;
+
+        let cmd = vec![
+            bulk("SENTINEL"),
+            bulk("get-master-addr-by-name"),
+            bulk(&self.service),
+        ];
+        let reply = conn.command(cmd.into()).await?;
+
+        let Some(addr) = Option::<Vec<String>>::from_resp(reply)? else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("unknown service {}", self.service),
+            ));
+        };
+        let [host, port] = &addr[..] else {
+            return Err(io::Error::from(io::ErrorKind::InvalidData));
+        };
+        let port = port
+            .parse()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid port"))?;
+
+        Ok((host.clone(), port))
+    }
+
+    async fn connect_primary<T>(
+        &self,
+        connect: impl AsyncFn(ConnectionInfo) -> io::Result<T>,
+    ) -> io::Result<T> {
+        let mut last_err = no_sentinel();
+
+        for sentinel in &self.sentinels {
+            let conn = match self.ask(sentinel).await {
+                Ok(addr) => connect(self.primary_info(addr)).await,
+                Err(e) => Err(e),
+            };
+
+            match conn {
+                Ok(conn) => return Ok(conn),
+                Err(e) => last_err = e,
+            }
+        }
+
+        Err(last_err)
+    }
+
+    fn primary_info(&self, (host, port): (String, u16)) -> ConnectionInfo {
+        let mut info = self.info.clone();
+
+        info.addr = match info.addr {
+            ConnectionAddr::TcpTls { .. } => ConnectionAddr::TcpTls { host, port },
+            _ => ConnectionAddr::Tcp { host, port },
+        };
+
+        info
+    }
+
+    async fn subscribe_switch(&self) -> io::Result<Receiver> {
+        let mut last_err = no_sentinel();
+
+        for sentinel in &self.sentinels {
+            let receiver = async {
+                let mut receiver = Receiver::connect(sentinel).await?;
+                receiver.subscribe(SWITCH_MASTER_CHANNEL).await?;
+                io::Result::Ok(receiver)
+            };
+
+            match receiver.await {
+                Ok(receiver) => return Ok(receiver),
+                Err(e) => last_err = e,
+            }
+        }
+
+        Err(last_err)
+    }
+
+    async fn run_watch(self, mut receiver: Receiver, tx: watch::Sender<Option<(String, u16)>>) {
+        loop {
+            let mesg = tokio::select! {
+                _ = tx.closed() => return,
+                mesg = receiver.next() => mesg,
+            };
+
+            match mesg {
+                Ok(mesg) => {
+                    if let Some(addr) = self.switched_to(&mesg) {
+                        tx.send_replace(Some(addr));
+                    }
+                }
+                Err(_) => {
+                    receiver = loop {
+                        tokio::select! {
+                            _ = tx.closed() => return,
+                            _ = time::sleep(WATCH_RETRY_DELAY) => {}
+                        }
+
+                        if let Ok(receiver) = self.subscribe_switch().await {
+                            break receiver;
+                        }
+                    };
+
+                    // a failover may have been missed meanwhile
+                    tx.send_modify(|_| {});
+                }
+            }
+        }
+    }
+
+    /// Parses a `+switch-master` announcement for this service, whose
+    /// payload is `<service> <old host> <old port> <new host> <new port>`.
+    fn switched_to(&self, mesg: &Message) -> Option<(String, u16)> {
+        if mesg.channel != SWITCH_MASTER_CHANNEL.as_bytes() {
+            return None;
+        }
+
+        let payload = std::str::from_utf8(&mesg.payload).ok()?;
+        match payload.split(' ').collect::<Vec<_>>()[..] {
+            [service, _, _, host, port] if service == self.service => {
+                Some((host.to_string(), port.parse().ok()?))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn no_sentinel() -> io::Error {
+    io::Error::new(io::ErrorKind::NotConnected, "no sentinel answered")
+}
+
+fn role() -> RespValue {
+    vec![bulk("ROLE")].into()
+}
+
+/// Checks a ROLE reply, which starts with `master` on a primary.
+fn expect_primary(reply: RespValue) -> io::Result<()> {
+    match reply {
+        RespValue::Array(Some(items))
+            if items.first().and_then(RespValue::as_str) == Some("master") =>
+        {
+            Ok(())
+        }
+        RespValue::Array(Some(_)) => Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            "not a primary",
+        )),
+        _ => Err(io::Error::from(io::ErrorKind::InvalidData)),
+    }
+}
+
+/// Notified when the sentinels announce a new primary, see
+/// [`Sentinel::watch`].
+#[derive(Debug, Clone)]
+pub struct Failovers {
+    rx: watch::Receiver<Option<(String, u16)>>,
+}
+
+impl Failovers {
+    /// Waits for the next failover. Also returns when the connection to the
+    /// sentinels was lost and restored, as a failover may have been missed.
+    pub async fn changed(&mut self) -> io::Result<()> {
+        self.rx
+            .changed()
+            .await
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))
+    }
+
+    /// The address of the latest primary announced, if any.
+    pub fn primary(&self) -> Option<(String, u16)> {
+        self.rx.borrow().clone()
+    }
+
+    /// Whether there was a failover since the last call.
+    fn take_changed(&mut self) -> bool {
+        match self.rx.has_changed() {
+            Ok(true) => {
+                self.rx.borrow_and_update();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A [`Sender`] to the primary of a service, see [`Sentinel::sender`].
+///
+/// After a failover, or once the connection fails, the next command finds
+/// the primary again. The command that failed is not retried, as it may
+/// have been applied.
+pub struct SentinelSender {
+    sentinel: Sentinel,
+    sender: Option<Sender>,
+    failovers: Failovers,
+}
+
+impl SentinelSender {
+    /// The sender to the current primary, connecting first if needed.
+    pub async fn get(&mut self) -> io::Result<&mut Sender> {
+        if self.failovers.take_changed() {
+            self.sender = None;
+        }
+
+        if self.sender.is_none() {
+            self.sender = Some(self.sentinel.connect_sender().await?);
+        }
+
+        Ok(self.sender.as_mut().unwrap())
+    }
+
+    pub async fn command(&mut self, cmd: RespValue) -> io::Result<RespValue> {
+        let reply = self.get().await?.command(cmd).await;
+
+        if let Err(e) = &reply {
+            // a demoted primary turns down writes
+            if e.kind() != io::ErrorKind::Other || e.to_string().starts_with("READONLY") {
+                self.sender = None;
+            }
+        }
+
+        reply
+    }
+
+    /// A handle notified of the failovers this sender follows.
+    pub fn failovers(&self) -> Failovers {
+        self.failovers.clone()
+    }
+}
+
+/// A [`Receiver`] to the primary of a service, see [`Sentinel::receiver`].
+///
+/// After a failover, or once the connection fails, it connects to the
+/// primary again and restores its subscriptions. Messages published in
+/// between are lost.
+pub struct SentinelReceiver {
+    sentinel: Sentinel,
+    receiver: Receiver,
+    /// Set once the connection must be replaced.
+    stale: bool,
+    failovers: Failovers,
+}
+
+impl SentinelReceiver {
+    /// The receiver to the current primary, e.g. to subscribe.
+    pub fn get(&mut self) -> &mut Receiver {
+        &mut self.receiver
+    }
+
+    /// Returns the next message, reconnecting as needed. Fails if the
+    /// primary can't be reached, and tries again on the next call.
+    pub async fn next(&mut self) -> io::Result<Message> {
+        loop {
+            if self.failovers.take_changed() || self.stale {
+                self.reconnect().await?;
+            }
+
+            tokio::select! {
+                mesg = self.receiver.next() => match mesg {
+                    Ok(mesg) => return Ok(mesg),
+                    Err(e) if e.kind() == io::ErrorKind::Other => return Err(e),
+                    Err(_) => self.stale = true,
+                },
+                Ok(()) = self.failovers.rx.changed() => self.stale = true,
+            }
+        }
+    }
+
+    async fn reconnect(&mut self) -> io::Result<()> {
+        self.stale = true;
+
+        let mut receiver = self.sentinel.connect_receiver().await?;
+        receiver.set_keepalive(self.receiver.keepalive().clone());
+
+        let subscriptions = self.receiver.subscriptions();
+        let channels = subscriptions.channels().iter().cloned().collect::<Vec<_>>();
+        let patterns = subscriptions.patterns().iter().cloned().collect::<Vec<_>>();
+        let shard_channels = subscriptions
+            .shard_channels()
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if !channels.is_empty() {
+            receiver.subscribe(channels).await?;
+        }
+        if !patterns.is_empty() {
+            receiver.psubscribe(patterns).await?;
+        }
+        if !shard_channels.is_empty() {
+            receiver.ssubscribe(shard_channels).await?;
+        }
+
+        self.receiver = receiver;
+        self.stale = false;
+
+        Ok(())
+    }
+
+    /// A handle notified of the failovers this receiver follows.
+    pub fn failovers(&self) -> Failovers {
+        self.failovers.clone()
+    }
+}
@@ -0,0 +1,624 @@
+use std::io;
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use super::{request, Sender};
+use crate::resp::convert::{FromResp, ToArg};
+use crate::resp::value::*;
+
+fn invalid() -> io::Error {
+    io::Error::from(io::ErrorKind::InvalidData)
+}
+
+fn millis(d: Duration) -> RespValue {
+    bulk(d.as_millis().to_string())
+}
+
+/// One entry of a stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamEntry {
+    pub id: String,
+    /// Field/value pairs in the order they were added. Empty for an entry
+    /// that was deleted while still pending in a consumer group.
+    pub fields: Vec<(Bytes, Bytes)>,
+}
+
+impl StreamEntry {
+    /// The value of the first field named `field`.
+    pub fn get(&self, field: impl AsRef<[u8]>) -> Option<&Bytes> {
+        self.fields
+            .iter()
+            .find(|(name, _)| name == field.as_ref())
+            .map(|(_, value)| value)
+    }
+}
+
+impl FromResp for StreamEntry {
+    fn from_resp(val: RespValue) -> io::Result<Self> {
+        let [id, fields]: [RespValue; 2] =
+            Vec::from_resp(val)?.try_into().map_err(|_| invalid())?;
+
+        let mut pairs = Vec::new();
+        let mut fields = Vec::<Bytes>::from_resp(fields)?.into_iter();
+
+        while let Some(name) = fields.next() {
+            pairs.push((name, fields.next().ok_or_else(invalid)?));
+        }
+
+        Ok(Self {
+            id: String::from_resp(id)?,
+            fields: pairs,
+        })
+    }
+}
+
+/// The entries read from one stream by XREAD or XREADGROUP.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamRead {
+    pub key: Bytes,
+    pub entries: Vec<StreamEntry>,
+}
+
+/// Parses a multi-stream reply, a map under RESP3 and an array of
+/// `[key, entries]` pairs under RESP2.
+fn stream_reads(val: RespValue) -> io::Result<Vec<StreamRead>> {
+    let pairs = match val {
+        RespValue::Map(pairs) => pairs,
+        val => Vec::<RespValue>::from_resp(val)?
+            .into_iter()
+            .map(|pair| {
+                let [key, entries]: [RespValue; 2] =
+                    Vec::from_resp(pair)?.try_into().map_err(|_| invalid())?;
+                Ok((key, entries))
+            })
+            .collect::<io::Result<_>>()?,
+    };
+
+    pairs
+        .into_iter()
+        .map(|(key, entries)| {
+            Ok(StreamRead {
+                key: Bytes::from_resp(key)?,
+                entries: Vec::from_resp(entries)?,
+            })
+        })
+        .collect()
+}
+
+/// How XADD and XTRIM cut a stream down.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamTrim {
+    /// Keep at most this many entries.
+    MaxLen(u64),
+    /// Drop entries with an ID lower than this one.
+    MinId(String),
+    /// Like [`StreamTrim::MaxLen`], but lets the server trim lazily, which
+    /// is much cheaper.
+    ApproxMaxLen(u64),
+    /// Like [`StreamTrim::MinId`], trimming lazily.
+    ApproxMinId(String),
+}
+
+impl StreamTrim {
+    fn args(&self) -> [RespValue; 3] {
+        match self {
+            Self::MaxLen(len) => [bulk("MAXLEN"), bulk("="), len.to_arg()],
+            Self::MinId(id) => [bulk("MINID"), bulk("="), id.to_arg()],
+            Self::ApproxMaxLen(len) => [bulk("MAXLEN"), bulk("~"), len.to_arg()],
+            Self::ApproxMinId(id) => [bulk("MINID"), bulk("~"), id.to_arg()],
+        }
+    }
+}
+
+/// Options shared by XREAD and XREADGROUP.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StreamReadOptions {
+    pub count: Option<usize>,
+    /// Wait up to this long for new entries. A zero duration waits forever.
+    pub block: Option<Duration>,
+    /// XREADGROUP only: don't add the entries to the pending list.
+    pub noack: bool,
+}
+
+impl StreamReadOptions {
+    pub fn count(mut self, count: usize) -> Self {
+        self.count = Some(count);
+        self
+    }
+
+    pub fn block(mut self, block: Duration) -> Self {
+        self.block = Some(block);
+        self
+    }
+
+    pub fn noack(mut self) -> Self {
+        self.noack = true;
+        self
+    }
+
+    fn args(&self, cmd: &mut Vec<RespValue>) {
+        if let Some(count) = self.count {
+            cmd.extend([bulk("COUNT"), count.to_arg()]);
+        }
+
+        if let Some(block) = self.block {
+            cmd.extend([bulk("BLOCK"), millis(block)]);
+        }
+    }
+}
+
+/// The summary form of XPENDING.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PendingSummary {
+    pub count: i64,
+    pub min_id: Option<String>,
+    pub max_id: Option<String>,
+    /// Each consumer with pending entries, and how many it has.
+    pub consumers: Vec<(String, i64)>,
+}
+
+impl FromResp for PendingSummary {
+    fn from_resp(val: RespValue) -> io::Result<Self> {
+        let [count, min_id, max_id, consumers]: [RespValue; 4] =
+            Vec::from_resp(val)?.try_into().map_err(|_| invalid())?;
+
+        let consumers = Vec::<Vec<RespValue>>::from_resp(consumers)?
+            .into_iter()
+            .map(|pair| {
+                let [name, count]: [RespValue; 2] = pair.try_into().map_err(|_| invalid())?;
+                Ok((String::from_resp(name)?, i64::from_resp(count)?))
+            })
+            .collect::<io::Result<_>>()?;
+
+        Ok(Self {
+            count: i64::from_resp(count)?,
+            min_id: FromResp::from_resp(min_id)?,
+            max_id: FromResp::from_resp(max_id)?,
+            consumers,
+        })
+    }
+}
+
+/// An entry of the extended form of XPENDING.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingEntry {
+    pub id: String,
+    pub consumer: String,
+    /// Time since the entry was last delivered.
+    pub idle: Duration,
+    /// How many times the entry has been delivered.
+    pub deliveries: i64,
+}
+
+impl FromResp for PendingEntry {
+    fn from_resp(val: RespValue) -> io::Result<Self> {
+        let [id, consumer, idle, deliveries]: [RespValue; 4] =
+            Vec::from_resp(val)?.try_into().map_err(|_| invalid())?;
+
+        let idle = u64::try_from(i64::from_resp(idle)?).map_err(|_| invalid())?;
+
+        Ok(Self {
+            id: String::from_resp(id)?,
+            consumer: String::from_resp(consumer)?,
+            idle: Duration::from_millis(idle),
+            deliveries: i64::from_resp(deliveries)?,
+        })
+    }
+}
+
+/// Filters for the extended form of XPENDING.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingRange {
+    pub start: String,
+    pub end: String,
+    pub count: usize,
+    /// Only entries idle for at least this long.
+    pub min_idle: Option<Duration>,
+    /// Only entries owned by this consumer.
+    pub consumer: Option<String>,
+}
+
+impl PendingRange {
+    /// Up to `count` pending entries, oldest first.
+    pub fn new(count: usize) -> Self {
+        Self {
+            start: "-".into(),
+            end: "+".into(),
+            count,
+            min_idle: None,
+            consumer: None,
+        }
+    }
+
+    pub fn min_idle(mut self, min_idle: Duration) -> Self {
+        self.min_idle = Some(min_idle);
+        self
+    }
+
+    pub fn consumer(mut self, consumer: impl Into<String>) -> Self {
+        self.consumer = Some(consumer.into());
+        self
+    }
+}
+
+/// The reply to XAUTOCLAIM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AutoClaim {
+    /// Where the next call should start scanning, `0-0` once done.
+    pub next_id: String,
+    pub entries: Vec<StreamEntry>,
+    /// Pending entries that no longer exist in the stream, and were removed
+    /// from the pending list (Redis 7 and later).
+    pub deleted: Vec<String>,
+}
+
+impl FromResp for AutoClaim {
+    fn from_resp(val: RespValue) -> io::Result<Self> {
+        let mut items = Vec::from_resp(val)?.into_iter();
+
+        let next_id = String::from_resp(items.next().ok_or_else(invalid)?)?;
+        let entries = claimed(items.next().ok_or_else(invalid)?)?;
+        let deleted = items.next().map(Vec::from_resp).transpose()?;
+
+        Ok(Self {
+            next_id,
+            entries,
+            deleted: deleted.unwrap_or_default(),
+        })
+    }
+}
+
+/// Parses claimed entries, skipping the nulls Redis returns for entries
+/// deleted from the stream.
+fn claimed(val: RespValue) -> io::Result<Vec<StreamEntry>> {
+    Ok(Vec::<Option<StreamEntry>>::from_resp(val)?
+        .into_iter()
+        .flatten()
+        .collect())
+}
+
+impl Sender {
+    /// Appends an entry, returning its ID. Pass `"*"` as `id` to have the
+    /// server generate one.
+    pub async fn xadd<K, V>(
+        &mut self,
+        key: impl ToArg,
+        id: &str,
+        fields: impl IntoIterator<Item = (K, V)>,
+    ) -> io::Result<String>
+    where
+        K: ToArg,
+        V: ToArg,
+    {
+        self.xadd_trim(key, None, id, fields).await
+    }
+
+    /// Appends an entry and trims the stream in the same command.
+    pub async fn xadd_trim<K, V>(
+        &mut self,
+        key: impl ToArg,
+        trim: Option<&StreamTrim>,
+        id: &str,
+        fields: impl IntoIterator<Item = (K, V)>,
+    ) -> io::Result<String>
+    where
+        K: ToArg,
+        V: ToArg,
+    {
+        let mut cmd = vec![bulk("XADD"), key.to_arg()];
+        cmd.extend(trim.into_iter().flat_map(StreamTrim::args));
+        cmd.push(bulk(id));
+
+        let len = cmd.len();
+        for (field, value) in fields {
+            cmd.extend([field.to_arg(), value.to_arg()]);
+        }
+
+        if cmd.len() == len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no fields given",
+            ));
+        }
+
+        String::from_resp(self.command(cmd.into()).await?)
+    }
+
+    /// Trims a stream, returning the number of entries removed.
+    pub async fn xtrim(&mut self, key: impl ToArg, trim: &StreamTrim) -> io::Result<i64> {
+        let mut cmd = vec![bulk("XTRIM"), key.to_arg()];
+        cmd.extend(trim.args());
+
+        i64::from_resp(self.command(cmd.into()).await?)
+    }
+
+    pub async fn xlen(&mut self, key: impl ToArg) -> io::Result<i64> {
+        let cmd = vec![bulk("XLEN"), key.to_arg()];
+        i64::from_resp(self.command(cmd.into()).await?)
+    }
+
+    pub async fn xdel(&mut self, key: impl ToArg, ids: &[&str]) -> io::Result<i64> {
+        let mut cmd = vec![bulk("XDEL"), key.to_arg()];
+        cmd.extend(ids.iter().map(bulk));
+
+        i64::from_resp(self.command(cmd.into()).await?)
+    }
+
+    /// Entries with IDs between `start` and `end` inclusive. Use `"-"` and
+    /// `"+"` for the first and last possible IDs.
+    pub async fn xrange(
+        &mut self,
+        key: impl ToArg,
+        start: &str,
+        end: &str,
+        count: Option<usize>,
+    ) -> io::Result<Vec<StreamEntry>> {
+        self.range("XRANGE", key, start, end, count).await
+    }
+
+    /// Like [`Sender::xrange`], newest first. Note that `end` comes first.
+    pub async fn xrevrange(
+        &mut self,
+        key: impl ToArg,
+        end: &str,
+        start: &str,
+        count: Option<usize>,
+    ) -> io::Result<Vec<StreamEntry>> {
+        self.range("XREVRANGE", key, end, start, count).await
+    }
+
+    async fn range(
+        &mut self,
+        name: &str,
+        key: impl ToArg,
+        from: &str,
+        to: &str,
+        count: Option<usize>,
+    ) -> io::Result<Vec<StreamEntry>> {
+        let mut cmd = vec![bulk(name), key.to_arg(), bulk(from), bulk(to)];
+
+        if let Some(count) = count {
+            cmd.extend([bulk("COUNT"), count.to_arg()]);
+        }
+
+        Vec::from_resp(self.command(cmd.into()).await?)
+    }
+
+    /// Reads entries newer than the given ID from each stream. Use `"$"` to
+    /// only get entries added after the call, together with
+    /// [`StreamReadOptions::block`]. Returns an empty list if the block
+    /// timed out.
+    pub async fn xread<K: ToArg>(
+        &mut self,
+        streams: &[(K, &str)],
+        opts: &StreamReadOptions,
+    ) -> io::Result<Vec<StreamRead>> {
+        let mut cmd = vec![bulk("XREAD")];
+        opts.args(&mut cmd);
+
+        self.read_streams(cmd, streams, opts).await
+    }
+
+    /// Reads entries as `consumer` of `group`. Use `">"` as the ID to get
+    /// new entries, or another ID to re-read the consumer's pending entries.
+    pub async fn xreadgroup<K: ToArg>(
+        &mut self,
+        group: impl ToArg,
+        consumer: impl ToArg,
+        streams: &[(K, &str)],
+        opts: &StreamReadOptions,
+    ) -> io::Result<Vec<StreamRead>> {
+        let mut cmd = vec![
+            bulk("XREADGROUP"),
+            bulk("GROUP"),
+            group.to_arg(),
+            consumer.to_arg(),
+        ];
+        opts.args(&mut cmd);
+
+        if opts.noack {
+            cmd.push(bulk("NOACK"));
+        }
+
+        self.read_streams(cmd, streams, opts).await
+    }
+
+    async fn read_streams<K: ToArg>(
+        &mut self,
+        mut cmd: Vec<RespValue>,
+        streams: &[(K, &str)],
+        opts: &StreamReadOptions,
+    ) -> io::Result<Vec<StreamRead>> {
+        cmd.push(bulk("STREAMS"));
+        cmd.extend(streams.iter().map(|(key, _)| key.to_arg()));
+        cmd.extend(streams.iter().map(|(_, id)| bulk(id)));
+
+        // the reply can't come before the block ends
+        let timeout = match (self.response_timeout, opts.block) {
+            (Some(_), Some(block)) if block.is_zero() => None,
+            (Some(timeout), Some(block)) => Some(timeout + block),
+            (timeout, _) => timeout,
+        };
+
+        stream_reads(request(&mut self.f_conn, cmd.into(), timeout).await?)
+    }
+
+    /// Acknowledges entries, returning how many were pending.
+    pub async fn xack(
+        &mut self,
+        key: impl ToArg,
+        group: impl ToArg,
+        ids: &[&str],
+    ) -> io::Result<i64> {
+        let mut cmd = vec![bulk("XACK"), key.to_arg(), group.to_arg()];
+        cmd.extend(ids.iter().map(bulk));
+
+        i64::from_resp(self.command(cmd.into()).await?)
+    }
+
+    pub async fn xpending(
+        &mut self,
+        key: impl ToArg,
+        group: impl ToArg,
+    ) -> io::Result<PendingSummary> {
+        let cmd = vec![bulk("XPENDING"), key.to_arg(), group.to_arg()];
+        PendingSummary::from_resp(self.command(cmd.into()).await?)
+    }
+
+    /// The extended form of XPENDING, listing individual entries.
+    pub async fn xpending_range(
+        &mut self,
+        key: impl ToArg,
+        group: impl ToArg,
+        range: &PendingRange,
+    ) -> io::Result<Vec<PendingEntry>> {
+        let mut cmd = vec![bulk("XPENDING"), key.to_arg(), group.to_arg()];
+
+        if let Some(idle) = range.min_idle {
+            cmd.extend([bulk("IDLE"), millis(idle)]);
+        }
+
+        cmd.extend([bulk(&range.start), bulk(&range.end), range.count.to_arg()]);
+        cmd.extend(range.consumer.iter().map(bulk));
+
+        Vec::from_resp(self.command(cmd.into()).await?)
+    }
+
+    /// Takes ownership of pending entries that have been idle for at least
+    /// `min_idle`. Entries deleted from the stream are left out.
+    pub async fn xclaim(
+        &mut self,
+        key: impl ToArg,
+        group: impl ToArg,
+        consumer: impl ToArg,
+        min_idle: Duration,
+        ids: &[&str],
+    ) -> io::Result<Vec<StreamEntry>> {
+        let mut cmd = vec![
+            bulk("XCLAIM"),
+            key.to_arg(),
+            group.to_arg(),
+            consumer.to_arg(),
+            millis(min_idle),
+        ];
+        cmd.extend(ids.iter().map(bulk));
+
+        claimed(self.command(cmd.into()).await?)
+    }
+
+    /// Scans the pending list from `start` and claims up to `count` entries
+    /// idle for at least `min_idle`.
+    pub async fn xautoclaim(
+        &mut self,
+        key: impl ToArg,
+        group: impl ToArg,
+        consumer: impl ToArg,
+        min_idle: Duration,
+        start: &str,
+        count: usize,
+    ) -> io::Result<AutoClaim> {
+        let cmd = vec![
+            bulk("XAUTOCLAIM"),
+            key.to_arg(),
+            group.to_arg(),
+            consumer.to_arg(),
+            millis(min_idle),
+            bulk(start),
+            bulk("COUNT"),
+            count.to_arg(),
+        ];
+
+        AutoClaim::from_resp(self.command(cmd.into()).await?)
+    }
+
+    /// Creates a consumer group starting at `id`, `"$"` for new entries only.
+    /// With `mkstream`, the stream is created if it doesn't exist.
+    pub async fn xgroup_create(
+        &mut self,
+        key: impl ToArg,
+        group: impl ToArg,
+        id: &str,
+        mkstream: bool,
+    ) -> io::Result<()> {
+        let mut cmd = vec![
+            bulk("XGROUP"),
+            bulk("CREATE"),
+            key.to_arg(),
+            group.to_arg(),
+            bulk(id),
+        ];
+
+        if mkstream {
+            cmd.push(bulk("MKSTREAM"));
+        }
+
+        self.command(cmd.into()).await.map(drop)
+    }
+
+    /// Returns false if the group didn't exist.
+    pub async fn xgroup_destroy(&mut self, key: impl ToArg, group: impl ToArg) -> io::Result<bool> {
+        let cmd = vec![
+            bulk("XGROUP"),
+            bulk("DESTROY"),
+            key.to_arg(),
+            group.to_arg(),
+        ];
+
+        bool::from_resp(self.command(cmd.into()).await?)
+    }
+
+    /// Moves the group's last delivered ID.
+    pub async fn xgroup_setid(
+        &mut self,
+        key: impl ToArg,
+        group: impl ToArg,
+        id: &str,
+    ) -> io::Result<()> {
+        let cmd = vec![
+            bulk("XGROUP"),
+            bulk("SETID"),
+            key.to_arg(),
+            group.to_arg(),
+            bulk(id),
+        ];
+
+        self.command(cmd.into()).await.map(drop)
+    }
+
+    /// Returns false if the consumer already existed.
+    pub async fn xgroup_createconsumer(
+        &mut self,
+        key: impl ToArg,
+        group: impl ToArg,
+        consumer: impl ToArg,
+    ) -> io::Result<bool> {
+        let cmd = vec![
+            bulk("XGROUP"),
+            bulk("CREATECONSUMER"),
+            key.to_arg(),
+            group.to_arg(),
+            consumer.to_arg(),
+        ];
+
+        bool::from_resp(self.command(cmd.into()).await?)
+    }
+
+    /// Removes a consumer, returning how many entries it had pending.
+    pub async fn xgroup_delconsumer(
+        &mut self,
+        key: impl ToArg,
+        group: impl ToArg,
+        consumer: impl ToArg,
+    ) -> io::Result<i64> {
+        let cmd = vec![
+            bulk("XGROUP"),
+            bulk("DELCONSUMER"),
+            key.to_arg(),
+            group.to_arg(),
+            consumer.to_arg(),
+        ];
+
+        i64::from_resp(self.command(cmd.into()).await?)
+    }
+}
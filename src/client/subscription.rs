@@ -0,0 +1,160 @@
+use std::collections::HashSet;
+use std::io;
+
+use bytes::Bytes;
+
+use super::keys::IntoKeys;
+use super::message::into_bytes;
+use crate::pattern;
+use crate::resp::value::RespValue;
+
+/// The pub/sub commands that the server acknowledges with a confirmation
+/// frame carrying the channel and the number of active subscriptions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SubscriptionKind {
+    Subscribe,
+    Unsubscribe,
+    PSubscribe,
+    PUnsubscribe,
+    SSubscribe,
+    SUnsubscribe,
+}
+
+impl SubscriptionKind {
+    pub(crate) fn from_frame_type(ty: &str) -> Option<Self> {
+        match ty {
+            "subscribe" => Some(Self::Subscribe),
+            "unsubscribe" => Some(Self::Unsubscribe),
+            "psubscribe" => Some(Self::PSubscribe),
+            "punsubscribe" => Some(Self::PUnsubscribe),
+            "ssubscribe" => Some(Self::SSubscribe),
+            "sunsubscribe" => Some(Self::SUnsubscribe),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn command(self) -> &'static str {
+        match self {
+            Self::Subscribe => "SUBSCRIBE",
+            Self::Unsubscribe => "UNSUBSCRIBE",
+            Self::PSubscribe => "PSUBSCRIBE",
+            Self::PUnsubscribe => "PUNSUBSCRIBE",
+            Self::SSubscribe => "SSUBSCRIBE",
+            Self::SUnsubscribe => "SUNSUBSCRIBE",
+        }
+    }
+}
+
+/// A subscribe or unsubscribe confirmation sent by the server.
+#[derive(Debug)]
+pub(crate) struct Confirmation {
+    pub kind: SubscriptionKind,
+    /// None when unsubscribing from everything while nothing was subscribed.
+    pub channel: Option<Bytes>,
+    /// Subscriptions still active on the connection after this one.
+    pub count: i64,
+}
+
+impl Confirmation {
+    /// Builds a confirmation from the items of a pub/sub frame, excluding the
+    /// leading frame type.
+    pub(crate) fn from_items(
+        kind: SubscriptionKind,
+        items: impl IntoIterator<Item = RespValue>,
+    ) -> io::Result<Self> {
+        let mut items = items.into_iter();
+
+        let channel = match items.next() {
+            Some(RespValue::BulkString(None) | RespValue::Null) => None,
+            Some(val) => Some(into_bytes(val).ok_or(io::ErrorKind::InvalidData)?),
+            None => return Err(io::Error::from(io::ErrorKind::InvalidData)),
+        };
+
+        let Some(RespValue::Integer(count)) = items.next() else {
+            return Err(io::Error::from(io::ErrorKind::InvalidData));
+        };
+
+        Ok(Self {
+            kind,
+            channel,
+            count,
+        })
+    }
+}
+
+/// The channels, patterns and shard channels a [`Receiver`](super::Receiver)
+/// is subscribed to, as confirmed by the server.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Subscriptions {
+    channels: HashSet<Bytes>,
+    patterns: HashSet<Bytes>,
+    shard_channels: HashSet<Bytes>,
+}
+
+impl Subscriptions {
+    pub fn channels(&self) -> &HashSet<Bytes> {
+        &self.channels
+    }
+
+    pub fn patterns(&self) -> &HashSet<Bytes> {
+        &self.patterns
+    }
+
+    pub fn shard_channels(&self) -> &HashSet<Bytes> {
+        &self.shard_channels
+    }
+
+    pub fn len(&self) -> usize {
+        self.channels.len() + self.patterns.len() + self.shard_channels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The subscribed patterns that `channel` matches, each of which the
+    /// server delivers a separate `pmessage` for.
+    pub fn matching_patterns<'a>(&'a self, channel: &'a [u8]) -> impl Iterator<Item = &'a Bytes> {
+        self.patterns
+            .iter()
+            .filter(move |pat| pattern::matches(pat, channel))
+    }
+
+    /// Returns true when a message published to `channel` would be received
+    /// through a channel or pattern subscription.
+    pub fn receives(&self, channel: &[u8]) -> bool {
+        self.channels.contains(channel) || self.matching_patterns(channel).next().is_some()
+    }
+
+    pub(crate) fn apply(&mut self, confirmation: &Confirmation) {
+        let Some(channel) = &confirmation.channel else {
+            return;
+        };
+
+        match confirmation.kind {
+            SubscriptionKind::Subscribe => self.channels.insert(channel.clone()),
+            SubscriptionKind::Unsubscribe => self.channels.remove(channel),
+            SubscriptionKind::PSubscribe => self.patterns.insert(channel.clone()),
+            SubscriptionKind::PUnsubscribe => self.patterns.remove(channel),
+            SubscriptionKind::SSubscribe => self.shard_channels.insert(channel.clone()),
+            SubscriptionKind::SUnsubscribe => self.shard_channels.remove(channel),
+        };
+    }
+}
+
+/// Channel names or patterns gathered from any iterator, for when they
+/// don't already sit in an array, slice or vector.
+#[derive(Debug, Clone, Default)]
+pub struct Channels(Vec<Bytes>);
+
+impl<T: Into<Bytes>> FromIterator<T> for Channels {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self(iter.into_iter().map(Into::into).collect())
+    }
+}
+
+impl IntoKeys for Channels {
+    fn into_keys(self) -> Vec<Bytes> {
+        self.0
+    }
+}
@@ -0,0 +1,122 @@
+use std::io::{self, Error, ErrorKind::*};
+use std::sync::Arc;
+
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::crypto::{self, CryptoProvider};
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{self, ClientConfig, DigitallySignedStruct, RootCertStore};
+use tokio_rustls::TlsConnector;
+
+use super::TlsConfig;
+
+/// Runs the TLS handshake over an established TCP connection to `host`.
+pub(crate) async fn connect(
+    host: &str,
+    config: &TlsConfig,
+    stream: TcpStream,
+) -> io::Result<TlsStream<TcpStream>> {
+    let name = config.server_name.as_deref().unwrap_or(host);
+    let name = ServerName::try_from(name.to_string())
+        .map_err(|_| Error::new(InvalidInput, format!("invalid server name: {}", name)))?;
+
+    let connector = TlsConnector::from(Arc::new(client_config(config)?));
+    connector.connect(name, stream).await
+}
+
+fn client_config(config: &TlsConfig) -> io::Result<ClientConfig> {
+    let provider = Arc::new(crypto::ring::default_provider());
+
+    let builder = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(invalid)?;
+
+    let builder = if config.insecure {
+        builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(NoVerifier(provider)))
+    } else {
+        let mut roots = RootCertStore::empty();
+
+        match &config.root_certs_pem {
+            Some(pem) => {
+                for cert in CertificateDer::pem_slice_iter(pem) {
+                    roots.add(cert.map_err(invalid)?).map_err(invalid)?;
+                }
+            }
+            None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+        }
+
+        builder.with_root_certificates(roots)
+    };
+
+    match &config.client_cert_pem {
+        Some((cert_pem, key_pem)) => {
+            let chain = CertificateDer::pem_slice_iter(cert_pem)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(invalid)?;
+            let key = PrivateKeyDer::from_pem_slice(key_pem).map_err(invalid)?;
+
+            builder.with_client_auth_cert(chain, key).map_err(invalid)
+        }
+        None => Ok(builder.with_no_client_auth()),
+    }
+}
+
+fn invalid(e: impl std::fmt::Display) -> Error {
+    Error::new(InvalidInput, e.to_string())
+}
+
+/// Accepts any certificate the server presents, for [`TlsConfig::insecure`].
+/// Handshake signatures are still checked so the session itself is sound.
+#[derive(Debug)]
+struct NoVerifier(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for NoVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
@@ -0,0 +1,179 @@
+use std::io;
+
+use futures::{SinkExt, StreamExt};
+
+use super::{error_reply, with_timeout, IntoKeys, Sender};
+use crate::resp::value::*;
+
+/// How often [`Sender::transaction`] retries by default when a watched key
+/// changes.
+pub const DEFAULT_TRANSACTION_RETRIES: usize = 10;
+
+/// A batch of commands to run atomically with [`Sender::exec`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Pipeline {
+    cmds: Vec<RespValue>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues a command given as its name followed by its arguments.
+    pub fn cmd<I>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        self.add(args.into_iter().map(bulk).collect::<Vec<_>>().into())
+    }
+
+    /// Queues an already encoded command.
+    pub fn add(&mut self, cmd: RespValue) -> &mut Self {
+        self.cmds.push(cmd);
+        self
+    }
+
+    pub fn commands(&self) -> &[RespValue] {
+        &self.cmds
+    }
+
+    pub fn len(&self) -> usize {
+        self.cmds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cmds.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.cmds.clear();
+    }
+}
+
+/// The outcome of each command in a transaction, in the order they were
+/// queued. Commands that failed at runtime, e.g. with WRONGTYPE, don't
+/// affect the others.
+pub type TransactionResults = Vec<io::Result<RespValue>>;
+
+impl Sender {
+    /// Sets how many times [`Sender::transaction`] retries after a watched
+    /// key changed, before giving up with [`io::ErrorKind::Interrupted`].
+    pub fn set_transaction_retries(&mut self, retries: usize) {
+        self.transaction_retries = retries;
+    }
+
+    /// Marks `keys` to be checked by the next EXEC.
+    pub async fn watch(&mut self, keys: impl IntoKeys) -> io::Result<()> {
+        let mut cmd = vec![bulk("WATCH")];
+        cmd.extend(keys.into_keys().iter().map(bulk));
+
+        self.expect_ok(cmd.into()).await
+    }
+
+    pub async fn unwatch(&mut self) -> io::Result<()> {
+        self.expect_ok(vec![bulk("UNWATCH")].into()).await
+    }
+
+    /// Aborts a transaction started with a raw MULTI.
+    pub async fn discard(&mut self) -> io::Result<()> {
+        self.expect_ok(vec![bulk("DISCARD")].into()).await
+    }
+
+    async fn expect_ok(&mut self, cmd: RespValue) -> io::Result<()> {
+        match self.command(cmd).await? {
+            RespValue::SimpleString(s) if &*s == "OK" => Ok(()),
+            _ => Err(io::Error::from(io::ErrorKind::InvalidData)),
+        }
+    }
+
+    /// Runs `pipeline` between MULTI and EXEC, sending every command before
+    /// reading any reply. Returns None if the transaction was aborted because
+    /// a key passed to [`Sender::watch`] changed.
+    ///
+    /// If the server rejects a command while queueing it, e.g. for a wrong
+    /// number of arguments, nothing is run and that error is returned.
+    pub async fn exec(&mut self, pipeline: &Pipeline) -> io::Result<Option<TransactionResults>> {
+        let replies = with_timeout(self.response_timeout, async {
+            self.f_conn.feed(vec![bulk("MULTI")].into()).await?;
+            for cmd in &pipeline.cmds {
+                self.f_conn.feed(cmd.clone()).await?;
+            }
+            self.f_conn.feed(vec![bulk("EXEC")].into()).await?;
+            self.f_conn.flush().await?;
+
+            let mut replies = Vec::with_capacity(pipeline.len() + 2);
+            for _ in 0..pipeline.len() + 2 {
+                let reply = self
+                    .f_conn
+                    .next()
+                    .await
+                    .ok_or(io::ErrorKind::BrokenPipe)??;
+                replies.push(reply);
+            }
+
+            Ok(replies)
+        })
+        .await?;
+
+        let mut replies = replies.into_iter();
+
+        // MULTI itself fails when nested
+        error_reply(replies.next().unwrap_or(BULK_NONE))?;
+
+        let exec = replies.next_back().unwrap_or(BULK_NONE);
+
+        for reply in replies {
+            match error_reply(reply)? {
+                RespValue::SimpleString(s) if &*s == "QUEUED" => {}
+                _ => return Err(io::Error::from(io::ErrorKind::InvalidData)),
+            }
+        }
+
+        match error_reply(exec)? {
+            RespValue::Array(Some(items)) if items.len() == pipeline.len() => {
+                Ok(Some(items.into_iter().map(error_reply).collect()))
+            }
+            RespValue::Array(None) | RespValue::Null => Ok(None),
+            _ => Err(io::Error::from(io::ErrorKind::InvalidData)),
+        }
+    }
+
+    /// Runs an optimistic transaction: WATCHes `keys`, lets `f` read them and
+    /// queue commands on the pipeline, then EXECs it. If a watched key
+    /// changed in the meantime, `f` is called again with an empty pipeline,
+    /// up to the limit set with [`Sender::set_transaction_retries`].
+    pub async fn transaction<F>(
+        &mut self,
+        keys: impl IntoKeys,
+        mut f: F,
+    ) -> io::Result<TransactionResults>
+    where
+        F: AsyncFnMut(&mut Sender, &mut Pipeline) -> io::Result<()>,
+    {
+        let keys = keys.into_keys();
+        let mut pipeline = Pipeline::new();
+
+        for _ in 0..=self.transaction_retries {
+            if !keys.is_empty() {
+                self.watch(keys.clone()).await?;
+            }
+
+            pipeline.clear();
+            if let Err(e) = f(self, &mut pipeline).await {
+                self.unwatch().await?;
+                return Err(e);
+            }
+
+            if let Some(results) = self.exec(&pipeline).await? {
+                return Ok(results);
+            }
+        }
+
+        Err(io::Error::new(
+            io::ErrorKind::Interrupted,
+            "watched keys kept changing",
+        ))
+    }
+}
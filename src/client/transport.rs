@@ -0,0 +1,51 @@
+use std::io;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+
+use super::{with_timeout, ConnectionAddr, ConnectionInfo};
+
+/// Any bidirectional byte stream that a connection can run over, e.g.
+/// [`TcpStream`], [`tokio::net::UnixStream`] or [`tokio::io::DuplexStream`].
+pub trait Transport: AsyncRead + AsyncWrite + Send + Unpin + 'static {}
+
+impl<T> Transport for T where T: AsyncRead + AsyncWrite + Send + Unpin + 'static {}
+
+pub type BoxedTransport = Box<dyn Transport>;
+
+/// Opens a stream to the address in `info`, giving up after the connect
+/// timeout. TLS addresses are wrapped once the TCP connection is up.
+pub(crate) async fn open(info: &ConnectionInfo) -> io::Result<BoxedTransport> {
+    let timeout = info.connect_timeout;
+
+    match &info.addr {
+        ConnectionAddr::Tcp { host, port } => {
+            let stream = with_timeout(timeout, TcpStream::connect((&**host, *port))).await?;
+            Ok(Box::new(stream))
+        }
+        #[cfg(feature = "tls")]
+        ConnectionAddr::TcpTls { host, port } => {
+            with_timeout(timeout, async {
+                let stream = TcpStream::connect((&**host, *port)).await?;
+                let stream = super::tls::connect(host, &info.tls, stream).await?;
+                Ok(Box::new(stream) as BoxedTransport)
+            })
+            .await
+        }
+        #[cfg(unix)]
+        ConnectionAddr::Unix(path) => {
+            let stream = with_timeout(timeout, tokio::net::UnixStream::connect(path)).await?;
+            Ok(Box::new(stream))
+        }
+        #[cfg(not(feature = "tls"))]
+        ConnectionAddr::TcpTls { .. } => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "rediss:// requires the tls feature",
+        )),
+        #[cfg(not(unix))]
+        ConnectionAddr::Unix(_) => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "unix sockets are not supported on this platform",
+        )),
+    }
+}
@@ -0,0 +1,210 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::marker::PhantomData;
+
+use bytes::Bytes;
+
+use super::{Message, Receiver, Sender};
+
+/// The error type codecs report failures with.
+pub type CodecError = Box<dyn Error + Send + Sync>;
+
+/// Converts values to and from pub/sub payloads.
+pub trait PayloadCodec<T> {
+    fn encode(&self, value: &T) -> Result<Bytes, CodecError>;
+
+    fn decode(&self, payload: &Bytes) -> Result<T, CodecError>;
+}
+
+/// Passes payloads through untouched.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BytesCodec;
+
+impl PayloadCodec<Bytes> for BytesCodec {
+    fn encode(&self, value: &Bytes) -> Result<Bytes, CodecError> {
+        Ok(value.clone())
+    }
+
+    fn decode(&self, payload: &Bytes) -> Result<Bytes, CodecError> {
+        Ok(payload.clone())
+    }
+}
+
+/// Payloads as UTF-8 strings.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Utf8Codec;
+
+impl PayloadCodec<String> for Utf8Codec {
+    fn encode(&self, value: &String) -> Result<Bytes, CodecError> {
+        Ok(Bytes::copy_from_slice(value.as_bytes()))
+    }
+
+    fn decode(&self, payload: &Bytes) -> Result<String, CodecError> {
+        Ok(std::str::from_utf8(payload)?.to_owned())
+    }
+}
+
+/// Payloads as JSON documents, for any serde type.
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+#[cfg(feature = "json")]
+impl<T: serde::Serialize + serde::de::DeserializeOwned> PayloadCodec<T> for JsonCodec {
+    fn encode(&self, value: &T) -> Result<Bytes, CodecError> {
+        Ok(serde_json::to_vec(value)?.into())
+    }
+
+    fn decode(&self, payload: &Bytes) -> Result<T, CodecError> {
+        Ok(serde_json::from_slice(payload)?)
+    }
+}
+
+/// Payloads as MessagePack, for any serde type. Structs are encoded as maps
+/// so that fields can be added without breaking older subscribers.
+#[cfg(feature = "msgpack")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MsgPackCodec;
+
+#[cfg(feature = "msgpack")]
+impl<T: serde::Serialize + serde::de::DeserializeOwned> PayloadCodec<T> for MsgPackCodec {
+    fn encode(&self, value: &T) -> Result<Bytes, CodecError> {
+        Ok(rmp_serde::to_vec_named(value)?.into())
+    }
+
+    fn decode(&self, payload: &Bytes) -> Result<T, CodecError> {
+        Ok(rmp_serde::from_slice(payload)?)
+    }
+}
+
+/// A message whose payload the codec could not decode.
+#[derive(Debug)]
+pub struct DecodeError {
+    message: Message,
+    source: CodecError,
+}
+
+impl DecodeError {
+    /// The message as it was received.
+    pub fn message(&self) -> &Message {
+        &self.message
+    }
+
+    pub fn into_message(self) -> Message {
+        self.message
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed to decode payload on channel {:?}: {}",
+            self.message.channel, self.source
+        )
+    }
+}
+
+impl Error for DecodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+impl From<DecodeError> for io::Error {
+    fn from(e: DecodeError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+type ErrorCallback = Box<dyn FnMut(DecodeError) + Send>;
+
+/// A pub/sub channel carrying values of type `T`, encoded with codec `C`.
+///
+/// The channel does not own a connection: values are published through a
+/// [`Sender`] and read from a [`Receiver`].
+pub struct TypedChannel<T, C> {
+    channel: Bytes,
+    codec: C,
+    on_error: Option<ErrorCallback>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T, C: PayloadCodec<T>> TypedChannel<T, C> {
+    pub fn new(channel: impl AsRef<[u8]>, codec: C) -> Self {
+        Self {
+            channel: Bytes::copy_from_slice(channel.as_ref()),
+            codec,
+            on_error: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Hands messages that fail to decode to `f` instead of returning them
+    /// from [`next`](Self::next).
+    pub fn on_error(mut self, f: impl FnMut(DecodeError) + Send + 'static) -> Self {
+        self.on_error = Some(Box::new(f));
+        self
+    }
+
+    pub fn channel(&self) -> &Bytes {
+        &self.channel
+    }
+
+    pub fn codec(&self) -> &C {
+        &self.codec
+    }
+
+    /// Publishes `value`, returning the number of subscribers that received
+    /// it. Fails with [`io::ErrorKind::InvalidInput`] if it cannot be encoded.
+    pub async fn publish(&self, sender: &mut Sender, value: &T) -> io::Result<i64> {
+        let payload = self
+            .codec
+            .encode(value)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        sender.publish(&self.channel, payload).await
+    }
+
+    pub async fn subscribe(&self, receiver: &mut Receiver) -> io::Result<i64> {
+        receiver.subscribe(self.channel.clone()).await
+    }
+
+    pub async fn unsubscribe(&self, receiver: &mut Receiver) -> io::Result<i64> {
+        receiver.unsubscribe(self.channel.clone()).await
+    }
+
+    /// Decodes a message, or returns None if it was sent to another channel.
+    pub fn decode(&self, mesg: Message) -> Option<Result<T, DecodeError>> {
+        if mesg.channel != self.channel {
+            return None;
+        }
+
+        Some(
+            self.codec
+                .decode(&mesg.payload)
+                .map_err(|source| DecodeError {
+                    message: mesg,
+                    source,
+                }),
+        )
+    }
+
+    /// Waits for the next value on this channel. Messages for any other
+    /// channel the receiver is subscribed to are discarded, so a receiver
+    /// shared between channels should be read with [`Receiver::next`] and
+    /// [`decode`](Self::decode) instead.
+    pub async fn next(&mut self, receiver: &mut Receiver) -> io::Result<Result<T, DecodeError>> {
+        loop {
+            let Some(res) = self.decode(receiver.next().await?) else {
+                continue;
+            };
+
+            match (res, &mut self.on_error) {
+                (Err(e), Some(on_error)) => on_error(e),
+                (res, _) => return Ok(res),
+            }
+        }
+    }
+}
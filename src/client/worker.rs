@@ -0,0 +1,348 @@
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::stream::{FuturesUnordered, StreamExt};
+use tokio::time::{self, Instant};
+
+use super::{PendingRange, Sender, StreamEntry, StreamReadOptions};
+
+/// Settings for a [`Worker`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkerConfig {
+    pub group: String,
+    /// Must be unique among the workers of a group.
+    pub consumer: String,
+    pub streams: Vec<Bytes>,
+    /// How many entries are handled at the same time.
+    pub concurrency: usize,
+    /// How long each XREADGROUP waits for new entries when the worker is
+    /// idle, and how often it looks for more while handlers are busy. Keep it
+    /// below `claim_interval` so claims aren't delayed.
+    pub block: Duration,
+    /// After this many failed deliveries an entry is moved to `dead_letter`.
+    pub max_deliveries: i64,
+    /// Stream receiving entries that failed too often. When unset, they are
+    /// acknowledged and dropped.
+    pub dead_letter: Option<Bytes>,
+    /// How often entries left pending by other consumers are claimed.
+    pub claim_interval: Duration,
+    /// How long an entry must have been pending before it's claimed, i.e. how
+    /// long a consumer may take to handle an entry.
+    pub claim_min_idle: Duration,
+}
+
+impl WorkerConfig {
+    pub fn new<I>(group: impl Into<String>, consumer: impl Into<String>, streams: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        Self {
+            group: group.into(),
+            consumer: consumer.into(),
+            streams: streams
+                .into_iter()
+                .map(|s| Bytes::copy_from_slice(s.as_ref()))
+                .collect(),
+            concurrency: 10,
+            block: Duration::from_secs(5),
+            max_deliveries: 5,
+            dead_letter: None,
+            claim_interval: Duration::from_secs(30),
+            claim_min_idle: Duration::from_secs(60),
+        }
+    }
+}
+
+/// An entry handed to a [`Worker`] handler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delivery {
+    pub stream: Bytes,
+    pub entry: StreamEntry,
+    /// How many times the entry has been delivered, including this time.
+    pub deliveries: i64,
+}
+
+/// Processes the entries of a consumer group with a handler.
+///
+/// Entries are acknowledged once the handler succeeds. When it fails, the
+/// entry stays pending and is retried after `claim_min_idle`, by this worker
+/// or another one in the group, until it has been delivered
+/// `max_deliveries` times.
+pub struct Worker {
+    conn: Sender,
+    config: WorkerConfig,
+    /// XAUTOCLAIM cursor for each stream.
+    cursors: Vec<String>,
+    last_claim: Option<Instant>,
+}
+
+impl Worker {
+    pub fn new(conn: Sender, config: WorkerConfig) -> Self {
+        Self {
+            conn,
+            cursors: vec!["0-0".into(); config.streams.len()],
+            config,
+            last_claim: None,
+        }
+    }
+
+    pub fn config(&self) -> &WorkerConfig {
+        &self.config
+    }
+
+    pub fn into_inner(self) -> Sender {
+        self.conn
+    }
+
+    /// Creates the group on every stream, starting at `id`, along with
+    /// missing streams. Groups that already exist are left alone.
+    pub async fn create_groups(&mut self, id: &str) -> io::Result<()> {
+        for stream in &self.config.streams {
+            match self
+                .conn
+                .xgroup_create(stream, &self.config.group, id, true)
+                .await
+            {
+                Err(e) if e.to_string().starts_with("BUSYGROUP") => {}
+                res => res?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handles entries until a command fails. Errors returned by the handler
+    /// only cause the entry to be retried, so it should report them itself.
+    ///
+    /// Up to `concurrency` entries are handled at once, and new ones are read
+    /// as soon as a handler finishes rather than once a whole batch is done.
+    pub async fn run<F>(&mut self, handler: F) -> io::Result<()>
+    where
+        F: AsyncFn(&Delivery) -> io::Result<()>,
+    {
+        let concurrency = self.config.concurrency.max(1);
+        let mut queued = VecDeque::new();
+        let mut running = FuturesUnordered::new();
+
+        loop {
+            if queued.is_empty() && running.len() < concurrency {
+                // only wait for new entries when nothing else is going on
+                let block = running.is_empty();
+                queued.extend(self.fetch(concurrency - running.len(), block).await?);
+            }
+
+            while running.len() < concurrency {
+                let Some(delivery) = self.next_queued(&mut queued).await? else {
+                    break;
+                };
+                running.push(handle(&handler, delivery));
+            }
+
+            // wakes up after `block` to look for entries for the free slots
+            if let Ok(Some((delivery, ok))) = time::timeout(self.config.block, running.next()).await
+            {
+                self.finish(&delivery, ok).await?;
+            }
+        }
+    }
+
+    /// Handles one batch: claimed entries if a claim is due and finds any,
+    /// otherwise new entries, waiting up to `block` for them. Returns how
+    /// many entries were handed to the handler.
+    pub async fn run_once<F>(&mut self, handler: &F) -> io::Result<usize>
+    where
+        F: AsyncFn(&Delivery) -> io::Result<()>,
+    {
+        let concurrency = self.config.concurrency.max(1);
+        let mut queued = VecDeque::from(self.fetch(concurrency, true).await?);
+        let mut running = FuturesUnordered::new();
+        let mut handled = 0;
+
+        loop {
+            while running.len() < concurrency {
+                let Some(delivery) = self.next_queued(&mut queued).await? else {
+                    break;
+                };
+                running.push(handle(handler, delivery));
+                handled += 1;
+            }
+
+            match running.next().await {
+                Some((delivery, ok)) => self.finish(&delivery, ok).await?,
+                None => return Ok(handled),
+            }
+        }
+    }
+
+    /// Claimed entries if a claim is due and finds any, otherwise new
+    /// entries. Only waits for new entries when `block` is set.
+    async fn fetch(&mut self, count: usize, block: bool) -> io::Result<Vec<Delivery>> {
+        let due = self
+            .last_claim
+            .is_none_or(|last| last.elapsed() >= self.config.claim_interval);
+
+        if due {
+            self.last_claim = Some(Instant::now());
+
+            let claimed = self.claim(count).await?;
+            if !claimed.is_empty() {
+                return Ok(claimed);
+            }
+        }
+
+        self.read(count, block).await
+    }
+
+    /// Takes the next entry to hand to the handler. Entries that crashed
+    /// their consumers too often aren't retried.
+    async fn next_queued(
+        &mut self,
+        queued: &mut VecDeque<Delivery>,
+    ) -> io::Result<Option<Delivery>> {
+        while let Some(delivery) = queued.pop_front() {
+            if delivery.deliveries <= self.config.max_deliveries {
+                return Ok(Some(delivery));
+            }
+
+            self.dead_letter(&delivery).await?;
+            self.ack(&delivery).await?;
+        }
+
+        Ok(None)
+    }
+
+    /// Acknowledges a handled entry, or one that failed for the last time
+    /// after copying it to the dead letter stream.
+    async fn finish(&mut self, delivery: &Delivery, ok: bool) -> io::Result<()> {
+        if !ok {
+            if delivery.deliveries < self.config.max_deliveries {
+                return Ok(());
+            }
+            self.dead_letter(delivery).await?;
+        }
+
+        self.ack(delivery).await
+    }
+
+    async fn read(&mut self, count: usize, block: bool) -> io::Result<Vec<Delivery>> {
+        let mut opts = StreamReadOptions::default().count(count);
+        if block {
+            opts = opts.block(self.config.block);
+        }
+
+        let streams = self
+            .config
+            .streams
+            .iter()
+            .map(|stream| (stream, ">"))
+            .collect::<Vec<_>>();
+
+        let reads = self
+            .conn
+            .xreadgroup(&self.config.group, &self.config.consumer, &streams, &opts)
+            .await?;
+
+        Ok(reads
+            .into_iter()
+            .flat_map(|read| {
+                let stream = read.key;
+                read.entries.into_iter().map(move |entry| Delivery {
+                    stream: stream.clone(),
+                    entry,
+                    deliveries: 1,
+                })
+            })
+            .collect())
+    }
+
+    /// Claims entries that other consumers left pending for too long, and
+    /// looks up how often they were delivered.
+    async fn claim(&mut self, count: usize) -> io::Result<Vec<Delivery>> {
+        let mut batch = Vec::new();
+
+        for (stream, cursor) in self.config.streams.iter().zip(&mut self.cursors) {
+            let claim = self
+                .conn
+                .xautoclaim(
+                    stream,
+                    &self.config.group,
+                    &self.config.consumer,
+                    self.config.claim_min_idle,
+                    cursor,
+                    count,
+                )
+                .await?;
+
+            *cursor = claim.next_id;
+
+            let (Some(first), Some(last)) = (claim.entries.first(), claim.entries.last()) else {
+                continue;
+            };
+
+            // the claimed entries are now ours and come in ID order
+            let range = PendingRange {
+                start: first.id.clone(),
+                end: last.id.clone(),
+                count: claim.entries.len(),
+                min_idle: None,
+                consumer: Some(self.config.consumer.clone()),
+            };
+
+            let deliveries = self
+                .conn
+                .xpending_range(stream, &self.config.group, &range)
+                .await?
+                .into_iter()
+                .map(|pending| (pending.id, pending.deliveries))
+                .collect::<HashMap<_, _>>();
+
+            batch.extend(claim.entries.into_iter().map(|entry| Delivery {
+                stream: stream.clone(),
+                deliveries: deliveries.get(&entry.id).copied().unwrap_or(1),
+                entry,
+            }));
+        }
+
+        Ok(batch)
+    }
+
+    /// Copies an entry to the dead letter stream, adding the `stream`, `id`
+    /// and `deliveries` it had.
+    async fn dead_letter(&mut self, delivery: &Delivery) -> io::Result<()> {
+        let Some(dead_letter) = &self.config.dead_letter else {
+            return Ok(());
+        };
+
+        let mut fields = delivery.entry.fields.clone();
+        fields.extend([
+            (Bytes::from("stream"), delivery.stream.clone()),
+            (Bytes::from("id"), Bytes::from(delivery.entry.id.clone())),
+            (
+                Bytes::from("deliveries"),
+                Bytes::from(delivery.deliveries.to_string()),
+            ),
+        ]);
+
+        self.conn.xadd(dead_letter, "*", fields).await.map(drop)
+    }
+
+    async fn ack(&mut self, delivery: &Delivery) -> io::Result<()> {
+        self.conn
+            .xack(&delivery.stream, &self.config.group, &[&delivery.entry.id])
+            .await
+            .map(drop)
+    }
+}
+
+/// Runs the handler on an entry, returning it along with whether it
+/// succeeded.
+async fn handle<F>(handler: &F, delivery: Delivery) -> (Delivery, bool)
+where
+    F: AsyncFn(&Delivery) -> io::Result<()>,
+{
+    let ok = handler(&delivery).await.is_ok();
+    (delivery, ok)
+}
@@ -0,0 +1,528 @@
+//! Redis Cluster support: hash slots and a client that routes each command
+//! to the node serving its key.
+//!
+//! Keys map to one of [`SLOT_COUNT`] slots through CRC16. When a key holds a
+//! `{hashtag}`, only the tag is hashed, so related keys can be kept on the
+//! same node. See [`key_slot`] for the exact rule.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+
+use crate::client::{ConnectionAddr, ConnectionInfo, Sender};
+use crate::command::CommandTable;
+use crate::resp::convert::FromResp;
+use crate::resp::value::*;
+
+/// The number of hash slots keys are spread over.
+pub const SLOT_COUNT: u16 = 16384;
+
+/// How many -MOVED and -ASK redirects a command follows by default.
+pub const DEFAULT_MAX_REDIRECTS: usize = 5;
+
+/// CRC16 with the XMODEM parameters, the variant Redis uses for key slots.
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+
+    crc
+}
+
+/// The slot `key` belongs to.
+///
+/// If the key contains a `{` followed later by a `}` with at least one byte
+/// in between, only the bytes between the first `{` and the first `}` after
+/// it are hashed. Otherwise the whole key is.
+pub fn key_slot(key: impl AsRef<[u8]>) -> u16 {
+    crc16(hash_tag(key.as_ref())) % SLOT_COUNT
+}
+
+fn hash_tag(key: &[u8]) -> &[u8] {
+    let Some(open) = key.iter().position(|&b| b == b'{') else {
+        return key;
+    };
+
+    match key[open + 1..].iter().position(|&b| b == b'}') {
+        Some(len) if len > 0 => &key[open + 1..open + 1 + len],
+        _ => key,
+    }
+}
+
+/// The address of a cluster node.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NodeAddr {
+    pub host: String,
+    pub port: u16,
+}
+
+impl NodeAddr {
+    pub fn new(host: impl Into<String>, port: u16) -> Self {
+        Self {
+            host: host.into(),
+            port,
+        }
+    }
+
+    /// Parses `host:port`, as found in redirects. The host may be an IPv6
+    /// address, so the last colon is the separator.
+    pub fn parse(addr: &str) -> Option<Self> {
+        let (host, port) = addr.rsplit_once(':')?;
+
+        Some(Self::new(host, port.parse().ok()?))
+    }
+}
+
+impl fmt::Display for NodeAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.host, self.port)
+    }
+}
+
+/// A redirect error reply.
+enum Redirect {
+    /// The slot now lives on another node for good.
+    Moved(u16, NodeAddr),
+    /// The slot is being migrated and the key may already be on the target
+    /// node, which only serves it after ASKING.
+    Ask(NodeAddr),
+}
+
+impl Redirect {
+    fn parse(err: &io::Error) -> Option<Self> {
+        if err.kind() != io::ErrorKind::Other {
+            return None;
+        }
+
+        let msg = err.to_string();
+        let mut parts = msg.split_whitespace();
+        let kind = parts.next()?;
+        let slot = parts.next()?.parse().ok()?;
+        let addr = NodeAddr::parse(parts.next()?)?;
+
+        match kind {
+            "MOVED" => Some(Redirect::Moved(slot, addr)),
+            "ASK" => Some(Redirect::Ask(addr)),
+            _ => None,
+        }
+    }
+}
+
+/// A client for Redis Cluster.
+///
+/// The slot layout is read from one of the seed nodes with CLUSTER SHARDS,
+/// or CLUSTER SLOTS on servers older than 7.0, and a connection is opened
+/// to each master the first time a command is routed to it. The connection
+/// settings of the first seed, such as credentials and TLS, are used for
+/// every node.
+///
+/// Commands that hit a node no longer serving their slot are retried where
+/// the server points to: a -MOVED reply updates the layout and reloads it,
+/// an -ASK reply sends ASKING and the command to the target node just once.
+/// A node whose connection fails is dropped and the layout reloaded before
+/// trying again.
+pub struct ClusterClient {
+    info: ConnectionInfo,
+    seeds: Vec<NodeAddr>,
+    /// The masters in the layout, indexed by `slots`.
+    masters: Vec<NodeAddr>,
+    slots: Vec<Option<u16>>,
+    nodes: HashMap<NodeAddr, Sender>,
+    max_redirects: usize,
+    /// Finds the keys of the commands passed to [`ClusterClient::route`].
+    commands: CommandTable,
+}
+
+impl ClusterClient {
+    /// Connects to the cluster through the first seed that answers.
+    pub async fn connect(seeds: &[ConnectionInfo]) -> io::Result<Self> {
+        let info = seeds
+            .first()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no seed nodes"))?;
+
+        let seeds = seeds
+            .iter()
+            .map(|seed| match &seed.addr {
+                ConnectionAddr::Tcp { host, port } | ConnectionAddr::TcpTls { host, port } => {
+                    Ok(NodeAddr::new(host, *port))
+                }
+                ConnectionAddr::Unix(_) => Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "cluster nodes must be reached over TCP",
+                )),
+            })
+            .collect::<io::Result<_>>()?;
+
+        let mut client = Self {
+            info: info.clone(),
+            seeds,
+            masters: Vec::new(),
+            slots: vec![None; SLOT_COUNT as usize],
+            nodes: HashMap::new(),
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            commands: CommandTable::new(),
+        };
+        client.refresh().await?;
+
+        Ok(client)
+    }
+
+    /// Sets how many redirects a command follows before giving up.
+    pub fn set_max_redirects(&mut self, max_redirects: usize) {
+        self.max_redirects = max_redirects;
+    }
+
+    /// The table used to find the keys of routed commands, the built-in one
+    /// unless changed.
+    pub fn commands(&self) -> &CommandTable {
+        &self.commands
+    }
+
+    /// Gives access to the command table, e.g. to add a module's commands.
+    pub fn commands_mut(&mut self) -> &mut CommandTable {
+        &mut self.commands
+    }
+
+    /// Adds every command the cluster knows to the table with `COMMAND`,
+    /// returning how many were added.
+    pub async fn refresh_commands(&mut self) -> io::Result<usize> {
+        let reply = self.route(vec![bulk("COMMAND")].into()).await?;
+
+        self.commands.update(reply)
+    }
+
+    /// The masters serving at least one slot.
+    pub fn masters(&self) -> &[NodeAddr] {
+        &self.masters
+    }
+
+    /// The master believed to serve `slot`.
+    pub fn slot_owner(&self, slot: u16) -> Option<&NodeAddr> {
+        let index = (*self.slots.get(slot as usize)?)?;
+
+        self.masters.get(index as usize)
+    }
+
+    /// Reloads the slot layout, asking the known masters first and then the
+    /// seeds.
+    pub async fn refresh(&mut self) -> io::Result<()> {
+        let mut candidates = self.masters.clone();
+        for seed in &self.seeds {
+            if !candidates.contains(seed) {
+                candidates.push(seed.clone());
+            }
+        }
+
+        let mut last_err = io::Error::new(io::ErrorKind::NotConnected, "no node answered");
+
+        for addr in candidates {
+            match self.load_slots(&addr).await {
+                Ok(ranges) => {
+                    self.set_ranges(ranges);
+                    return Ok(());
+                }
+                Err(e) => last_err = e,
+            }
+        }
+
+        Err(last_err)
+    }
+
+    /// Runs a command whose keys all hash to the slot of `key`.
+    pub async fn command(
+        &mut self,
+        key: impl AsRef<[u8]>,
+        cmd: RespValue,
+    ) -> io::Result<RespValue> {
+        self.command_in_slot(key_slot(key), cmd).await
+    }
+
+    /// Runs a command on the master serving its keys, as found by
+    /// [`ClusterClient::commands`]. Commands without keys go to any master.
+    /// Fails with [`io::ErrorKind::InvalidInput`] if the keys are in
+    /// different slots.
+    pub async fn route(&mut self, cmd: RespValue) -> io::Result<RespValue> {
+        let slots = self
+            .commands
+            .keys_of(&cmd)
+            .into_iter()
+            .map(key_slot)
+            .collect::<Vec<_>>();
+
+        let Some(&slot) = slots.first() else {
+            if self.masters.is_empty() {
+                self.refresh().await?;
+            }
+            let addr =
+                self.masters.first().cloned().ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::NotConnected, "no master known")
+                })?;
+            return self.command_on(&addr, cmd).await;
+        };
+
+        if slots.iter().any(|&other| other != slot) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "keys in request don't hash to the same slot",
+            ));
+        }
+
+        self.command_in_slot(slot, cmd).await
+    }
+
+    /// Runs a command on the master serving `slot`, following redirects.
+    pub async fn command_in_slot(&mut self, slot: u16, cmd: RespValue) -> io::Result<RespValue> {
+        let mut addr = match self.slot_owner(slot) {
+            Some(addr) => addr.clone(),
+            None => {
+                self.refresh().await?;
+                self.slot_owner(slot).cloned().ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("slot {} is not served", slot),
+                    )
+                })?
+            }
+        };
+        let mut asking = false;
+
+        for _ in 0..=self.max_redirects {
+            let reply = if asking {
+                self.asking(&addr, cmd.clone()).await
+            } else {
+                self.command_on(&addr, cmd.clone()).await
+            };
+
+            let err = match reply {
+                Ok(reply) => return Ok(reply),
+                Err(e) => e,
+            };
+
+            match Redirect::parse(&err) {
+                Some(Redirect::Moved(slot, to)) => {
+                    self.set_owner(slot, to.clone());
+                    // best effort, the redirect alone is enough to go on
+                    let _ = self.refresh().await;
+                    addr = to;
+                    asking = false;
+                }
+                Some(Redirect::Ask(to)) => {
+                    addr = to;
+                    asking = true;
+                }
+                None if err.kind() == io::ErrorKind::Other => return Err(err),
+                None => {
+                    if self.refresh().await.is_err() {
+                        return Err(err);
+                    }
+                    addr = self.slot_owner(slot).cloned().ok_or(err)?;
+                    asking = false;
+                }
+            }
+        }
+
+        Err(io::Error::other(format!(
+            "too many redirects for slot {}",
+            slot
+        )))
+    }
+
+    /// Runs a command on a given node, without following redirects. Useful
+    /// for commands without keys, such as INFO or DBSIZE, sent to each of
+    /// the [`ClusterClient::masters`].
+    pub async fn command_on(&mut self, addr: &NodeAddr, cmd: RespValue) -> io::Result<RespValue> {
+        let conn = self.node(addr).await?;
+        let reply = conn.command(cmd).await;
+
+        if let Err(e) = &reply {
+            if e.kind() != io::ErrorKind::Other {
+                self.nodes.remove(addr);
+            }
+        }
+
+        reply
+    }
+
+    async fn asking(&mut self, addr: &NodeAddr, cmd: RespValue) -> io::Result<RespValue> {
+        match self.command_on(addr, array(vec![bulk("ASKING")])).await? {
+            RespValue::SimpleString(s) if &*s == "OK" => {}
+            _ => return Err(io::Error::from(io::ErrorKind::InvalidData)),
+        }
+
+        self.command_on(addr, cmd).await
+    }
+
+    async fn node(&mut self, addr: &NodeAddr) -> io::Result<&mut Sender> {
+        if !self.nodes.contains_key(addr) {
+            let conn = Sender::connect(&self.node_info(addr)).await?;
+            self.nodes.insert(addr.clone(), conn);
+        }
+
+        Ok(self.nodes.get_mut(addr).unwrap())
+    }
+
+    fn node_info(&self, addr: &NodeAddr) -> ConnectionInfo {
+        let mut info = self.info.clone();
+        let (host, port) = (addr.host.clone(), addr.port);
+
+        info.addr = match info.addr {
+            ConnectionAddr::TcpTls { .. } => ConnectionAddr::TcpTls { host, port },
+            _ => ConnectionAddr::Tcp { host, port },
+        };
+
+        info
+    }
+
+    fn tls(&self) -> bool {
+        matches!(self.info.addr, ConnectionAddr::TcpTls { .. })
+    }
+
+    /// Reads the slot ranges and their masters from `addr`.
+    async fn load_slots(&mut self, addr: &NodeAddr) -> io::Result<Vec<(u16, u16, NodeAddr)>> {
+        let shards = array(vec![bulk("CLUSTER"), bulk("SHARDS")]);
+
+        match self.command_on(addr, shards).await {
+            Ok(reply) => parse_shards(reply, self.tls()),
+            // CLUSTER SHARDS was added in 7.0
+            Err(e) if e.kind() == io::ErrorKind::Other => {
+                let slots = array(vec![bulk("CLUSTER"), bulk("SLOTS")]);
+                parse_slots(self.command_on(addr, slots).await?, addr)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn set_ranges(&mut self, ranges: Vec<(u16, u16, NodeAddr)>) {
+        self.masters.clear();
+        self.slots.fill(None);
+
+        for (start, end, addr) in ranges {
+            let index = self.master_index(addr);
+            for slot in start..=end.min(SLOT_COUNT - 1) {
+                self.slots[slot as usize] = Some(index);
+            }
+        }
+
+        // connections to nodes that no longer serve anything aren't needed
+        let masters = &self.masters;
+        self.nodes.retain(|addr, _| masters.contains(addr));
+    }
+
+    fn set_owner(&mut self, slot: u16, addr: NodeAddr) {
+        if slot < SLOT_COUNT {
+            let index = self.master_index(addr);
+            self.slots[slot as usize] = Some(index);
+        }
+    }
+
+    fn master_index(&mut self, addr: NodeAddr) -> u16 {
+        match self.masters.iter().position(|m| *m == addr) {
+            Some(index) => index as u16,
+            None => {
+                self.masters.push(addr);
+                (self.masters.len() - 1) as u16
+            }
+        }
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn slot_number(n: i64) -> io::Result<u16> {
+    u16::try_from(n)
+        .ok()
+        .filter(|&n| n < SLOT_COUNT)
+        .ok_or_else(|| invalid("slot out of range"))
+}
+
+/// Parses a CLUSTER SHARDS reply, in its RESP3 map or RESP2 flat array form.
+fn parse_shards(reply: RespValue, tls: bool) -> io::Result<Vec<(u16, u16, NodeAddr)>> {
+    let mut ranges = Vec::new();
+
+    for mut shard in Vec::<HashMap<String, RespValue>>::from_resp(reply)? {
+        let slots = Vec::<i64>::from_resp(shard.remove("slots").unwrap_or(RespValue::Null))?;
+        let nodes = Vec::<HashMap<String, RespValue>>::from_resp(
+            shard.remove("nodes").unwrap_or(RespValue::Null),
+        )?;
+
+        let master = nodes.into_iter().find(|node| {
+            node.get("role").and_then(RespValue::as_str) == Some("master")
+                && node.get("health").and_then(RespValue::as_str) != Some("fail")
+        });
+        let Some(mut master) = master else {
+            continue;
+        };
+
+        let mut host = |name: &str| {
+            master
+                .remove(name)
+                .and_then(|v| String::from_resp(v).ok())
+                .filter(|h| !h.is_empty() && h != "?")
+        };
+        let Some(host) = host("endpoint").or_else(|| host("ip")) else {
+            continue;
+        };
+
+        let port = master
+            .remove(if tls { "tls-port" } else { "port" })
+            .ok_or_else(|| invalid("shard master without a port"))?;
+        let port = u16::try_from(i64::from_resp(port)?).map_err(|_| invalid("invalid port"))?;
+
+        for pair in slots.chunks_exact(2) {
+            ranges.push((
+                slot_number(pair[0])?,
+                slot_number(pair[1])?,
+                NodeAddr::new(host.clone(), port),
+            ));
+        }
+    }
+
+    Ok(ranges)
+}
+
+/// Parses a CLUSTER SLOTS reply from `from`, which is the node meant by an
+/// empty host.
+fn parse_slots(reply: RespValue, from: &NodeAddr) -> io::Result<Vec<(u16, u16, NodeAddr)>> {
+    let mut ranges = Vec::new();
+
+    for range in Vec::<Vec<RespValue>>::from_resp(reply)? {
+        let mut range = range.into_iter();
+        let (Some(start), Some(end), Some(master)) = (range.next(), range.next(), range.next())
+        else {
+            return Err(invalid("short slot range"));
+        };
+
+        let mut master = Vec::<RespValue>::from_resp(master)?.into_iter();
+        let (Some(host), Some(port)) = (master.next(), master.next()) else {
+            return Err(invalid("slot range without a master"));
+        };
+
+        let host = String::from_resp(host)?;
+        let host = match &*host {
+            "" => from.host.clone(),
+            // the master's address is unknown
+            "?" => continue,
+            _ => host,
+        };
+        let port = u16::try_from(i64::from_resp(port)?).map_err(|_| invalid("invalid port"))?;
+
+        ranges.push((
+            slot_number(i64::from_resp(start)?)?,
+            slot_number(i64::from_resp(end)?)?,
+            NodeAddr::new(host, port),
+        ));
+    }
+
+    Ok(ranges)
+}
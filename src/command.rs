@@ -0,0 +1,551 @@
+//! Metadata about Redis commands, most importantly which of their arguments
+//! are keys, as needed to route commands in a cluster, to send reads to
+//! replicas, or to namespace keys.
+//!
+//! The positions of keys are described the way `COMMAND INFO` does: the
+//! first key, the last key (negative counting from the end) and the step
+//! between keys. Commands whose keys can't be described that way, such as
+//! EVAL or XREAD, are flagged as having movable keys and are handled by
+//! name. Container commands such as OBJECT are looked up along with their
+//! subcommand, e.g. `object|encoding`.
+//!
+//! A built-in table covers the common commands. [`CommandTable::update`]
+//! adds to it from the server's own `COMMAND` or `COMMAND INFO` reply.
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::LazyLock;
+
+use crate::client::Sender;
+use crate::resp::convert::FromResp;
+use crate::resp::value::*;
+
+/// What is known about a command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandInfo {
+    /// The lowercase name, `container|subcommand` for subcommands.
+    pub name: String,
+    /// The number of arguments, counting the name. A negative arity `-n`
+    /// means at least `n`.
+    pub arity: i64,
+    /// The position of the first key, 0 if the command has none.
+    pub first_key: i64,
+    /// The position of the last key, negative to count from the end.
+    pub last_key: i64,
+    pub step: i64,
+    pub readonly: bool,
+    pub write: bool,
+    pub blocking: bool,
+    /// Keys are found by parsing the arguments, e.g. after a `numkeys`
+    /// argument.
+    pub movable_keys: bool,
+}
+
+impl CommandInfo {
+    /// Whether `argc` arguments, counting the name, satisfy the arity.
+    pub fn accepts(&self, argc: usize) -> bool {
+        let argc = argc as i64;
+
+        if self.arity < 0 {
+            argc >= -self.arity
+        } else {
+            argc == self.arity
+        }
+    }
+
+    /// The positions of the keys among `argc` arguments, per the
+    /// first/last/step range.
+    fn key_range(&self, argc: usize) -> impl Iterator<Item = usize> {
+        let argc = argc as i64;
+        let last = match self.last_key {
+            last if self.first_key <= 0 => last.min(0),
+            last if last < 0 => argc + last,
+            last => last.min(argc - 1),
+        };
+
+        (self.first_key.max(1)..=last)
+            .step_by(self.step.max(1) as usize)
+            .map(|i| i as usize)
+    }
+}
+
+const R: u8 = 1;
+const W: u8 = 2;
+const B: u8 = 4;
+const M: u8 = 8;
+
+/// (name, arity, first key, last key, step, flags)
+#[rustfmt::skip]
+const BUILTIN: &[(&str, i64, i64, i64, i64, u8)] = &[
+    // generic
+    ("copy", -3, 1, 2, 1, W),
+    ("dbsize", 1, 0, 0, 0, R),
+    ("del", -2, 1, -1, 1, W),
+    ("dump", 2, 1, 1, 1, R),
+    ("exists", -2, 1, -1, 1, R),
+    ("expire", -3, 1, 1, 1, W),
+    ("expireat", -3, 1, 1, 1, W),
+    ("expiretime", 2, 1, 1, 1, R),
+    ("keys", 2, 0, 0, 0, R),
+    ("migrate", -6, 3, 3, 1, W | M),
+    ("move", 3, 1, 1, 1, W),
+    ("object|encoding", 3, 2, 2, 1, R),
+    ("object|freq", 3, 2, 2, 1, R),
+    ("object|idletime", 3, 2, 2, 1, R),
+    ("object|refcount", 3, 2, 2, 1, R),
+    ("persist", 2, 1, 1, 1, W),
+    ("pexpire", -3, 1, 1, 1, W),
+    ("pexpireat", -3, 1, 1, 1, W),
+    ("pexpiretime", 2, 1, 1, 1, R),
+    ("pttl", 2, 1, 1, 1, R),
+    ("randomkey", 1, 0, 0, 0, R),
+    ("rename", 3, 1, 2, 1, W),
+    ("renamenx", 3, 1, 2, 1, W),
+    ("restore", -4, 1, 1, 1, W),
+    ("scan", -2, 0, 0, 0, R),
+    ("sort", -2, 1, 1, 1, W | M),
+    ("sort_ro", -2, 1, 1, 1, R),
+    ("touch", -2, 1, -1, 1, R),
+    ("ttl", 2, 1, 1, 1, R),
+    ("type", 2, 1, 1, 1, R),
+    ("unlink", -2, 1, -1, 1, W),
+    // strings
+    ("append", 3, 1, 1, 1, W),
+    ("decr", 2, 1, 1, 1, W),
+    ("decrby", 3, 1, 1, 1, W),
+    ("get", 2, 1, 1, 1, R),
+    ("getdel", 2, 1, 1, 1, W),
+    ("getex", -2, 1, 1, 1, W),
+    ("getrange", 4, 1, 1, 1, R),
+    ("getset", 3, 1, 1, 1, W),
+    ("incr", 2, 1, 1, 1, W),
+    ("incrby", 3, 1, 1, 1, W),
+    ("incrbyfloat", 3, 1, 1, 1, W),
+    ("lcs", -3, 1, 2, 1, R),
+    ("mget", -2, 1, -1, 1, R),
+    ("mset", -3, 1, -1, 2, W),
+    ("msetnx", -3, 1, -1, 2, W),
+    ("psetex", 4, 1, 1, 1, W),
+    ("set", -3, 1, 1, 1, W),
+    ("setex", 4, 1, 1, 1, W),
+    ("setnx", 3, 1, 1, 1, W),
+    ("setrange", 4, 1, 1, 1, W),
+    ("strlen", 2, 1, 1, 1, R),
+    ("substr", 4, 1, 1, 1, R),
+    // bitmaps and hyperloglogs
+    ("bitcount", -2, 1, 1, 1, R),
+    ("bitfield", -2, 1, 1, 1, W),
+    ("bitfield_ro", -2, 1, 1, 1, R),
+    ("bitop", -4, 2, -1, 1, W),
+    ("bitpos", -3, 1, 1, 1, R),
+    ("getbit", 3, 1, 1, 1, R),
+    ("setbit", 4, 1, 1, 1, W),
+    ("pfadd", -2, 1, 1, 1, W),
+    ("pfcount", -2, 1, -1, 1, R),
+    ("pfmerge", -2, 1, -1, 1, W),
+    // lists
+    ("blmove", 6, 1, 2, 1, W | B),
+    ("blmpop", -5, 0, 0, 0, W | B | M),
+    ("blpop", -3, 1, -2, 1, W | B),
+    ("brpop", -3, 1, -2, 1, W | B),
+    ("brpoplpush", 4, 1, 2, 1, W | B),
+    ("lindex", 3, 1, 1, 1, R),
+    ("linsert", 5, 1, 1, 1, W),
+    ("llen", 2, 1, 1, 1, R),
+    ("lmove", 5, 1, 2, 1, W),
+    ("lmpop", -4, 0, 0, 0, W | M),
+    ("lpop", -2, 1, 1, 1, W),
+    ("lpos", -3, 1, 1, 1, R),
+    ("lpush", -3, 1, 1, 1, W),
+    ("lpushx", -3, 1, 1, 1, W),
+    ("lrange", 4, 1, 1, 1, R),
+    ("lrem", 4, 1, 1, 1, W),
+    ("lset", 4, 1, 1, 1, W),
+    ("ltrim", 4, 1, 1, 1, W),
+    ("rpop", -2, 1, 1, 1, W),
+    ("rpoplpush", 3, 1, 2, 1, W),
+    ("rpush", -3, 1, 1, 1, W),
+    ("rpushx", -3, 1, 1, 1, W),
+    // hashes
+    ("hdel", -3, 1, 1, 1, W),
+    ("hexists", 3, 1, 1, 1, R),
+    ("hget", 3, 1, 1, 1, R),
+    ("hgetall", 2, 1, 1, 1, R),
+    ("hincrby", 4, 1, 1, 1, W),
+    ("hincrbyfloat", 4, 1, 1, 1, W),
+    ("hkeys", 2, 1, 1, 1, R),
+    ("hlen", 2, 1, 1, 1, R),
+    ("hmget", -3, 1, 1, 1, R),
+    ("hmset", -4, 1, 1, 1, W),
+    ("hrandfield", -2, 1, 1, 1, R),
+    ("hscan", -3, 1, 1, 1, R),
+    ("hset", -4, 1, 1, 1, W),
+    ("hsetnx", 4, 1, 1, 1, W),
+    ("hstrlen", 3, 1, 1, 1, R),
+    ("hvals", 2, 1, 1, 1, R),
+    // sets
+    ("sadd", -3, 1, 1, 1, W),
+    ("scard", 2, 1, 1, 1, R),
+    ("sdiff", -2, 1, -1, 1, R),
+    ("sdiffstore", -3, 1, -1, 1, W),
+    ("sinter", -2, 1, -1, 1, R),
+    ("sintercard", -3, 0, 0, 0, R | M),
+    ("sinterstore", -3, 1, -1, 1, W),
+    ("sismember", 3, 1, 1, 1, R),
+    ("smembers", 2, 1, 1, 1, R),
+    ("smismember", -3, 1, 1, 1, R),
+    ("smove", 4, 1, 2, 1, W),
+    ("spop", -2, 1, 1, 1, W),
+    ("srandmember", -2, 1, 1, 1, R),
+    ("srem", -3, 1, 1, 1, W),
+    ("sscan", -3, 1, 1, 1, R),
+    ("sunion", -2, 1, -1, 1, R),
+    ("sunionstore", -3, 1, -1, 1, W),
+    // sorted sets
+    ("bzmpop", -5, 0, 0, 0, W | B | M),
+    ("bzpopmax", -3, 1, -2, 1, W | B),
+    ("bzpopmin", -3, 1, -2, 1, W | B),
+    ("zadd", -4, 1, 1, 1, W),
+    ("zcard", 2, 1, 1, 1, R),
+    ("zcount", 4, 1, 1, 1, R),
+    ("zdiff", -3, 0, 0, 0, R | M),
+    ("zdiffstore", -4, 1, 1, 1, W | M),
+    ("zincrby", 4, 1, 1, 1, W),
+    ("zinter", -3, 0, 0, 0, R | M),
+    ("zintercard", -3, 0, 0, 0, R | M),
+    ("zinterstore", -4, 1, 1, 1, W | M),
+    ("zlexcount", 4, 1, 1, 1, R),
+    ("zmpop", -4, 0, 0, 0, W | M),
+    ("zmscore", -3, 1, 1, 1, R),
+    ("zpopmax", -2, 1, 1, 1, W),
+    ("zpopmin", -2, 1, 1, 1, W),
+    ("zrandmember", -2, 1, 1, 1, R),
+    ("zrange", -4, 1, 1, 1, R),
+    ("zrangebylex", -4, 1, 1, 1, R),
+    ("zrangebyscore", -4, 1, 1, 1, R),
+    ("zrangestore", -5, 1, 2, 1, W),
+    ("zrank", -3, 1, 1, 1, R),
+    ("zrem", -3, 1, 1, 1, W),
+    ("zremrangebylex", 4, 1, 1, 1, W),
+    ("zremrangebyrank", 4, 1, 1, 1, W),
+    ("zremrangebyscore", 4, 1, 1, 1, W),
+    ("zrevrange", -4, 1, 1, 1, R),
+    ("zrevrangebylex", -4, 1, 1, 1, R),
+    ("zrevrangebyscore", -4, 1, 1, 1, R),
+    ("zrevrank", -3, 1, 1, 1, R),
+    ("zscan", -3, 1, 1, 1, R),
+    ("zscore", 3, 1, 1, 1, R),
+    ("zunion", -3, 0, 0, 0, R | M),
+    ("zunionstore", -4, 1, 1, 1, W | M),
+    // geo
+    ("geoadd", -5, 1, 1, 1, W),
+    ("geodist", -4, 1, 1, 1, R),
+    ("geohash", -2, 1, 1, 1, R),
+    ("geopos", -2, 1, 1, 1, R),
+    ("georadius", -6, 1, 1, 1, W | M),
+    ("georadius_ro", -6, 1, 1, 1, R),
+    ("georadiusbymember", -5, 1, 1, 1, W | M),
+    ("georadiusbymember_ro", -5, 1, 1, 1, R),
+    ("geosearch", -7, 1, 1, 1, R),
+    ("geosearchstore", -8, 1, 2, 1, W),
+    // streams
+    ("xack", -4, 1, 1, 1, W),
+    ("xadd", -5, 1, 1, 1, W),
+    ("xautoclaim", -6, 1, 1, 1, W),
+    ("xclaim", -6, 1, 1, 1, W),
+    ("xdel", -3, 1, 1, 1, W),
+    ("xgroup|create", -5, 2, 2, 1, W),
+    ("xgroup|createconsumer", 5, 2, 2, 1, W),
+    ("xgroup|delconsumer", 5, 2, 2, 1, W),
+    ("xgroup|destroy", 4, 2, 2, 1, W),
+    ("xgroup|setid", -5, 2, 2, 1, W),
+    ("xinfo|consumers", 4, 2, 2, 1, R),
+    ("xinfo|groups", 3, 2, 2, 1, R),
+    ("xinfo|stream", -3, 2, 2, 1, R),
+    ("xlen", 2, 1, 1, 1, R),
+    ("xpending", -3, 1, 1, 1, R),
+    ("xrange", -4, 1, 1, 1, R),
+    ("xread", -4, 0, 0, 0, R | B | M),
+    ("xreadgroup", -7, 0, 0, 0, W | B | M),
+    ("xrevrange", -4, 1, 1, 1, R),
+    ("xsetid", -3, 1, 1, 1, W),
+    ("xtrim", -4, 1, 1, 1, W),
+    // scripting and transactions
+    ("eval", -3, 0, 0, 0, M),
+    ("eval_ro", -3, 0, 0, 0, R | M),
+    ("evalsha", -3, 0, 0, 0, M),
+    ("evalsha_ro", -3, 0, 0, 0, R | M),
+    ("fcall", -3, 0, 0, 0, M),
+    ("fcall_ro", -3, 0, 0, 0, R | M),
+    ("discard", 1, 0, 0, 0, 0),
+    ("exec", 1, 0, 0, 0, 0),
+    ("multi", 1, 0, 0, 0, 0),
+    ("unwatch", 1, 0, 0, 0, 0),
+    ("watch", -2, 1, -1, 1, 0),
+    // pub/sub
+    ("psubscribe", -2, 0, 0, 0, 0),
+    ("publish", 3, 0, 0, 0, 0),
+    ("punsubscribe", -1, 0, 0, 0, 0),
+    ("spublish", 3, 1, 1, 1, 0),
+    ("ssubscribe", -2, 1, -1, 1, 0),
+    ("subscribe", -2, 0, 0, 0, 0),
+    ("sunsubscribe", -1, 1, -1, 1, 0),
+    ("unsubscribe", -1, 0, 0, 0, 0),
+    // connection and server
+    ("asking", 1, 0, 0, 0, 0),
+    ("auth", -2, 0, 0, 0, 0),
+    ("echo", 2, 0, 0, 0, 0),
+    ("flushall", -1, 0, 0, 0, W),
+    ("flushdb", -1, 0, 0, 0, W),
+    ("hello", -1, 0, 0, 0, 0),
+    ("info", -1, 0, 0, 0, 0),
+    ("memory|usage", -3, 2, 2, 1, R),
+    ("ping", -1, 0, 0, 0, 0),
+    ("quit", -1, 0, 0, 0, 0),
+    ("readonly", 1, 0, 0, 0, 0),
+    ("readwrite", 1, 0, 0, 0, 0),
+    ("role", 1, 0, 0, 0, 0),
+    ("select", 2, 0, 0, 0, 0),
+    ("time", 1, 0, 0, 0, 0),
+];
+
+static DEFAULT_TABLE: LazyLock<CommandTable> = LazyLock::new(CommandTable::new);
+
+/// The keys of an encoded command according to the built-in table, see
+/// [`CommandTable::keys_of`].
+pub fn keys_of(cmd: &RespValue) -> Vec<&[u8]> {
+    DEFAULT_TABLE.keys_of(cmd)
+}
+
+/// Command metadata by name.
+#[derive(Debug, Clone)]
+pub struct CommandTable {
+    commands: HashMap<String, CommandInfo>,
+}
+
+impl Default for CommandTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CommandTable {
+    /// The built-in table of common commands.
+    pub fn new() -> Self {
+        let commands = BUILTIN
+            .iter()
+            .map(|&(name, arity, first_key, last_key, step, flags)| {
+                let info = CommandInfo {
+                    name: name.into(),
+                    arity,
+                    first_key,
+                    last_key,
+                    step,
+                    readonly: flags & R != 0,
+                    write: flags & W != 0,
+                    blocking: flags & B != 0,
+                    movable_keys: flags & M != 0,
+                };
+                (info.name.clone(), info)
+            })
+            .collect();
+
+        Self { commands }
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Looks up a command by name, ignoring case. Subcommands are named
+    /// `container|subcommand`.
+    pub fn get(&self, name: &str) -> Option<&CommandInfo> {
+        self.commands.get(&name.to_ascii_lowercase())
+    }
+
+    pub fn insert(&mut self, info: CommandInfo) {
+        self.commands.insert(info.name.clone(), info);
+    }
+
+    /// Looks up an encoded command, trying its subcommand first.
+    pub fn lookup(&self, cmd: &RespValue) -> Option<&CommandInfo> {
+        self.lookup_args(&args(cmd)?)
+    }
+
+    fn lookup_args(&self, args: &[&[u8]]) -> Option<&CommandInfo> {
+        let name = String::from_utf8_lossy(args.first()?).to_ascii_lowercase();
+
+        if let Some(sub) = args.get(1) {
+            let full = format!("{}|{}", name, String::from_utf8_lossy(sub));
+            if let Some(info) = self.commands.get(&full.to_ascii_lowercase()) {
+                return Some(info);
+            }
+        }
+
+        self.commands.get(&name)
+    }
+
+    /// The arguments of an encoded command that are keys, in order. Unknown
+    /// commands, and commands that aren't arrays of strings, have none.
+    pub fn keys_of<'a>(&self, cmd: &'a RespValue) -> Vec<&'a [u8]> {
+        let Some(args) = args(cmd) else {
+            return Vec::new();
+        };
+        let Some(info) = self.lookup_args(&args) else {
+            return Vec::new();
+        };
+
+        if info.movable_keys {
+            if let Some(keys) = movable_keys(&info.name, &args) {
+                return keys;
+            }
+        }
+
+        info.key_range(args.len())
+            .filter_map(|i| args.get(i).copied())
+            .collect()
+    }
+
+    /// Adds the commands in a `COMMAND` or `COMMAND INFO` reply, along with
+    /// their subcommands, replacing those already known. Returns how many
+    /// were added.
+    pub fn update(&mut self, reply: RespValue) -> io::Result<usize> {
+        let mut count = 0;
+
+        // unknown commands asked for by name are null
+        for entry in Vec::<Option<Vec<RespValue>>>::from_resp(reply)?
+            .into_iter()
+            .flatten()
+        {
+            count += self.update_entry(entry)?;
+        }
+
+        Ok(count)
+    }
+
+    fn update_entry(&mut self, entry: Vec<RespValue>) -> io::Result<usize> {
+        let mut fields = entry.into_iter();
+        let [name, arity, flags, first_key, last_key, step] =
+            [(); 6].map(|_| fields.next().unwrap_or(RespValue::Null));
+
+        let flags = Vec::<String>::from_resp(flags)?;
+        let has = |flag: &str| flags.iter().any(|f| f.eq_ignore_ascii_case(flag));
+
+        self.insert(CommandInfo {
+            name: String::from_resp(name)?.to_ascii_lowercase(),
+            arity: i64::from_resp(arity)?,
+            first_key: i64::from_resp(first_key)?,
+            last_key: i64::from_resp(last_key)?,
+            step: i64::from_resp(step)?,
+            readonly: has("readonly"),
+            write: has("write"),
+            blocking: has("blocking"),
+            movable_keys: has("movablekeys"),
+        });
+
+        // ACL categories, tips and key specs come next, then subcommands
+        // since 7.0
+        let mut count = 1;
+        if let Some(subcommands) = fields.nth(3) {
+            for sub in Vec::<Vec<RespValue>>::from_resp(subcommands)? {
+                count += self.update_entry(sub)?;
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Adds every command the server knows with `COMMAND`.
+    pub async fn refresh(&mut self, sender: &mut Sender) -> io::Result<usize> {
+        let reply = sender.command(vec![bulk("COMMAND")].into()).await?;
+
+        self.update(reply)
+    }
+}
+
+/// The arguments of a command as bytes.
+fn args(cmd: &RespValue) -> Option<Vec<&[u8]>> {
+    let RespValue::Array(Some(items)) = cmd else {
+        return None;
+    };
+
+    items
+        .iter()
+        .map(|item| match item {
+            RespValue::BulkString(Some(buf)) => Some(&buf[..]),
+            RespValue::SimpleString(s) => Some(s.as_bytes()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// `count` keys after a numkeys argument at `at`.
+fn numkeys<'a>(args: &[&'a [u8]], at: usize) -> Option<Vec<&'a [u8]>> {
+    let count: usize = std::str::from_utf8(args.get(at)?).ok()?.parse().ok()?;
+
+    args.get(at + 1..at + 1 + count).map(<[_]>::to_vec)
+}
+
+/// The index of the first `token` argument at or after `from`.
+fn position(args: &[&[u8]], from: usize, token: &str) -> Option<usize> {
+    (from..args.len()).find(|&i| args[i].eq_ignore_ascii_case(token.as_bytes()))
+}
+
+/// The keys of commands flagged as having movable keys. Returns None for
+/// commands not known here, which fall back to their key range.
+fn movable_keys<'a>(name: &str, args: &[&'a [u8]]) -> Option<Vec<&'a [u8]>> {
+    let keys = match name {
+        "eval" | "eval_ro" | "evalsha" | "evalsha_ro" | "fcall" | "fcall_ro" => {
+            numkeys(args, 2).unwrap_or_default()
+        }
+        "lmpop" | "sintercard" | "zdiff" | "zinter" | "zintercard" | "zmpop" | "zunion" => {
+            numkeys(args, 1).unwrap_or_default()
+        }
+        // after the timeout
+        "blmpop" | "bzmpop" => numkeys(args, 2).unwrap_or_default(),
+        // the destination, then the sources
+        "zdiffstore" | "zinterstore" | "zunionstore" => {
+            let mut keys = args.get(1..2).map(<[_]>::to_vec).unwrap_or_default();
+            keys.extend(numkeys(args, 2).unwrap_or_default());
+            keys
+        }
+        // STREAMS key [key ...] id [id ...], after GROUP group consumer for
+        // XREADGROUP, which could be named like the token
+        "xread" | "xreadgroup" => {
+            let from = if name == "xread" { 1 } else { 4 };
+            match position(args, from, "STREAMS") {
+                Some(i) => {
+                    let rest = &args[i + 1..];
+                    rest[..rest.len() / 2].to_vec()
+                }
+                None => Vec::new(),
+            }
+        }
+        // an empty key means the keys follow KEYS
+        "migrate" => match args.get(3) {
+            Some(key) if !key.is_empty() => vec![*key],
+            _ => match position(args, 6, "KEYS") {
+                Some(i) => args[i + 1..].to_vec(),
+                None => Vec::new(),
+            },
+        },
+        "georadius" | "georadiusbymember" | "sort" => {
+            let mut keys = args.get(1..2).map(<[_]>::to_vec).unwrap_or_default();
+            let store = (2..args.len()).find(|&i| {
+                args[i].eq_ignore_ascii_case(b"STORE")
+                    || (name != "sort" && args[i].eq_ignore_ascii_case(b"STOREDIST"))
+            });
+            if let Some(dest) = store.and_then(|i| args.get(i + 1)) {
+                keys.push(dest);
+            }
+            keys
+        }
+        _ => return None,
+    };
+
+    Some(keys)
+}
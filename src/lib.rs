@@ -1,2 +1,5 @@
 pub mod client;
+pub mod cluster;
+pub mod command;
+pub mod pattern;
 pub mod resp;
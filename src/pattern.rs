@@ -0,0 +1,164 @@
+//! Glob-style pattern matching with the semantics of Redis's
+//! `stringmatchlen`, as used by PSUBSCRIBE, KEYS and SCAN MATCH.
+//!
+//! - `*` matches any run of bytes, including an empty one
+//! - `?` matches exactly one byte
+//! - `[abc]`, `[a-z]` and `[^x]` match one byte from (or not from) a set
+//! - `\` escapes the next byte, in and out of a set
+//!
+//! Matching works on raw bytes. Like Redis, a malformed pattern (such as an
+//! unterminated `[`) is not an error, it is interpreted as best as possible.
+
+/// Redis gives up on patterns that recurse deeper than this.
+const MAX_NESTING: usize = 1000;
+
+/// Returns true when `string` matches the glob `pattern`.
+pub fn matches(pattern: impl AsRef<[u8]>, string: impl AsRef<[u8]>) -> bool {
+    let mut skip_longer = false;
+    match_from(
+        pattern.as_ref(),
+        string.as_ref(),
+        false,
+        &mut skip_longer,
+        0,
+    )
+}
+
+/// Like [`matches`], ignoring ASCII case.
+pub fn matches_nocase(pattern: impl AsRef<[u8]>, string: impl AsRef<[u8]>) -> bool {
+    let mut skip_longer = false;
+    match_from(pattern.as_ref(), string.as_ref(), true, &mut skip_longer, 0)
+}
+
+fn eq(a: u8, b: u8, nocase: bool) -> bool {
+    if nocase {
+        a.eq_ignore_ascii_case(&b)
+    } else {
+        a == b
+    }
+}
+
+fn match_from(
+    mut p: &[u8],
+    mut s: &[u8],
+    nocase: bool,
+    skip_longer: &mut bool,
+    nesting: usize,
+) -> bool {
+    if nesting > MAX_NESTING {
+        return false;
+    }
+
+    while !p.is_empty() && !s.is_empty() {
+        match p[0] {
+            b'*' => {
+                while p.len() > 1 && p[1] == b'*' {
+                    p = &p[1..];
+                }
+
+                if p.len() == 1 {
+                    return true;
+                }
+
+                while !s.is_empty() {
+                    if match_from(&p[1..], s, nocase, skip_longer, nesting + 1) {
+                        return true;
+                    }
+
+                    // the rest of the pattern cannot match anywhere further
+                    // along, so neither can a longer match for this star
+                    if *skip_longer {
+                        return false;
+                    }
+
+                    s = &s[1..];
+                }
+
+                *skip_longer = true;
+                return false;
+            }
+            b'?' => {
+                p = &p[1..];
+                s = &s[1..];
+            }
+            b'[' => {
+                p = &p[1..];
+
+                let not = p.first() == Some(&b'^');
+                if not {
+                    p = &p[1..];
+                }
+
+                let c = s[0];
+                let mut matched = false;
+
+                loop {
+                    match p {
+                        // an escaped byte is always compared exactly
+                        [b'\\', escaped, ..] => {
+                            matched |= *escaped == c;
+                            p = &p[2..];
+                        }
+                        [b']', ..] => {
+                            p = &p[1..];
+                            break;
+                        }
+                        // unterminated set
+                        [] => break,
+                        [start, b'-', end, ..] => {
+                            let (mut start, mut end, mut c) = (*start, *end, c);
+
+                            if start > end {
+                                std::mem::swap(&mut start, &mut end);
+                            }
+
+                            if nocase {
+                                start = start.to_ascii_lowercase();
+                                end = end.to_ascii_lowercase();
+                                c = c.to_ascii_lowercase();
+                            }
+
+                            matched |= (start..=end).contains(&c);
+                            p = &p[3..];
+                        }
+                        [b, ..] => {
+                            matched |= eq(*b, c, nocase);
+                            p = &p[1..];
+                        }
+                    }
+                }
+
+                if matched == not {
+                    return false;
+                }
+
+                s = &s[1..];
+            }
+            b'\\' if p.len() >= 2 => {
+                if !eq(p[1], s[0], nocase) {
+                    return false;
+                }
+
+                p = &p[2..];
+                s = &s[1..];
+            }
+            b => {
+                if !eq(b, s[0], nocase) {
+                    return false;
+                }
+
+                p = &p[1..];
+                s = &s[1..];
+            }
+        }
+
+        if s.is_empty() {
+            while p.first() == Some(&b'*') {
+                p = &p[1..];
+            }
+            break;
+        }
+    }
+
+    p.is_empty() && s.is_empty()
+}
@@ -0,0 +1,191 @@
+//! Conversions between Rust values and RESP command arguments and replies.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::io;
+
+use bytes::Bytes;
+
+use super::value::{bulk, RespValue};
+
+/// A value that can be sent as a command argument. Everything is encoded as
+/// a bulk string, numbers in their decimal form.
+pub trait ToArg {
+    fn to_arg(&self) -> RespValue;
+}
+
+impl<T: ToArg + ?Sized> ToArg for &T {
+    fn to_arg(&self) -> RespValue {
+        (**self).to_arg()
+    }
+}
+
+macro_rules! bytes_arg {
+    ($($ty:ty),*) => {$(
+        impl ToArg for $ty {
+            fn to_arg(&self) -> RespValue {
+                bulk(self)
+            }
+        }
+    )*};
+}
+
+bytes_arg!(str, String, [u8], Vec<u8>, Bytes);
+
+macro_rules! display_arg {
+    ($($ty:ty),*) => {$(
+        impl ToArg for $ty {
+            fn to_arg(&self) -> RespValue {
+                bulk(self.to_string())
+            }
+        }
+    )*};
+}
+
+display_arg!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64);
+
+impl<const N: usize> ToArg for [u8; N] {
+    fn to_arg(&self) -> RespValue {
+        bulk(self)
+    }
+}
+
+fn invalid() -> io::Error {
+    io::Error::from(io::ErrorKind::InvalidData)
+}
+
+/// A type that a reply can be converted into. Conversions fail with
+/// [`io::ErrorKind::InvalidData`] when the reply has the wrong shape.
+pub trait FromResp: Sized {
+    fn from_resp(val: RespValue) -> io::Result<Self>;
+}
+
+impl FromResp for RespValue {
+    fn from_resp(val: RespValue) -> io::Result<Self> {
+        Ok(val)
+    }
+}
+
+/// Accepts any reply, for commands called only for their side effects.
+impl FromResp for () {
+    fn from_resp(_: RespValue) -> io::Result<Self> {
+        Ok(())
+    }
+}
+
+impl FromResp for i64 {
+    fn from_resp(val: RespValue) -> io::Result<Self> {
+        match val {
+            RespValue::Integer(i) => Ok(i),
+            // numbers inside scripts and MULTI replies may arrive as strings
+            val => val
+                .as_str()
+                .and_then(|s| s.parse().ok())
+                .ok_or_else(invalid),
+        }
+    }
+}
+
+impl FromResp for f64 {
+    fn from_resp(val: RespValue) -> io::Result<Self> {
+        match val {
+            RespValue::Double(d) => Ok(d),
+            RespValue::Integer(i) => Ok(i as f64),
+            val => val
+                .as_str()
+                .and_then(|s| s.parse().ok())
+                .ok_or_else(invalid),
+        }
+    }
+}
+
+impl FromResp for bool {
+    fn from_resp(val: RespValue) -> io::Result<Self> {
+        match val {
+            RespValue::Boolean(b) => Ok(b),
+            RespValue::Integer(i) => Ok(i != 0),
+            RespValue::SimpleString(s) => Ok(&*s == "OK"),
+            // a false Lua value becomes a null reply
+            val if val.is_null() => Ok(false),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl FromResp for String {
+    fn from_resp(val: RespValue) -> io::Result<Self> {
+        match val {
+            RespValue::SimpleString(s) | RespValue::BigNumber(s) => Ok(s.into()),
+            RespValue::BulkString(Some(buf)) | RespValue::VerbatimString(_, buf) => {
+                String::from_utf8(buf.into()).map_err(|_| invalid())
+            }
+            RespValue::Integer(i) => Ok(i.to_string()),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl FromResp for Vec<u8> {
+    fn from_resp(val: RespValue) -> io::Result<Self> {
+        match val {
+            RespValue::BulkString(Some(buf)) | RespValue::VerbatimString(_, buf) => Ok(buf.into()),
+            RespValue::SimpleString(s) => Ok(String::from(s).into_bytes()),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl FromResp for Bytes {
+    fn from_resp(val: RespValue) -> io::Result<Self> {
+        Vec::<u8>::from_resp(val).map(Bytes::from)
+    }
+}
+
+/// Maps a null reply to None.
+impl<T: FromResp> FromResp for Option<T> {
+    fn from_resp(val: RespValue) -> io::Result<Self> {
+        if val.is_null() {
+            return Ok(None);
+        }
+
+        T::from_resp(val).map(Some)
+    }
+}
+
+impl<T: FromResp> FromResp for Vec<T> {
+    fn from_resp(val: RespValue) -> io::Result<Self> {
+        match val {
+            RespValue::Array(Some(items)) | RespValue::Set(items) | RespValue::Push(items) => {
+                items.into_iter().map(T::from_resp).collect()
+            }
+            // a missing key reads as an empty collection
+            val if val.is_null() => Ok(Vec::new()),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Accepts RESP3 maps as well as the flat key/value arrays RESP2 uses
+/// instead.
+impl<K: FromResp + Eq + Hash, V: FromResp> FromResp for HashMap<K, V> {
+    fn from_resp(val: RespValue) -> io::Result<Self> {
+        match val {
+            RespValue::Map(pairs) => pairs
+                .into_iter()
+                .map(|(k, v)| Ok((K::from_resp(k)?, V::from_resp(v)?)))
+                .collect(),
+            RespValue::Array(Some(items)) if items.len() % 2 == 0 => {
+                let mut items = items.into_iter();
+                let mut map = HashMap::with_capacity(items.len() / 2);
+
+                while let (Some(k), Some(v)) = (items.next(), items.next()) {
+                    map.insert(K::from_resp(k)?, V::from_resp(v)?);
+                }
+
+                Ok(map)
+            }
+            val if val.is_null() => Ok(HashMap::new()),
+            _ => Err(invalid()),
+        }
+    }
+}
@@ -14,20 +14,53 @@ enum Op {
     Integer,
     BulkString,
     Array,
+    Null,
+    Boolean,
+    Double,
+    BigNumber,
+    BlobError,
+    VerbatimString,
+    Map,
+    Set,
+    Push,
+    Attribute,
+}
+
+/// The most items preallocated for an aggregate before any have arrived.
+const MAX_PREALLOC: i64 = 1024;
+
+/// The aggregate type an ArrayContext is collecting items for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Aggregate {
+    Array,
+    Map,
+    Set,
+    Push,
+    Attribute,
 }
 
-#[derive(Default)]
 struct ArrayContext {
+    kind: Aggregate,
     rem: i64,
     items: Vec<RespValue>,
 }
 
 impl ArrayContext {
-    fn new(len: i64) -> Self {
-        Self {
+    fn new(kind: Aggregate, len: i64) -> io::Result<Self> {
+        // maps and attributes hold a key and a value per entry
+        let len = match kind {
+            Aggregate::Map | Aggregate::Attribute => len
+                .checked_mul(2)
+                .ok_or_else(|| Error::new(InvalidData, "invalid aggregate length"))?,
+            _ => len,
+        };
+
+        // the length comes off the wire, so only preallocate up to a bound
+        Ok(Self {
+            kind,
             rem: len,
-            items: Vec::with_capacity(len as usize),
-        }
+            items: Vec::with_capacity(len.min(MAX_PREALLOC) as usize),
+        })
     }
 
     fn push(&mut self, item: RespValue) {
@@ -41,8 +74,23 @@ impl ArrayContext {
         self.rem == 0
     }
 
-    fn items(self) -> Vec<RespValue> {
-        self.items
+    /// Builds the finished value. Attributes carry metadata about the
+    /// reply that follows them, they are dropped and yield None.
+    fn into_value(self) -> Option<RespValue> {
+        match self.kind {
+            Aggregate::Array => Some(self.items.into()),
+            Aggregate::Set => Some(Set(self.items)),
+            Aggregate::Push => Some(Push(self.items)),
+            Aggregate::Map => {
+                let mut items = self.items.into_iter();
+                let mut pairs = Vec::with_capacity(items.len() / 2);
+                while let (Some(k), Some(v)) = (items.next(), items.next()) {
+                    pairs.push((k, v));
+                }
+                Some(Map(pairs))
+            }
+            Aggregate::Attribute => None,
+        }
     }
 }
 
@@ -71,6 +119,16 @@ impl RespDecoder {
                     b':' => Some(Op::Integer),
                     b'$' => Some(Op::BulkString),
                     b'*' => Some(Op::Array),
+                    b'_' => Some(Op::Null),
+                    b'#' => Some(Op::Boolean),
+                    b',' => Some(Op::Double),
+                    b'(' => Some(Op::BigNumber),
+                    b'!' => Some(Op::BlobError),
+                    b'=' => Some(Op::VerbatimString),
+                    b'%' => Some(Op::Map),
+                    b'~' => Some(Op::Set),
+                    b'>' => Some(Op::Push),
+                    b'|' => Some(Op::Attribute),
                     _ => {
                         return Err(Error::new(
                             InvalidData,
@@ -144,7 +202,35 @@ impl RespDecoder {
         Ok(Integer(self.inner_i64(src)?))
     }
 
-    fn get_bulk_string(&mut self, src: &mut BytesMut) -> io::Result<RespValue> {
+    fn get_null(&mut self, src: &mut BytesMut) -> io::Result<RespValue> {
+        self.inner_string(src)?;
+        Ok(Null)
+    }
+
+    fn get_boolean(&mut self, src: &mut BytesMut) -> io::Result<RespValue> {
+        match &*self.inner_string(src)? {
+            "t" => Ok(Boolean(true)),
+            "f" => Ok(Boolean(false)),
+            _ => Err(Error::new(InvalidData, "invalid boolean")),
+        }
+    }
+
+    fn get_double(&mut self, src: &mut BytesMut) -> io::Result<RespValue> {
+        let num = self
+            .inner_string(src)?
+            .parse()
+            .map_err(|_| Error::new(InvalidData, "invalid double"))?;
+
+        Ok(Double(num))
+    }
+
+    fn get_big_number(&mut self, src: &mut BytesMut) -> io::Result<RespValue> {
+        Ok(BigNumber(self.inner_string(src)?.into()))
+    }
+
+    /// Takes a length-prefixed payload, as used by bulk strings, blob errors
+    /// and verbatim strings. Returns None for the RESP2 null bulk string.
+    fn inner_blob(&mut self, src: &mut BytesMut) -> io::Result<Option<Box<[u8]>>> {
         // if the length has already been calculated, use it
         let len = match self.cached_len {
             Some(len) => len,
@@ -152,7 +238,7 @@ impl RespDecoder {
                 let len = self.inner_i64(src)?;
 
                 if len == -1 {
-                    return Ok(BulkString(None));
+                    return Ok(None);
                 }
 
                 self.cached_len = Some(len);
@@ -168,55 +254,118 @@ impl RespDecoder {
         let buf: Box<[_]> = src.split_to(len as usize)[..].into();
         src.advance(2);
 
-        Ok(bulk(buf))
+        Ok(Some(buf))
+    }
+
+    fn get_bulk_string(&mut self, src: &mut BytesMut) -> io::Result<RespValue> {
+        Ok(BulkString(self.inner_blob(src)?))
+    }
+
+    fn get_blob_error(&mut self, src: &mut BytesMut) -> io::Result<RespValue> {
+        let buf = self
+            .inner_blob(src)?
+            .ok_or_else(|| Error::new(InvalidData, "invalid blob error length"))?;
+
+        Ok(BlobError(buf))
+    }
+
+    fn get_verbatim_string(&mut self, src: &mut BytesMut) -> io::Result<RespValue> {
+        let buf = self
+            .inner_blob(src)?
+            .ok_or_else(|| Error::new(InvalidData, "invalid verbatim string length"))?;
+
+        // the payload is prefixed with a three byte format and a colon
+        match (buf.get(..3).map(std::str::from_utf8), buf.get(3)) {
+            (Some(Ok(fmt)), Some(b':')) => Ok(VerbatimString(fmt.into(), buf[4..].into())),
+            _ => Err(Error::new(InvalidData, "invalid verbatim string format")),
+        }
     }
 
     /// Returns an ArrayContext instead of a RedisValue. When resume_decode
     /// gets a RedisValue from one of the above functions, it will push it
     /// to the topmost ArrayContext on the stack, which keeps track of how
     /// many items are left to be decoded.
-    fn get_array_context(&mut self, src: &mut BytesMut) -> io::Result<Option<ArrayContext>> {
+    fn get_array_context(
+        &mut self,
+        kind: Aggregate,
+        src: &mut BytesMut,
+    ) -> io::Result<Option<ArrayContext>> {
         let len = self.inner_i64(src)?;
 
-        if len == -1 {
+        if len == -1 && kind == Aggregate::Array {
             return Ok(None);
         }
 
-        Ok(Some(ArrayContext::new(len)))
+        if len < 0 {
+            return Err(Error::new(InvalidData, "invalid aggregate length"));
+        }
+
+        ArrayContext::new(kind, len).map(Some)
+    }
+
+    /// Starts decoding an aggregate. Returns the value straight away if it
+    /// has no items, otherwise the context is pushed onto the stack and
+    /// None is returned.
+    fn get_aggregate(
+        &mut self,
+        kind: Aggregate,
+        src: &mut BytesMut,
+    ) -> io::Result<Option<RespValue>> {
+        match self.get_array_context(kind, src)? {
+            None => Ok(Some(Array(None))),
+            Some(ctx) if ctx.is_complete() => Ok(ctx.into_value()),
+            Some(ctx) => {
+                self.stack.push(ctx);
+                Ok(None)
+            }
+        }
     }
 
     /// Begin decoding the BytesMut instance, or resume where it left off.
     pub(crate) fn resume_decode(&mut self, src: &mut BytesMut) -> io::Result<RespValue> {
         loop {
-            let mut val = match self.get_op(src)? {
-                Op::SimpleString => self.get_simple_string(src)?,
-                Op::Error => self.get_error(src)?,
-                Op::Integer => self.get_integer(src)?,
-                Op::BulkString => self.get_bulk_string(src)?,
-                Op::Array => match self.get_array_context(src)? {
-                    None => Array(None),
-                    Some(ctx) if ctx.is_complete() => ctx.items().into(),
-                    Some(ctx) => {
-                        self.stack.push(ctx);
-                        self.op = None;
-                        continue;
-                    }
-                },
+            let val = match self.get_op(src)? {
+                Op::SimpleString => Some(self.get_simple_string(src)?),
+                Op::Error => Some(self.get_error(src)?),
+                Op::Integer => Some(self.get_integer(src)?),
+                Op::BulkString => Some(self.get_bulk_string(src)?),
+                Op::Null => Some(self.get_null(src)?),
+                Op::Boolean => Some(self.get_boolean(src)?),
+                Op::Double => Some(self.get_double(src)?),
+                Op::BigNumber => Some(self.get_big_number(src)?),
+                Op::BlobError => Some(self.get_blob_error(src)?),
+                Op::VerbatimString => Some(self.get_verbatim_string(src)?),
+                Op::Array => self.get_aggregate(Aggregate::Array, src)?,
+                Op::Map => self.get_aggregate(Aggregate::Map, src)?,
+                Op::Set => self.get_aggregate(Aggregate::Set, src)?,
+                Op::Push => self.get_aggregate(Aggregate::Push, src)?,
+                Op::Attribute => self.get_aggregate(Aggregate::Attribute, src)?,
             };
 
             self.op = None;
 
-            loop {
-                let Some(mut ctx) = self.stack.pop() else { return Ok(val) };
+            if let Some(val) = val.and_then(|val| self.complete(val)) {
+                return Ok(val);
+            }
+        }
+    }
 
-                ctx.push(val);
-                if !ctx.is_complete() {
-                    self.stack.push(ctx);
-                    break;
-                }
+    /// Pushes a decoded value into the enclosing aggregates, returning the
+    /// outermost value once every aggregate on the stack is complete.
+    fn complete(&mut self, mut val: RespValue) -> Option<RespValue> {
+        loop {
+            let Some(mut ctx) = self.stack.pop() else {
+                return Some(val);
+            };
 
-                val = ctx.items().into();
+            ctx.push(val);
+            if !ctx.is_complete() {
+                self.stack.push(ctx);
+                return None;
             }
+
+            // a finished attribute annotates the next value at this level
+            val = ctx.into_value()?;
         }
     }
 }
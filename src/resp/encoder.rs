@@ -55,5 +55,80 @@ pub fn resp_encode(item: RespValue, dst: &mut BytesMut) {
                 dst.put_slice(b"*-1\r\n");
             }
         }
+        RespValue::Null => {
+            dst.reserve(3);
+            dst.put_slice(b"_\r\n");
+        }
+        RespValue::Boolean(b) => {
+            dst.reserve(4);
+            dst.put_slice(if b { b"#t\r\n" } else { b"#f\r\n" });
+        }
+        RespValue::Double(d) => {
+            let str = if d.is_nan() {
+                "nan".into()
+            } else if d.is_infinite() {
+                if d > 0.0 { "inf" } else { "-inf" }.into()
+            } else {
+                d.to_string()
+            };
+            dst.reserve(str.len() + 3);
+            dst.put_slice(b",");
+            dst.put_slice(str.as_bytes());
+            dst.put_slice(b"\r\n");
+        }
+        RespValue::BigNumber(n) => {
+            dst.reserve(n.len() + 3);
+            dst.put_slice(b"(");
+            dst.put_slice(n.as_bytes());
+            dst.put_slice(b"\r\n");
+        }
+        RespValue::BlobError(e) => put_blob(b'!', &e, dst),
+        RespValue::VerbatimString(fmt, s) => {
+            let mut blob = Vec::with_capacity(fmt.len() + 1 + s.len());
+            blob.extend_from_slice(fmt.as_bytes());
+            blob.push(b':');
+            blob.extend_from_slice(&s);
+            put_blob(b'=', &blob, dst);
+        }
+        RespValue::Map(pairs) => {
+            put_header(b'%', pairs.len(), dst);
+
+            for (k, v) in pairs {
+                resp_encode(k, dst);
+                resp_encode(v, dst);
+            }
+        }
+        RespValue::Set(items) => {
+            put_header(b'~', items.len(), dst);
+
+            for item in items {
+                resp_encode(item, dst);
+            }
+        }
+        RespValue::Push(items) => {
+            put_header(b'>', items.len(), dst);
+
+            for item in items {
+                resp_encode(item, dst);
+            }
+        }
     }
 }
+
+/// Writes an opcode followed by a length, e.g. `%2<CRLF>`.
+fn put_header(op: u8, len: usize, dst: &mut BytesMut) {
+    let len = len.to_string();
+    dst.reserve(len.len() + 3);
+    dst.put_u8(op);
+    dst.put_slice(len.as_bytes());
+    dst.put_slice(b"\r\n");
+}
+
+/// Writes a length-prefixed payload in the same shape as a bulk string.
+fn put_blob(op: u8, blob: &[u8], dst: &mut BytesMut) {
+    put_header(op, blob.len(), dst);
+
+    dst.reserve(blob.len() + 2);
+    dst.put_slice(blob);
+    dst.put_slice(b"\r\n");
+}
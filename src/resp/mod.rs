@@ -5,6 +5,7 @@ use tokio_util::codec::{Decoder, Encoder};
 
 use value::RespValue;
 
+pub mod convert;
 pub mod decoder;
 pub mod encoder;
 pub mod value;
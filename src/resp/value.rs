@@ -1,12 +1,23 @@
 use std::str;
 
-#[derive(PartialEq, Eq, Clone)]
+#[derive(Clone)]
 pub enum RespValue {
     SimpleString(Box<str>),
     SimpleError(Box<str>),
     Integer(i64),
     BulkString(Option<Box<[u8]>>),
     Array(Option<Vec<RespValue>>),
+    // RESP3 types, only sent by the server after HELLO 3
+    Null,
+    Boolean(bool),
+    Double(f64),
+    BigNumber(Box<str>),
+    BlobError(Box<[u8]>),
+    /// A three character format (e.g. `txt`, `mkd`) and the string itself.
+    VerbatimString(Box<str>, Box<[u8]>),
+    Map(Vec<(RespValue, RespValue)>),
+    Set(Vec<RespValue>),
+    Push(Vec<RespValue>),
 }
 
 use RespValue::*;
@@ -17,6 +28,7 @@ impl RespValue {
             BulkString(Some(buf)) => str::from_utf8(&buf[..]).ok(),
             SimpleString(val) => Some(val),
             SimpleError(val) => Some(val),
+            VerbatimString(_, buf) => str::from_utf8(&buf[..]).ok(),
             // no other types can be converted to a str
             _ => None,
         }
@@ -25,6 +37,11 @@ impl RespValue {
     pub fn as_buf(&self) -> Option<&str> {
         None
     }
+
+    /// Returns true for the RESP2 and RESP3 null representations.
+    pub fn is_null(&self) -> bool {
+        matches!(self, Null | BulkString(None) | Array(None))
+    }
 }
 
 use std::fmt;
@@ -40,10 +57,53 @@ impl fmt::Debug for RespValue {
             RespValue::BulkString(None) => write!(f, "BulkString(None)"),
             RespValue::Array(Some(arr)) => write!(f, "Array<{}>({:?}))", arr.len(), arr),
             RespValue::Array(None) => write!(f, "Array(None)"),
+            RespValue::Null => write!(f, "Null"),
+            RespValue::Boolean(val) => write!(f, "Boolean({})", val),
+            RespValue::Double(val) => write!(f, "Double({})", val),
+            RespValue::BigNumber(val) => write!(f, "BigNumber({})", val),
+            RespValue::BlobError(buf) => {
+                write!(f, "BlobError({:?})", String::from_utf8_lossy(buf))
+            }
+            RespValue::VerbatimString(fmt, buf) => {
+                write!(
+                    f,
+                    "VerbatimString({}:{:?})",
+                    fmt,
+                    String::from_utf8_lossy(buf)
+                )
+            }
+            RespValue::Map(map) => write!(f, "Map<{}>({:?})", map.len(), map),
+            RespValue::Set(set) => write!(f, "Set<{}>({:?})", set.len(), set),
+            RespValue::Push(arr) => write!(f, "Push<{}>({:?})", arr.len(), arr),
         }
     }
 }
 
+// Doubles compare by their bits, so NaN equals itself and the enum stays Eq.
+impl PartialEq for RespValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (SimpleString(a), SimpleString(b)) => a == b,
+            (SimpleError(a), SimpleError(b)) => a == b,
+            (Integer(a), Integer(b)) => a == b,
+            (BulkString(a), BulkString(b)) => a == b,
+            (Array(a), Array(b)) => a == b,
+            (Null, Null) => true,
+            (Boolean(a), Boolean(b)) => a == b,
+            (Double(a), Double(b)) => a.to_bits() == b.to_bits(),
+            (BigNumber(a), BigNumber(b)) => a == b,
+            (BlobError(a), BlobError(b)) => a == b,
+            (VerbatimString(a, x), VerbatimString(b, y)) => a == b && x == y,
+            (Map(a), Map(b)) => a == b,
+            (Set(a), Set(b)) => a == b,
+            (Push(a), Push(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for RespValue {}
+
 pub fn array(values: Vec<RespValue>) -> RespValue {
     Array(Some(values))
 }
@@ -64,6 +124,14 @@ pub fn bulk(bs: impl AsRef<[u8]>) -> RespValue {
     BulkString(Some(Box::from(bs.as_ref())))
 }
 
+pub fn map(pairs: Vec<(RespValue, RespValue)>) -> RespValue {
+    Map(pairs)
+}
+
+pub fn push(values: Vec<RespValue>) -> RespValue {
+    Push(values)
+}
+
 pub const BULK_NONE: RespValue = BulkString(None);
 
 impl From<Vec<RespValue>> for RespValue {
@@ -0,0 +1,144 @@
+mod common;
+
+use std::io;
+use std::time::Duration;
+
+use common::{pipe_conn, recv, send};
+use futures::stream::{select_all, StreamExt, TryStreamExt};
+use redis_proto_parse::client::{Client, Keepalive, Receiver, Sender};
+use redis_proto_parse::resp::value::{self, RespValue};
+
+fn cmd(args: &[&str]) -> RespValue {
+    value::array(args.iter().map(value::bulk).collect())
+}
+
+fn message(channel: &str, mesg: &str) -> RespValue {
+    value::array(vec![
+        value::bulk("message"),
+        value::bulk(channel),
+        value::bulk(mesg),
+    ])
+}
+
+#[tokio::test]
+async fn test_stream_combinators() {
+    let (rx1, server1) = Receiver::pipe(1024);
+    let (rx2, server2) = Receiver::pipe(1024);
+    let (mut conn1, mut conn2) = (pipe_conn(server1), pipe_conn(server2));
+
+    send(&mut conn1, message("a", "1")).await;
+    send(&mut conn2, message("b", "2")).await;
+    send(&mut conn1, message("a", "3")).await;
+
+    let mut payloads: Vec<_> = select_all([rx1, rx2])
+        .map_ok(|mesg| mesg.payload)
+        .take(3)
+        .try_collect()
+        .await
+        .unwrap();
+    payloads.sort();
+
+    assert_eq!(payloads, ["1", "2", "3"]);
+}
+
+#[tokio::test]
+async fn test_stream_keepalive() {
+    let (mut rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    rx.set_keepalive(Keepalive {
+        interval: Duration::from_millis(20),
+        timeout: Duration::from_millis(100),
+        payload: None,
+    });
+
+    let handle = tokio::spawn(async move {
+        assert_eq!(recv(&mut conn).await, cmd(&["PING"]));
+        send(&mut conn, value::simple("PONG")).await;
+        send(&mut conn, message("a", "after pong")).await;
+
+        // stop answering, the next PING has to time out
+        assert_eq!(recv(&mut conn).await, cmd(&["PING"]));
+        conn
+    });
+
+    let mesg = StreamExt::next(&mut rx).await.unwrap().unwrap();
+    assert_eq!(mesg.payload, "after pong");
+    assert!(rx.latency().is_some());
+
+    let err = StreamExt::next(&mut rx).await.unwrap().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_stream_ends_after_quit() {
+    let (mut rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        assert_eq!(recv(&mut conn).await, cmd(&["SUBSCRIBE", "a"]));
+        send(
+            &mut conn,
+            value::array(vec![
+                value::bulk("subscribe"),
+                value::bulk("a"),
+                value::int(1),
+            ]),
+        )
+        .await;
+
+        assert_eq!(recv(&mut conn).await, cmd(&["UNSUBSCRIBE"]));
+        // published just before the unsubscribe took effect
+        send(&mut conn, message("a", "last")).await;
+        send(
+            &mut conn,
+            value::array(vec![
+                value::bulk("unsubscribe"),
+                value::bulk("a"),
+                value::int(0),
+            ]),
+        )
+        .await;
+
+        assert_eq!(recv(&mut conn).await, cmd(&["QUIT"]));
+        send(&mut conn, value::simple("OK")).await;
+        // the server closes the connection after replying
+    });
+
+    rx.subscribe("a").await.unwrap();
+    rx.unsubscribe_all().await.unwrap();
+    rx.quit().await.unwrap();
+    handle.await.unwrap();
+
+    let rest: Vec<_> = rx.try_collect().await.unwrap();
+    assert_eq!(rest.len(), 1);
+    assert_eq!(rest[0].payload, "last");
+}
+
+#[tokio::test]
+async fn test_client_stream() {
+    let (sender, sender_server) = Sender::pipe(1024);
+    let (receiver, receiver_server) = Receiver::pipe(1024);
+    let mut client = Client::join(sender, receiver);
+
+    let mut sender_conn = pipe_conn(sender_server);
+    let mut receiver_conn = pipe_conn(receiver_server);
+
+    send(&mut receiver_conn, message("a", "hello")).await;
+
+    let handle = tokio::spawn(async move {
+        for conn in [&mut sender_conn, &mut receiver_conn] {
+            assert_eq!(recv(conn).await, cmd(&["QUIT"]));
+            send(conn, value::simple("OK")).await;
+        }
+    });
+
+    client.quit().await.unwrap();
+    handle.await.unwrap();
+
+    let mesg = StreamExt::next(&mut client).await.unwrap().unwrap();
+    assert_eq!(mesg.payload, "hello");
+    assert!(StreamExt::next(&mut client).await.is_none());
+}
@@ -0,0 +1,110 @@
+mod common;
+
+use std::io;
+use std::time::{Duration, Instant};
+
+use common::{recv, send, FakeServer};
+use redis_proto_parse::client::{BlockingClient, ConnectionInfo, ListEnd};
+use redis_proto_parse::resp::value::{self, RespValue};
+
+fn cmd(args: &[&str]) -> RespValue {
+    value::array(args.iter().map(value::bulk).collect())
+}
+
+fn local(server: &FakeServer) -> ConnectionInfo {
+    ConnectionInfo::tcp("127.0.0.1", server.addr().port())
+}
+
+#[tokio::test]
+async fn test_blpop_reuses_connection() {
+    let server = FakeServer::bind().await;
+    let client = BlockingClient::new(local(&server));
+
+    let handle = tokio::spawn(async move {
+        let mut conn = server.accept().await;
+
+        assert_eq!(recv(&mut conn).await, cmd(&["BLPOP", "a", "b", "1.5"]));
+        send(&mut conn, cmd(&["b", "job"])).await;
+
+        assert_eq!(recv(&mut conn).await, cmd(&["BRPOP", "a", "0.1"]));
+        send(&mut conn, value::ARRAY_NONE).await;
+
+        assert_eq!(
+            recv(&mut conn).await,
+            cmd(&["BLMOVE", "a", "b", "RIGHT", "LEFT", "2"])
+        );
+        send(&mut conn, RespValue::Null).await;
+
+        assert_eq!(recv(&mut conn).await, cmd(&["BZPOPMIN", "z", "1"]));
+        send(&mut conn, cmd(&["z", "m", "2.5"])).await;
+    });
+
+    let popped = client
+        .blpop(["a", "b"], Duration::from_millis(1500))
+        .await
+        .unwrap();
+    assert_eq!(popped, Some(("b".into(), "job".into())));
+
+    let popped = client.brpop("a", Duration::from_millis(100)).await.unwrap();
+    assert_eq!(popped, None);
+
+    let moved = client
+        .blmove(
+            "a",
+            "b",
+            ListEnd::Right,
+            ListEnd::Left,
+            Duration::from_secs(2),
+        )
+        .await
+        .unwrap();
+    assert_eq!(moved, None);
+
+    let popped = client.bzpopmin("z", Duration::from_secs(1)).await.unwrap();
+    assert_eq!(popped, Some(("z".into(), "m".into(), 2.5)));
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_blocking_timeout_margin() {
+    let server = FakeServer::bind().await;
+    let mut client = BlockingClient::new(local(&server));
+    client.set_timeout_margin(Duration::from_millis(100));
+
+    let handle = tokio::spawn(async move {
+        // never answers
+        let mut stuck = server.accept().await;
+        recv(&mut stuck).await;
+
+        // the timed out connection isn't reused
+        let mut conn = server.accept().await;
+        assert_eq!(recv(&mut conn).await, cmd(&["BLPOP", "a", "0.1"]));
+        send(&mut conn, value::err("WRONGTYPE wrong kind of value")).await;
+
+        // error replies don't make the connection unusable
+        assert_eq!(recv(&mut conn).await, cmd(&["BLPOP", "a", "0"]));
+        send(&mut conn, cmd(&["a", "x"])).await;
+
+        stuck
+    });
+
+    let start = Instant::now();
+    let e = client
+        .blpop("a", Duration::from_millis(100))
+        .await
+        .unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+    assert!(start.elapsed() >= Duration::from_millis(200));
+
+    let e = client
+        .blpop("a", Duration::from_millis(100))
+        .await
+        .unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::Other);
+
+    let popped = client.blpop("a", Duration::ZERO).await.unwrap();
+    assert_eq!(popped, Some(("a".into(), "x".into())));
+
+    handle.await.unwrap();
+}
@@ -0,0 +1,222 @@
+mod common;
+
+use common::{pipe_conn, recv, send};
+use redis_proto_parse::client::{
+    ClientCache, Connection, Receiver, Sender, TrackingMode, INVALIDATE_CHANNEL,
+};
+use redis_proto_parse::resp::value::{self, RespValue};
+use redis_proto_parse::resp::RespCodec;
+use tokio::io::DuplexStream;
+use tokio_util::codec::Framed;
+
+fn cmd(args: &[&str]) -> RespValue {
+    value::array(args.iter().map(value::bulk).collect())
+}
+
+fn invalidate(keys: Option<&[&str]>) -> RespValue {
+    let keys = match keys {
+        Some(keys) => cmd(keys),
+        None => value::ARRAY_NONE,
+    };
+
+    value::push(vec![value::bulk("invalidate"), keys])
+}
+
+async fn hello(conn: &mut Framed<DuplexStream, RespCodec>) {
+    assert_eq!(recv(conn).await, cmd(&["HELLO", "3"]));
+    send(
+        conn,
+        value::map(vec![(value::bulk("proto"), value::int(3))]),
+    )
+    .await;
+}
+
+/// Waits for the client to PING, then sends `frame` ahead of the PONG, so
+/// the client knows it has been handled once the PING returns.
+async fn sync(conn: &mut Framed<DuplexStream, RespCodec>, frame: Option<RespValue>) {
+    assert_eq!(recv(conn).await, cmd(&["PING", "sync"]));
+    if let Some(frame) = frame {
+        send(conn, frame).await;
+    }
+    send(conn, value::bulk("sync")).await;
+}
+
+#[tokio::test]
+async fn test_resp3_invalidation() {
+    let (client, server) = tokio::io::duplex(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        hello(&mut conn).await;
+
+        assert_eq!(recv(&mut conn).await, cmd(&["CLIENT", "TRACKING", "ON"]));
+        send(&mut conn, value::simple("OK")).await;
+
+        assert_eq!(recv(&mut conn).await, cmd(&["GET", "k"]));
+        send(&mut conn, value::bulk("v1")).await;
+
+        assert_eq!(recv(&mut conn).await, cmd(&["HGET", "h", "f"]));
+        send(&mut conn, value::BULK_NONE).await;
+
+        // the repeated reads are served locally
+        sync(&mut conn, Some(invalidate(Some(&["k"])))).await;
+
+        assert_eq!(recv(&mut conn).await, cmd(&["GET", "k"]));
+        // the key changes right after being read
+        send(&mut conn, value::bulk("v2")).await;
+        send(&mut conn, invalidate(Some(&["k"]))).await;
+        sync(&mut conn, None).await;
+
+        sync(&mut conn, Some(invalidate(None))).await;
+
+        conn
+    });
+
+    let (mut redis, _messages) = Connection::from_stream(client).await.unwrap();
+
+    let cache = ClientCache::new(10);
+    cache.track(&redis, &TrackingMode::Default).await.unwrap();
+
+    for _ in 0..2 {
+        assert_eq!(cache.get(&mut redis, "k").await.unwrap().unwrap(), "v1");
+        assert_eq!(cache.hget(&mut redis, "h", "f").await.unwrap(), None);
+    }
+    assert_eq!(cache.len(), 2);
+
+    redis.ping("sync").await.unwrap();
+    assert_eq!(cache.len(), 1);
+
+    assert_eq!(cache.get(&mut redis, "k").await.unwrap().unwrap(), "v2");
+    redis.ping("sync").await.unwrap();
+    assert_eq!(cache.len(), 1);
+
+    redis.ping("sync").await.unwrap();
+    assert!(cache.is_empty());
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_resp2_redirect() {
+    let (mut tx, tx_server) = Sender::pipe(1024);
+    let (mut rx, rx_server) = Receiver::pipe(1024);
+    let mut tx_conn = pipe_conn(tx_server);
+    let mut rx_conn = pipe_conn(rx_server);
+
+    let handle = tokio::spawn(async move {
+        assert_eq!(recv(&mut rx_conn).await, cmd(&["CLIENT", "ID"]));
+        send(&mut rx_conn, value::int(7)).await;
+
+        assert_eq!(
+            recv(&mut rx_conn).await,
+            cmd(&["SUBSCRIBE", "__redis__:invalidate"])
+        );
+        let confirm = value::array(vec![
+            value::bulk("subscribe"),
+            value::bulk("__redis__:invalidate"),
+            value::int(1),
+        ]);
+        send(&mut rx_conn, confirm).await;
+
+        assert_eq!(
+            recv(&mut tx_conn).await,
+            cmd(&[
+                "CLIENT", "TRACKING", "ON", "REDIRECT", "7", "BCAST", "PREFIX", "user:", "PREFIX",
+                "session:"
+            ])
+        );
+        send(&mut tx_conn, value::simple("OK")).await;
+
+        assert_eq!(recv(&mut tx_conn).await, cmd(&["GET", "user:1"]));
+        send(&mut tx_conn, value::bulk("alice")).await;
+
+        let mesg = value::array(vec![
+            value::bulk("message"),
+            value::bulk("__redis__:invalidate"),
+            cmd(&["user:1"]),
+        ]);
+        send(&mut rx_conn, mesg).await;
+
+        assert_eq!(recv(&mut rx_conn).await, cmd(&["PING", "sync"]));
+        send(&mut rx_conn, cmd(&["pong", "sync"])).await;
+
+        (tx_conn, rx_conn)
+    });
+
+    let cache = ClientCache::new(10);
+    let mode = TrackingMode::Broadcast(vec!["user:".into(), "session:".into()]);
+    cache.track_redirect(&mut tx, &mut rx, &mode).await.unwrap();
+
+    assert_eq!(
+        cache.get(&mut tx, "user:1").await.unwrap().unwrap(),
+        "alice"
+    );
+    assert_eq!(cache.len(), 1);
+
+    rx.ping("sync").await.unwrap();
+    assert!(cache.is_empty());
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_invalidations_without_cache() {
+    let (mut rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        assert_eq!(
+            recv(&mut conn).await,
+            cmd(&["SUBSCRIBE", "__redis__:invalidate"])
+        );
+        let confirm = value::array(vec![
+            value::bulk("subscribe"),
+            value::bulk("__redis__:invalidate"),
+            value::int(1),
+        ]);
+        send(&mut conn, confirm).await;
+
+        let mesg = value::array(vec![
+            value::bulk("message"),
+            value::bulk("__redis__:invalidate"),
+            cmd(&["user:1"]),
+        ]);
+        send(&mut conn, mesg).await;
+
+        conn
+    });
+
+    rx.subscribe(INVALIDATE_CHANNEL).await.unwrap();
+
+    // handed over like any other message, keys and all
+    let mesg = rx.next().await.unwrap();
+    assert_eq!(mesg.channel_str(), Some(INVALIDATE_CHANNEL));
+    assert_eq!(mesg.payload, "*1\r\n$6\r\nuser:1\r\n");
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_lru_eviction() {
+    let (mut tx, server) = Sender::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        for key in ["a", "b", "c", "b"] {
+            assert_eq!(recv(&mut conn).await, cmd(&["GET", key]));
+            send(&mut conn, value::bulk(key)).await;
+        }
+
+        conn
+    });
+
+    let cache = ClientCache::new(2);
+
+    // "a" is used again, so "b" is the one dropped to make room for "c"
+    for key in ["a", "b", "a", "c", "a", "b"] {
+        assert_eq!(cache.get(&mut tx, key).await.unwrap().unwrap(), key);
+    }
+    assert_eq!(cache.len(), 2);
+
+    handle.await.unwrap();
+}
@@ -0,0 +1,259 @@
+mod common;
+
+use common::{pipe_conn, recv, send};
+use futures::StreamExt;
+use redis_proto_parse::client::Connection;
+use redis_proto_parse::resp::value::{self, RespValue};
+use redis_proto_parse::resp::RespCodec;
+use tokio::io::{AsyncReadExt, DuplexStream};
+use tokio::sync::oneshot;
+use tokio_util::codec::Framed;
+
+fn cmd(args: &[&str]) -> RespValue {
+    value::array(args.iter().map(value::bulk).collect())
+}
+
+fn push(items: &[&str], count: Option<i64>) -> RespValue {
+    let mut items: Vec<_> = items.iter().map(value::bulk).collect();
+    items.extend(count.map(value::int));
+    value::push(items)
+}
+
+/// Answers the HELLO 3 sent when the connection is set up.
+async fn hello(conn: &mut Framed<DuplexStream, RespCodec>) {
+    assert_eq!(recv(conn).await, cmd(&["HELLO", "3"]));
+    send(
+        conn,
+        value::map(vec![(value::bulk("proto"), value::int(3))]),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_commands_while_subscribed() {
+    let (client, server) = tokio::io::duplex(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        hello(&mut conn).await;
+
+        assert_eq!(recv(&mut conn).await, cmd(&["SUBSCRIBE", "a", "b"]));
+        send(&mut conn, push(&["subscribe", "a"], Some(1))).await;
+        send(&mut conn, push(&["message", "a", "early"], None)).await;
+        send(&mut conn, push(&["subscribe", "b"], Some(2))).await;
+
+        assert_eq!(recv(&mut conn).await, cmd(&["GET", "key"]));
+        // a push between a command and its reply is not mistaken for it
+        send(&mut conn, push(&["message", "b", "late"], None)).await;
+        send(&mut conn, value::bulk("value")).await;
+
+        conn
+    });
+
+    let (redis, mut messages) = Connection::from_stream(client).await.unwrap();
+
+    assert_eq!(redis.subscribe(["a", "b"]).await.unwrap(), 2);
+    assert_eq!(redis.subscriptions().channels().len(), 2);
+
+    let reply = redis.command(cmd(&["GET", "key"])).await.unwrap();
+    assert_eq!(reply, value::bulk("value"));
+
+    assert_eq!(messages.next().await.unwrap().unwrap().payload, "early");
+    assert_eq!(messages.next().await.unwrap().unwrap().payload, "late");
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_replies_keep_order() {
+    let (client, server) = tokio::io::duplex(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        hello(&mut conn).await;
+
+        let mut keys = vec![];
+        for _ in 0..3 {
+            keys.push(recv(&mut conn).await);
+        }
+
+        // reply in the order the commands arrived, echoing the key
+        for key in keys {
+            let RespValue::Array(Some(items)) = key else {
+                panic!("unexpected frame {:?}", key);
+            };
+            send(&mut conn, items[1].clone()).await;
+        }
+
+        conn
+    });
+
+    let (redis, _messages) = Connection::from_stream(client).await.unwrap();
+
+    let get = |key: &'static str| {
+        let redis = redis.clone();
+        async move { redis.command(cmd(&["GET", key])).await.unwrap() }
+    };
+
+    let (a, b, c) = tokio::join!(get("a"), get("b"), get("c"));
+    assert_eq!(
+        [a, b, c],
+        [value::bulk("a"), value::bulk("b"), value::bulk("c")]
+    );
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_error_replies() {
+    let (client, server) = tokio::io::duplex(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        hello(&mut conn).await;
+
+        recv(&mut conn).await;
+        send(&mut conn, value::err("WRONGTYPE not a string")).await;
+
+        // a failed SUBSCRIBE gets a regular reply instead of a push
+        recv(&mut conn).await;
+        send(&mut conn, value::err("ERR wrong number of arguments")).await;
+    });
+
+    let (redis, mut messages) = Connection::from_stream(client).await.unwrap();
+
+    let err = redis.command(cmd(&["GET", "list"])).await.unwrap_err();
+    assert_eq!(err.to_string(), "WRONGTYPE not a string");
+
+    let err = redis.subscribe("a").await.unwrap_err();
+    assert_eq!(err.to_string(), "ERR wrong number of arguments");
+
+    handle.await.unwrap();
+
+    // the server hung up
+    assert!(messages.next().await.is_none());
+    assert!(redis.command(cmd(&["PING"])).await.is_err());
+}
+
+#[tokio::test]
+async fn test_refused_subscribe_before_command() {
+    let (client, server) = tokio::io::duplex(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        hello(&mut conn).await;
+
+        assert_eq!(recv(&mut conn).await, cmd(&["SUBSCRIBE", "secret"]));
+        assert_eq!(recv(&mut conn).await, cmd(&["GET", "a"]));
+
+        // both replies are regular frames, in the order the commands went out
+        send(
+            &mut conn,
+            value::err("NOPERM no permissions to access 'secret'"),
+        )
+        .await;
+        send(&mut conn, value::bulk("1")).await;
+
+        conn
+    });
+
+    let (redis, _messages) = Connection::from_stream(client).await.unwrap();
+
+    let (subscribed, get) =
+        tokio::join!(redis.subscribe("secret"), redis.command(cmd(&["GET", "a"])));
+    assert_eq!(
+        subscribed.unwrap_err().to_string(),
+        "NOPERM no permissions to access 'secret'"
+    );
+    assert_eq!(get.unwrap(), value::bulk("1"));
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_unsubscribe_all_while_subscribing() {
+    let (client, server) = tokio::io::duplex(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        hello(&mut conn).await;
+
+        assert_eq!(recv(&mut conn).await, cmd(&["SUBSCRIBE", "a"]));
+        send(&mut conn, push(&["subscribe", "a"], Some(1))).await;
+
+        assert_eq!(recv(&mut conn).await, cmd(&["SUBSCRIBE", "b"]));
+        assert_eq!(recv(&mut conn).await, cmd(&["UNSUBSCRIBE"]));
+
+        // the UNSUBSCRIBE also drops the channel subscribed just before it
+        send(&mut conn, push(&["subscribe", "b"], Some(2))).await;
+        send(&mut conn, push(&["unsubscribe", "a"], Some(1))).await;
+        send(&mut conn, push(&["unsubscribe", "b"], Some(0))).await;
+
+        conn
+    });
+
+    let (redis, _messages) = Connection::from_stream(client).await.unwrap();
+
+    redis.subscribe("a").await.unwrap();
+
+    let (subscribed, unsubscribed) = tokio::join!(redis.subscribe("b"), redis.unsubscribe_all());
+    assert_eq!(subscribed.unwrap(), 2);
+    assert_eq!(unsubscribed.unwrap(), 0);
+    assert!(redis.subscriptions().is_empty());
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_reads_while_writing() {
+    // too small to take the whole command at once
+    let (client, server) = tokio::io::duplex(64);
+    let mut conn = pipe_conn(server);
+    let (seen, seen_rx) = oneshot::channel();
+
+    let handle = tokio::spawn(async move {
+        hello(&mut conn).await;
+
+        // wait for the command to start coming in, keeping what was read
+        let mut buf = [0; 16];
+        let n = conn.get_mut().read(&mut buf).await.unwrap();
+        conn.read_buffer_mut().extend_from_slice(&buf[..n]);
+
+        // only read the rest once the client has taken this
+        send(&mut conn, push(&["message", "a", "hi"], None)).await;
+        seen_rx.await.unwrap();
+
+        let value = "x".repeat(1024);
+        assert_eq!(recv(&mut conn).await, cmd(&["SET", "key", &value]));
+        send(&mut conn, value::simple("OK")).await;
+
+        conn
+    });
+
+    let (redis, mut messages) = Connection::from_stream(client).await.unwrap();
+
+    let set = tokio::spawn({
+        let redis = redis.clone();
+        async move { redis.command(cmd(&["SET", "key", &"x".repeat(1024)])).await }
+    });
+
+    assert_eq!(messages.next().await.unwrap().unwrap().payload, "hi");
+    seen.send(()).unwrap();
+
+    assert_eq!(set.await.unwrap().unwrap(), value::simple("OK"));
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_requires_resp3() {
+    let (client, server) = tokio::io::duplex(1024);
+    let mut conn = pipe_conn(server);
+
+    tokio::spawn(async move {
+        recv(&mut conn).await;
+        send(&mut conn, value::err("ERR unknown command 'HELLO'")).await;
+    });
+
+    assert!(Connection::from_stream(client).await.is_err());
+}
@@ -0,0 +1,171 @@
+mod common;
+
+use common::{pipe_conn, replay};
+use redis_proto_parse::client::{MessageKind, Receiver, Sender};
+
+#[tokio::test]
+async fn test_subscribe_single_channel() {
+    let (mut rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        replay(&mut conn, "subscribe_single_channel").await;
+        conn
+    });
+
+    assert_eq!(rx.subscribe("test_channel_1").await.unwrap(), 1);
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_subscribe_multiple_channels() {
+    let (mut rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        replay(&mut conn, "subscribe_multiple_channels").await;
+        conn
+    });
+
+    let channels = ["test_channel_1", "test_channel_2", "test_channel_3"];
+    assert_eq!(rx.subscribe(channels).await.unwrap(), 3);
+
+    let subs = rx.subscriptions().channels();
+    assert_eq!(subs.len(), 3);
+    for channel in channels {
+        assert!(subs.contains(channel.as_bytes()));
+    }
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_ssubscribe_single_channel() {
+    let (mut rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        replay(&mut conn, "ssubscribe_single_channel").await;
+        conn
+    });
+
+    assert_eq!(rx.ssubscribe("test_channel_1").await.unwrap(), 1);
+    assert!(rx
+        .subscriptions()
+        .shard_channels()
+        .contains("test_channel_1".as_bytes()));
+    assert!(rx.subscriptions().channels().is_empty());
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_ssubscribe_multiple_channels() {
+    let (mut rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        replay(&mut conn, "ssubscribe_multiple_channels").await;
+        conn
+    });
+
+    let channels = ["{test}_channel_1", "{test}_channel_2", "{test}_channel_3"];
+    assert_eq!(rx.ssubscribe(channels).await.unwrap(), 3);
+    assert_eq!(rx.subscriptions().shard_channels().len(), 3);
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_sunsubscribe_single_channel() {
+    let (mut rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        replay(&mut conn, "sunsubscribe_single_channel").await;
+        conn
+    });
+
+    assert_eq!(rx.sunsubscribe("test_channel_1").await.unwrap(), 0);
+    assert!(rx.subscriptions().is_empty());
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_spublish() {
+    let (mut tx, server) = Sender::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        replay(&mut conn, "spublish").await;
+        conn
+    });
+
+    assert_eq!(
+        tx.spublish("test_channel_1", "hello world").await.unwrap(),
+        1
+    );
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_smessage() {
+    let (mut rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        replay(&mut conn, "smessage").await;
+        conn
+    });
+
+    rx.ssubscribe("test_channel_1").await.unwrap();
+
+    let mesg = rx.next().await.unwrap();
+    assert_eq!(mesg.kind, MessageKind::SMessage);
+    assert_eq!(mesg.pattern, None);
+    assert_eq!(mesg.channel, "test_channel_1");
+    assert_eq!(mesg.payload, "hello world");
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_reset() {
+    let (mut rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        replay(&mut conn, "subscribe_multiple_channels").await;
+        replay(&mut conn, "reset").await;
+        conn
+    });
+
+    let channels = ["test_channel_1", "test_channel_2", "test_channel_3"];
+    rx.subscribe(channels).await.unwrap();
+
+    rx.reset().await.unwrap();
+    assert!(rx.subscriptions().is_empty());
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_quit() {
+    let (mut rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        replay(&mut conn, "subscribe_single_channel").await;
+        replay(&mut conn, "quit").await;
+        // the server hangs up after replying
+    });
+
+    rx.subscribe("test_channel_1").await.unwrap();
+    rx.quit().await.unwrap();
+
+    handle.await.unwrap();
+    assert!(rx.next().await.is_err());
+}
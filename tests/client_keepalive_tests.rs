@@ -0,0 +1,166 @@
+mod common;
+
+use std::io;
+use std::time::Duration;
+
+use common::{recv, send, FakeServer};
+use redis_proto_parse::client::{Keepalive, Receiver};
+use redis_proto_parse::resp::value;
+
+fn keepalive(payload: Option<&str>) -> Keepalive {
+    Keepalive {
+        interval: Duration::from_millis(50),
+        timeout: Duration::from_millis(100),
+        payload: payload.map(|p| p.as_bytes().into()),
+    }
+}
+
+#[tokio::test]
+async fn test_keepalive_ping_payload() {
+    let server = FakeServer::bind().await;
+    let mut rx = Receiver::new(server.addr()).await.unwrap();
+    rx.set_keepalive(keepalive(Some("are you there")));
+
+    let mut conn = server.accept().await;
+
+    let server = tokio::spawn(async move {
+        assert_eq!(
+            recv(&mut conn).await,
+            value::array(vec![value::bulk("PING"), value::bulk("are you there")])
+        );
+
+        send(
+            &mut conn,
+            value::array(vec![value::bulk("pong"), value::bulk("are you there")]),
+        )
+        .await;
+        send(
+            &mut conn,
+            value::array(vec![
+                value::bulk("message"),
+                value::bulk("test_channel_1"),
+                value::bulk("hello"),
+            ]),
+        )
+        .await;
+
+        conn
+    });
+
+    assert_eq!(rx.latency(), None);
+
+    let mesg = rx.next().await.unwrap();
+    assert_eq!(mesg.channel, "test_channel_1");
+    assert_eq!(mesg.payload, "hello");
+    assert!(rx.latency().is_some());
+
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_keepalive_timeout() {
+    let server = FakeServer::bind().await;
+    let mut rx = Receiver::new(server.addr()).await.unwrap();
+    rx.set_keepalive(keepalive(None));
+
+    // accept, but never answer the PING
+    let mut conn = server.accept().await;
+
+    let err = rx.next().await.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+    assert_eq!(
+        recv(&mut conn).await,
+        value::array(vec![value::bulk("PING")])
+    );
+}
+
+#[tokio::test]
+async fn test_keepalive_busy_channel() {
+    let server = FakeServer::bind().await;
+    let mut rx = Receiver::new(server.addr()).await.unwrap();
+    rx.set_keepalive(keepalive(None));
+
+    let mut conn = server.accept().await;
+
+    // a constant stream of messages must not starve the PING timer
+    let server = tokio::spawn(async move {
+        let tick = value::array(vec![
+            value::bulk("message"),
+            value::bulk("busy"),
+            value::bulk("tick"),
+        ]);
+
+        loop {
+            send(&mut conn, tick.clone()).await;
+
+            let ping = tokio::time::timeout(Duration::from_millis(10), recv(&mut conn)).await;
+            if let Ok(ping) = ping {
+                assert_eq!(ping, value::array(vec![value::bulk("PING")]));
+                send(&mut conn, value::simple("PONG")).await;
+                // one more message so the client returns from next()
+                send(&mut conn, tick).await;
+                return conn;
+            }
+        }
+    });
+
+    while rx.latency().is_none() {
+        let mesg = rx.next().await.unwrap();
+        assert_eq!(mesg.channel, "busy");
+    }
+
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_keepalive_changed_while_pinging() {
+    let server = FakeServer::bind().await;
+    let mut rx = Receiver::new(server.addr()).await.unwrap();
+    rx.set_keepalive(keepalive(Some("old")));
+
+    let mut conn = server.accept().await;
+    let (pinged, mut pinged_rx) = tokio::sync::oneshot::channel();
+    let (go, go_rx) = tokio::sync::oneshot::channel();
+
+    let server = tokio::spawn(async move {
+        assert_eq!(
+            recv(&mut conn).await,
+            value::array(vec![value::bulk("PING"), value::bulk("old")])
+        );
+        pinged.send(()).unwrap();
+        go_rx.await.unwrap();
+
+        // the PONG still carries the payload the PING was sent with
+        send(&mut conn, value::bulk("old")).await;
+        send(
+            &mut conn,
+            value::array(vec![
+                value::bulk("message"),
+                value::bulk("test_channel_1"),
+                value::bulk("hello"),
+            ]),
+        )
+        .await;
+
+        conn
+    });
+
+    // drive the receiver until the PING is out
+    tokio::select! {
+        res = rx.next() => panic!("unexpected {:?}", res),
+        _ = &mut pinged_rx => {}
+    }
+
+    rx.set_keepalive(Keepalive {
+        interval: Duration::from_secs(10),
+        timeout: Duration::from_secs(10),
+        payload: Some(b"new"[..].into()),
+    });
+    go.send(()).unwrap();
+
+    let mesg = rx.next().await.unwrap();
+    assert_eq!(mesg.payload, "hello");
+
+    server.await.unwrap();
+}
@@ -0,0 +1,130 @@
+mod common;
+
+use common::{pipe_conn, recv, send};
+use redis_proto_parse::client::{
+    KeyEvent, KeyspaceChannels, KeyspaceEvent, KeyspaceNotifications, Message, MessageKind,
+    Receiver, Sender,
+};
+use redis_proto_parse::resp::value::{self, RespValue};
+
+fn cmd(args: &[&str]) -> RespValue {
+    value::array(args.iter().map(value::bulk).collect())
+}
+
+fn pmessage(pattern: &str, channel: &str, payload: &str) -> RespValue {
+    cmd(&["pmessage", pattern, channel, payload])
+}
+
+fn message(channel: &str, payload: &str) -> Message {
+    Message {
+        kind: MessageKind::PMessage,
+        pattern: None,
+        channel: channel.to_string().into(),
+        payload: payload.to_string().into(),
+    }
+}
+
+#[test]
+fn test_parse_keyspace_event() {
+    assert_eq!(
+        KeyspaceEvent::from_message(&message("__keyspace@3__:user:1", "expired")),
+        Some(KeyspaceEvent {
+            db: 3,
+            key: "user:1".into(),
+            event: KeyEvent::Expired,
+        })
+    );
+
+    // keys may contain the separator
+    assert_eq!(
+        KeyspaceEvent::from_message(&message("__keyevent@0__:rename_to", "a__:b")),
+        Some(KeyspaceEvent {
+            db: 0,
+            key: "a__:b".into(),
+            event: KeyEvent::RenameTo,
+        })
+    );
+
+    let event = KeyspaceEvent::from_message(&message("__keyevent@0__:hexpired", "h")).unwrap();
+    assert_eq!(event.event, KeyEvent::Other("hexpired".into()));
+    assert_eq!(event.event.name(), "hexpired");
+
+    for channel in [
+        "news",
+        "__keyspace@x__:k",
+        "__keyspace@0:k",
+        "__keyother@0__:k",
+    ] {
+        assert_eq!(KeyspaceEvent::from_message(&message(channel, "del")), None);
+    }
+}
+
+#[tokio::test]
+async fn test_keyspace_notifications() {
+    let (mut rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        assert_eq!(
+            recv(&mut conn).await,
+            cmd(&["PSUBSCRIBE", "__keyspace@2__:*", "__keyevent@2__:*"])
+        );
+        for (n, pattern) in ["__keyspace@2__:*", "__keyevent@2__:*"].iter().enumerate() {
+            let confirm = value::array(vec![
+                value::bulk("psubscribe"),
+                value::bulk(pattern),
+                value::int(n as i64 + 1),
+            ]);
+            send(&mut conn, confirm).await;
+        }
+
+        send(&mut conn, cmd(&["message", "news", "hi"])).await;
+        send(
+            &mut conn,
+            pmessage("__keyspace@2__:*", "__keyspace@2__:session", "set"),
+        )
+        .await;
+        send(
+            &mut conn,
+            pmessage("__keyevent@2__:*", "__keyevent@2__:set", "session"),
+        )
+        .await;
+
+        conn
+    });
+
+    let notifications = KeyspaceNotifications::new(Some(2), KeyspaceChannels::Both);
+    assert_eq!(notifications.subscribe(&mut rx).await.unwrap(), 2);
+
+    let expected = KeyspaceEvent {
+        db: 2,
+        key: "session".into(),
+        event: KeyEvent::Set,
+    };
+    assert_eq!(notifications.next(&mut rx).await.unwrap(), expected);
+    assert_eq!(notifications.next(&mut rx).await.unwrap(), expected);
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_enable_notifications() {
+    let (mut tx, server) = Sender::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        assert_eq!(
+            recv(&mut conn).await,
+            cmd(&["CONFIG", "SET", "notify-keyspace-events", "Exe"])
+        );
+        send(&mut conn, value::simple("OK")).await;
+
+        conn
+    });
+
+    let notifications = KeyspaceNotifications::new(None, KeyspaceChannels::Keyevent);
+    assert_eq!(notifications.patterns(), vec!["__keyevent@*__:*"]);
+    notifications.enable(&mut tx, "xe").await.unwrap();
+
+    handle.await.unwrap();
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use std::io;
+
+use common::{pipe_conn, recv, send};
+use redis_proto_parse::client::{MessageKind, Receiver, Sender};
+use redis_proto_parse::resp::value;
+
+// a protobuf-style payload, deliberately not valid UTF-8
+const BLOB: &[u8] = &[0x0a, 0x03, 0xff, 0xfe, 0x00, 0x80];
+
+#[tokio::test]
+async fn test_binary_message() {
+    let (mut rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    send(
+        &mut conn,
+        value::array(vec![
+            value::bulk("message"),
+            value::bulk("events"),
+            value::bulk(BLOB),
+        ]),
+    )
+    .await;
+
+    let mesg = rx.next().await.unwrap();
+    assert_eq!(mesg.kind, MessageKind::Message);
+    assert_eq!(mesg.pattern, None);
+    assert_eq!(mesg.channel, "events");
+    assert_eq!(mesg.channel_str(), Some("events"));
+    assert_eq!(&mesg.payload[..], BLOB);
+    assert_eq!(mesg.payload_str(), None);
+}
+
+#[tokio::test]
+async fn test_pmessage_keeps_pattern() {
+    let (mut rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    send(
+        &mut conn,
+        value::array(vec![
+            value::bulk("pmessage"),
+            value::bulk("events.*"),
+            value::bulk("events.orders"),
+            value::bulk("hello"),
+        ]),
+    )
+    .await;
+
+    let mesg = rx.next().await.unwrap();
+    assert_eq!(mesg.kind, MessageKind::PMessage);
+    assert_eq!(mesg.pattern_str(), Some("events.*"));
+    assert_eq!(mesg.channel_str(), Some("events.orders"));
+    assert_eq!(mesg.payload_str(), Some("hello"));
+}
+
+#[tokio::test]
+async fn test_truncated_message() {
+    let (mut rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    send(
+        &mut conn,
+        value::array(vec![value::bulk("pmessage"), value::bulk("events.*")]),
+    )
+    .await;
+
+    let err = rx.next().await.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[tokio::test]
+async fn test_publish_bytes() {
+    let (mut tx, server) = Sender::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        assert_eq!(
+            recv(&mut conn).await,
+            value::array(vec![
+                value::bulk("PUBLISH"),
+                value::bulk("events"),
+                value::bulk(BLOB)
+            ])
+        );
+        send(&mut conn, value::int(2)).await;
+    });
+
+    assert_eq!(tx.publish("events", BLOB).await.unwrap(), 2);
+    handle.await.unwrap();
+}
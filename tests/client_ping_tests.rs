@@ -0,0 +1,175 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{pipe_conn, recv, replay, send};
+use redis_proto_parse::client::{Connection, Keepalive, Receiver, Sender};
+use redis_proto_parse::resp::value::{self, RespValue};
+
+fn cmd(args: &[&str]) -> RespValue {
+    value::array(args.iter().map(value::bulk).collect())
+}
+
+fn pong(payload: &str) -> RespValue {
+    value::array(vec![value::bulk("pong"), value::bulk(payload)])
+}
+
+#[tokio::test]
+async fn test_sender_ping() {
+    let (mut tx, server) = Sender::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        replay(&mut conn, "ping_bulk").await;
+        conn
+    });
+
+    let pong = tx.ping("hello world").await.unwrap();
+    assert_eq!(pong.payload, "hello world");
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_receiver_ping_unsubscribed() {
+    let (mut rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        replay(&mut conn, "ping_bulk").await;
+        conn
+    });
+
+    let pong = rx.ping("hello world").await.unwrap();
+    assert_eq!(pong.payload, "hello world");
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_receiver_ping_wrong_payload() {
+    let (mut rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        assert_eq!(recv(&mut conn).await, cmd(&["PING", "first"]));
+        send(&mut conn, value::bulk("something else")).await;
+
+        assert_eq!(recv(&mut conn).await, cmd(&["PING", "second"]));
+        send(&mut conn, value::bulk("second")).await;
+
+        conn
+    });
+
+    // a bulk string is only a PONG when it echoes the payload
+    let err = rx.ping("first").await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    let pong = rx.ping("second").await.unwrap();
+    assert_eq!(pong.payload, "second");
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_receiver_ping_subscribed() {
+    let (mut rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        replay(&mut conn, "subscribe_single_channel").await;
+
+        assert_eq!(recv(&mut conn).await, cmd(&["PING", "are you there"]));
+        send(
+            &mut conn,
+            value::array(vec![
+                value::bulk("message"),
+                value::bulk("test_channel_1"),
+                value::bulk("hi"),
+            ]),
+        )
+        .await;
+        send(&mut conn, pong("are you there")).await;
+
+        conn
+    });
+
+    rx.subscribe("test_channel_1").await.unwrap();
+
+    let pong = rx.ping("are you there").await.unwrap();
+    assert_eq!(pong.payload, "are you there");
+
+    // the message that raced the PONG is kept
+    assert_eq!(rx.next().await.unwrap().payload, "hi");
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_receiver_ping_with_keepalive() {
+    let (mut rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        replay(&mut conn, "subscribe_single_channel").await;
+
+        // the keepalive PING goes out while the user PING is outstanding
+        assert_eq!(recv(&mut conn).await, cmd(&["PING", "user"]));
+        assert_eq!(recv(&mut conn).await, cmd(&["PING", "keepalive"]));
+        send(&mut conn, pong("user")).await;
+        send(&mut conn, pong("keepalive")).await;
+        send(
+            &mut conn,
+            value::array(vec![
+                value::bulk("message"),
+                value::bulk("test_channel_1"),
+                value::bulk("after"),
+            ]),
+        )
+        .await;
+
+        conn
+    });
+
+    rx.subscribe("test_channel_1").await.unwrap();
+
+    // restarts the timer, so the keepalive PING is due while waiting below
+    rx.set_keepalive(Keepalive {
+        interval: Duration::from_millis(20),
+        timeout: Duration::from_secs(5),
+        payload: Some("keepalive".as_bytes().into()),
+    });
+
+    let pong = rx.ping("user").await.unwrap();
+    assert_eq!(pong.payload, "user");
+    assert!(rx.latency().is_none());
+
+    // the keepalive PONG is matched to the keepalive PING
+    assert_eq!(rx.next().await.unwrap().payload, "after");
+    assert!(rx.latency().is_some());
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_connection_ping() {
+    let (client, server) = tokio::io::duplex(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        recv(&mut conn).await;
+        send(&mut conn, value::map(vec![])).await;
+
+        assert_eq!(recv(&mut conn).await, cmd(&["PING", "resp3"]));
+        send(&mut conn, value::bulk("resp3")).await;
+
+        conn
+    });
+
+    let (redis, _messages) = Connection::from_stream(client).await.unwrap();
+
+    let pong = redis.ping("resp3").await.unwrap();
+    assert_eq!(pong.payload, "resp3");
+
+    handle.await.unwrap();
+}
@@ -0,0 +1,202 @@
+mod common;
+
+use common::{pipe_conn, recv, send};
+use futures::StreamExt;
+use redis_proto_parse::client::{PubSub, Receiver};
+use redis_proto_parse::resp::value::{self, RespValue};
+
+fn cmd(args: &[&str]) -> RespValue {
+    value::array(args.iter().map(value::bulk).collect())
+}
+
+fn confirmation(ty: &str, channel: &str, count: i64) -> RespValue {
+    value::array(vec![
+        value::bulk(ty),
+        value::bulk(channel),
+        value::int(count),
+    ])
+}
+
+fn message(channel: &str, mesg: &str) -> RespValue {
+    value::array(vec![
+        value::bulk("message"),
+        value::bulk(channel),
+        value::bulk(mesg),
+    ])
+}
+
+fn pmessage(pattern: &str, channel: &str, mesg: &str) -> RespValue {
+    value::array(vec![
+        value::bulk("pmessage"),
+        value::bulk(pattern),
+        value::bulk(channel),
+        value::bulk(mesg),
+    ])
+}
+
+#[tokio::test]
+async fn test_fan_out_to_handles() {
+    let (rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+    let pubsub = PubSub::new(rx);
+
+    let handle = tokio::spawn(async move {
+        // the second handle for "a" does not subscribe again
+        assert_eq!(recv(&mut conn).await, cmd(&["SUBSCRIBE", "a"]));
+        send(&mut conn, confirmation("subscribe", "a", 1)).await;
+
+        assert_eq!(recv(&mut conn).await, cmd(&["PSUBSCRIBE", "b.*"]));
+        send(&mut conn, confirmation("psubscribe", "b.*", 2)).await;
+
+        send(&mut conn, message("a", "one")).await;
+        send(&mut conn, pmessage("b.*", "b.x", "two")).await;
+        // nobody listens here, it is dropped
+        send(&mut conn, message("c", "lost")).await;
+        send(&mut conn, message("a", "three")).await;
+
+        conn
+    });
+
+    let mut first = pubsub.subscribe("a").await.unwrap();
+    let mut second = pubsub.subscribe("a").await.unwrap();
+    let mut pattern = pubsub.psubscribe("b.*").await.unwrap();
+    assert_eq!(pattern.channel(), "b.*");
+
+    for sub in [&mut first, &mut second] {
+        assert_eq!(sub.next().await.unwrap().unwrap().payload, "one");
+        assert_eq!(sub.next().await.unwrap().unwrap().payload, "three");
+    }
+
+    let mesg = pattern.next().await.unwrap().unwrap();
+    assert_eq!(mesg.channel, "b.x");
+    assert_eq!(mesg.payload, "two");
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_unsubscribe_after_last_handle_dropped() {
+    let (rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+    let pubsub = PubSub::new(rx);
+
+    let handle = tokio::spawn(async move {
+        assert_eq!(recv(&mut conn).await, cmd(&["SUBSCRIBE", "a"]));
+        send(&mut conn, confirmation("subscribe", "a", 1)).await;
+
+        assert_eq!(recv(&mut conn).await, cmd(&["UNSUBSCRIBE", "a"]));
+        send(&mut conn, confirmation("unsubscribe", "a", 0)).await;
+
+        // subscribing again goes back to the server
+        assert_eq!(recv(&mut conn).await, cmd(&["SUBSCRIBE", "a"]));
+        send(&mut conn, confirmation("subscribe", "a", 1)).await;
+        send(&mut conn, message("a", "back")).await;
+
+        conn
+    });
+
+    let first = pubsub.subscribe("a").await.unwrap();
+    let second = pubsub.subscribe("a").await.unwrap();
+
+    drop(first);
+    drop(second);
+
+    let mut again = pubsub.subscribe("a").await.unwrap();
+    assert_eq!(again.next().await.unwrap().unwrap().payload, "back");
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_connection_loss_ends_handles() {
+    let (rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+    let pubsub = PubSub::new(rx);
+
+    let handle = tokio::spawn(async move {
+        assert_eq!(recv(&mut conn).await, cmd(&["SUBSCRIBE", "a"]));
+        send(&mut conn, confirmation("subscribe", "a", 1)).await;
+        send(&mut conn, message("a", "last")).await;
+    });
+
+    let mut sub = pubsub.subscribe("a").await.unwrap();
+    handle.await.unwrap();
+
+    assert_eq!(sub.next().await.unwrap().unwrap().payload, "last");
+    assert!(sub.next().await.unwrap().is_err());
+    assert!(sub.next().await.is_none());
+
+    // the manager is gone as well
+    assert!(pubsub.subscribe("b").await.is_err());
+}
+
+#[tokio::test]
+async fn test_refused_subscribe_keeps_running() {
+    let (rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+    let pubsub = PubSub::new(rx);
+
+    let handle = tokio::spawn(async move {
+        assert_eq!(recv(&mut conn).await, cmd(&["SUBSCRIBE", "a"]));
+        send(&mut conn, confirmation("subscribe", "a", 1)).await;
+
+        assert_eq!(recv(&mut conn).await, cmd(&["SUBSCRIBE", "secret"]));
+        send(
+            &mut conn,
+            value::err("NOPERM no permissions to access 'secret'"),
+        )
+        .await;
+
+        send(&mut conn, message("a", "still here")).await;
+
+        conn
+    });
+
+    let mut sub = pubsub.subscribe("a").await.unwrap();
+
+    let err = pubsub.subscribe("secret").await.err().unwrap();
+    assert_eq!(err.to_string(), "NOPERM no permissions to access 'secret'");
+
+    // the other handles keep receiving
+    assert_eq!(sub.next().await.unwrap().unwrap().payload, "still here");
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_channel_matching_several_patterns() {
+    let (rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+    let pubsub = PubSub::new(rx);
+
+    let handle = tokio::spawn(async move {
+        assert_eq!(recv(&mut conn).await, cmd(&["PSUBSCRIBE", "news.*"]));
+        send(&mut conn, confirmation("psubscribe", "news.*", 1)).await;
+
+        assert_eq!(recv(&mut conn).await, cmd(&["PSUBSCRIBE", "*.sports"]));
+        send(&mut conn, confirmation("psubscribe", "*.sports", 2)).await;
+
+        // one publish, delivered once per matching pattern
+        send(&mut conn, pmessage("news.*", "news.sports", "goal")).await;
+        send(&mut conn, pmessage("*.sports", "news.sports", "goal")).await;
+        send(&mut conn, pmessage("news.*", "news.weather", "rain")).await;
+        send(&mut conn, pmessage("*.sports", "live.sports", "match")).await;
+
+        conn
+    });
+
+    let mut news = pubsub.psubscribe("news.*").await.unwrap();
+    let mut sports = pubsub.psubscribe("*.sports").await.unwrap();
+
+    let mesg = news.next().await.unwrap().unwrap();
+    assert_eq!(mesg.pattern.unwrap(), "news.*");
+    assert_eq!(mesg.payload, "goal");
+    assert_eq!(news.next().await.unwrap().unwrap().payload, "rain");
+
+    let mesg = sports.next().await.unwrap().unwrap();
+    assert_eq!(mesg.pattern.unwrap(), "*.sports");
+    assert_eq!(mesg.payload, "goal");
+    assert_eq!(sports.next().await.unwrap().unwrap().payload, "match");
+
+    handle.await.unwrap();
+}
@@ -0,0 +1,327 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{pipe_conn, recv, send};
+use redis_proto_parse::client::{PendingRange, Sender, StreamEntry, StreamReadOptions, StreamTrim};
+use redis_proto_parse::resp::value::{self, RespValue};
+
+fn cmd(args: &[&str]) -> RespValue {
+    value::array(args.iter().map(value::bulk).collect())
+}
+
+fn entry(id: &str, fields: &[&str]) -> RespValue {
+    value::array(vec![value::bulk(id), cmd(fields)])
+}
+
+#[tokio::test]
+async fn test_xadd_trim() {
+    let (mut tx, server) = Sender::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        assert_eq!(
+            recv(&mut conn).await,
+            cmd(&["XADD", "events", "MAXLEN", "~", "1000", "*", "kind", "click", "x", "10"])
+        );
+        send(&mut conn, value::bulk("1700000000000-0")).await;
+
+        assert_eq!(
+            recv(&mut conn).await,
+            cmd(&["XTRIM", "events", "MINID", "=", "1700000000000-0"])
+        );
+        send(&mut conn, value::int(3)).await;
+
+        conn
+    });
+
+    let id = tx
+        .xadd_trim(
+            "events",
+            Some(&StreamTrim::ApproxMaxLen(1000)),
+            "*",
+            [("kind", "click".to_string()), ("x", 10.to_string())],
+        )
+        .await
+        .unwrap();
+    assert_eq!(id, "1700000000000-0");
+
+    let trim = StreamTrim::MinId(id);
+    assert_eq!(tx.xtrim("events", &trim).await.unwrap(), 3);
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_xrange_entries() {
+    let (mut tx, server) = Sender::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        assert_eq!(
+            recv(&mut conn).await,
+            cmd(&["XREVRANGE", "events", "+", "-", "COUNT", "2"])
+        );
+        send(
+            &mut conn,
+            value::array(vec![
+                entry("2-0", &["kind", "view"]),
+                entry("1-0", &["kind", "click", "x", "10"]),
+            ]),
+        )
+        .await;
+
+        conn
+    });
+
+    let entries = tx.xrevrange("events", "+", "-", Some(2)).await.unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].id, "2-0");
+    assert_eq!(entries[1].get("x").unwrap(), "10");
+    assert_eq!(entries[1].get("y"), None);
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_xreadgroup() {
+    let (mut tx, server) = Sender::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        assert_eq!(
+            recv(&mut conn).await,
+            cmd(&[
+                "XREADGROUP",
+                "GROUP",
+                "workers",
+                "w1",
+                "COUNT",
+                "10",
+                "BLOCK",
+                "500",
+                "STREAMS",
+                "a",
+                "b",
+                ">",
+                ">"
+            ])
+        );
+        send(
+            &mut conn,
+            value::map(vec![
+                (
+                    value::bulk("a"),
+                    value::array(vec![entry("1-0", &["n", "1"])]),
+                ),
+                // deleted while pending
+                (
+                    value::bulk("b"),
+                    value::array(vec![value::array(vec![
+                        value::bulk("2-0"),
+                        value::ARRAY_NONE,
+                    ])]),
+                ),
+            ]),
+        )
+        .await;
+
+        assert_eq!(
+            recv(&mut conn).await,
+            cmd(&["XREAD", "BLOCK", "0", "STREAMS", "a", "$"])
+        );
+        send(&mut conn, value::ARRAY_NONE).await;
+
+        conn
+    });
+
+    let opts = StreamReadOptions::default()
+        .count(10)
+        .block(Duration::from_millis(500));
+    let reads = tx
+        .xreadgroup("workers", "w1", &[("a", ">"), ("b", ">")], &opts)
+        .await
+        .unwrap();
+
+    assert_eq!(reads.len(), 2);
+    assert_eq!(reads[0].key, "a");
+    assert_eq!(
+        reads[0].entries,
+        vec![StreamEntry {
+            id: "1-0".into(),
+            fields: vec![("n".into(), "1".into())],
+        }]
+    );
+    assert!(reads[1].entries[0].fields.is_empty());
+
+    let opts = StreamReadOptions::default().block(Duration::ZERO);
+    assert!(tx.xread(&[("a", "$")], &opts).await.unwrap().is_empty());
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_xpending_and_claim() {
+    let (mut tx, server) = Sender::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        assert_eq!(
+            recv(&mut conn).await,
+            cmd(&["XPENDING", "events", "workers"])
+        );
+        send(
+            &mut conn,
+            value::array(vec![
+                value::int(2),
+                value::bulk("1-0"),
+                value::bulk("2-0"),
+                value::array(vec![cmd(&["w1", "2"])]),
+            ]),
+        )
+        .await;
+
+        assert_eq!(
+            recv(&mut conn).await,
+            cmd(&["XPENDING", "events", "workers", "IDLE", "60000", "-", "+", "10", "w1"])
+        );
+        send(
+            &mut conn,
+            value::array(vec![value::array(vec![
+                value::bulk("1-0"),
+                value::bulk("w1"),
+                value::int(61000),
+                value::int(3),
+            ])]),
+        )
+        .await;
+
+        assert_eq!(
+            recv(&mut conn).await,
+            cmd(&["XCLAIM", "events", "workers", "w2", "60000", "1-0", "2-0"])
+        );
+        send(
+            &mut conn,
+            value::array(vec![entry("1-0", &["n", "1"]), value::ARRAY_NONE]),
+        )
+        .await;
+
+        assert_eq!(
+            recv(&mut conn).await,
+            cmd(&[
+                "XAUTOCLAIM",
+                "events",
+                "workers",
+                "w2",
+                "60000",
+                "0-0",
+                "COUNT",
+                "5"
+            ])
+        );
+        send(
+            &mut conn,
+            value::array(vec![
+                value::bulk("0-0"),
+                value::array(vec![entry("1-0", &["n", "1"])]),
+                cmd(&["2-0"]),
+            ]),
+        )
+        .await;
+
+        assert_eq!(
+            recv(&mut conn).await,
+            cmd(&["XACK", "events", "workers", "1-0"])
+        );
+        send(&mut conn, value::int(1)).await;
+
+        conn
+    });
+
+    let summary = tx.xpending("events", "workers").await.unwrap();
+    assert_eq!(summary.count, 2);
+    assert_eq!(summary.min_id.as_deref(), Some("1-0"));
+    assert_eq!(summary.consumers, vec![("w1".to_string(), 2)]);
+
+    let idle = Duration::from_secs(60);
+    let range = PendingRange::new(10).min_idle(idle).consumer("w1");
+    let pending = tx
+        .xpending_range("events", "workers", &range)
+        .await
+        .unwrap();
+    assert_eq!(pending[0].idle, Duration::from_millis(61000));
+    assert_eq!(pending[0].deliveries, 3);
+
+    let claimed = tx
+        .xclaim("events", "workers", "w2", idle, &["1-0", "2-0"])
+        .await
+        .unwrap();
+    assert_eq!(claimed.len(), 1);
+
+    let auto = tx
+        .xautoclaim("events", "workers", "w2", idle, "0-0", 5)
+        .await
+        .unwrap();
+    assert_eq!(auto.next_id, "0-0");
+    assert_eq!(auto.entries[0].id, "1-0");
+    assert_eq!(auto.deleted, vec!["2-0".to_string()]);
+
+    assert_eq!(tx.xack("events", "workers", &["1-0"]).await.unwrap(), 1);
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_xgroup() {
+    let (mut tx, server) = Sender::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        assert_eq!(
+            recv(&mut conn).await,
+            cmd(&["XGROUP", "CREATE", "events", "workers", "$", "MKSTREAM"])
+        );
+        send(&mut conn, value::simple("OK")).await;
+
+        recv(&mut conn).await;
+        send(
+            &mut conn,
+            value::err("BUSYGROUP Consumer Group name already exists"),
+        )
+        .await;
+
+        assert_eq!(
+            recv(&mut conn).await,
+            cmd(&["XGROUP", "DELCONSUMER", "events", "workers", "w1"])
+        );
+        send(&mut conn, value::int(4)).await;
+
+        assert_eq!(
+            recv(&mut conn).await,
+            cmd(&["XGROUP", "DESTROY", "events", "workers"])
+        );
+        send(&mut conn, value::int(1)).await;
+
+        conn
+    });
+
+    tx.xgroup_create("events", "workers", "$", true)
+        .await
+        .unwrap();
+
+    let e = tx
+        .xgroup_create("events", "workers", "$", true)
+        .await
+        .unwrap_err();
+    assert!(e.to_string().starts_with("BUSYGROUP"));
+
+    assert_eq!(
+        tx.xgroup_delconsumer("events", "workers", "w1")
+            .await
+            .unwrap(),
+        4
+    );
+    assert!(tx.xgroup_destroy("events", "workers").await.unwrap());
+
+    handle.await.unwrap();
+}
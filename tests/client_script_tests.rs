@@ -0,0 +1,127 @@
+mod common;
+
+use common::{pipe_conn, recv, send};
+use redis_proto_parse::client::{Script, ScriptArgs, Sender};
+use redis_proto_parse::resp::value::{self, RespValue};
+
+const GET: &str = "return redis.call('GET', KEYS[1])";
+const GET_SHA: &str = "d3c21d0c2b9ca22f82737626a27bcaf5d288f99f";
+
+fn cmd(args: &[&str]) -> RespValue {
+    value::array(args.iter().map(value::bulk).collect())
+}
+
+#[test]
+fn test_script_hash() {
+    assert_eq!(Script::new(GET).hash(), GET_SHA);
+}
+
+#[tokio::test]
+async fn test_evalsha() {
+    let (mut tx, server) = Sender::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        assert_eq!(
+            recv(&mut conn).await,
+            cmd(&["EVALSHA", GET_SHA, "1", "user:1", "10", "x"])
+        );
+        send(&mut conn, value::bulk("alice")).await;
+
+        conn
+    });
+
+    let args = ScriptArgs::new().key("user:1").arg(10).arg("x");
+    let name: String = Script::new(GET).invoke(&mut tx, &args).await.unwrap();
+    assert_eq!(name, "alice");
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_noscript_loads_and_retries() {
+    let (mut tx, server) = Sender::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        let evalsha = cmd(&["EVALSHA", GET_SHA, "1", "user:2"]);
+
+        assert_eq!(recv(&mut conn).await, evalsha);
+        send(
+            &mut conn,
+            value::err("NOSCRIPT No matching script. Please use EVAL."),
+        )
+        .await;
+
+        assert_eq!(recv(&mut conn).await, cmd(&["SCRIPT", "LOAD", GET]));
+        send(&mut conn, value::bulk(GET_SHA)).await;
+
+        assert_eq!(recv(&mut conn).await, evalsha);
+        send(&mut conn, value::BULK_NONE).await;
+
+        conn
+    });
+
+    let args = ScriptArgs::new().key("user:2");
+    let name: Option<String> = Script::new(GET).invoke(&mut tx, &args).await.unwrap();
+    assert_eq!(name, None);
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_script_error() {
+    let (mut tx, server) = Sender::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        recv(&mut conn).await;
+        send(&mut conn, value::err("ERR user_script:1: oops")).await;
+
+        conn
+    });
+
+    let err = Script::new("error('oops')")
+        .invoke::<RespValue>(&mut tx, &ScriptArgs::new())
+        .await
+        .unwrap_err();
+    assert!(err.to_string().starts_with("ERR user_script"));
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_fcall() {
+    let (mut tx, server) = Sender::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        assert_eq!(
+            recv(&mut conn).await,
+            cmd(&["FCALL", "incr_by", "1", "counter", "5"])
+        );
+        send(&mut conn, value::int(15)).await;
+
+        assert_eq!(
+            recv(&mut conn).await,
+            cmd(&["FCALL_RO", "members", "1", "set"])
+        );
+        send(
+            &mut conn,
+            value::array(vec![value::bulk("a"), value::bulk("b")]),
+        )
+        .await;
+
+        conn
+    });
+
+    let args = ScriptArgs::new().key("counter").arg(5);
+    let n: i64 = tx.fcall("incr_by", &args).await.unwrap();
+    assert_eq!(n, 15);
+
+    let args = ScriptArgs::new().key("set");
+    let members: Vec<String> = tx.fcall_ro("members", &args).await.unwrap();
+    assert_eq!(members, ["a", "b"]);
+
+    handle.await.unwrap();
+}
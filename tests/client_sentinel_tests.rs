@@ -0,0 +1,193 @@
+mod common;
+
+use std::io;
+
+use common::{recv, send, FakeServer, ServerConn};
+use redis_proto_parse::client::{ConnectionInfo, Sentinel};
+use redis_proto_parse::resp::value::{self, RespValue};
+
+fn cmd(args: &[&str]) -> RespValue {
+    value::array(args.iter().map(value::bulk).collect())
+}
+
+fn local(server: &FakeServer) -> ConnectionInfo {
+    ConnectionInfo::tcp("127.0.0.1", server.addr().port())
+}
+
+fn sentinel(sentinels: &[&FakeServer]) -> Sentinel {
+    let sentinels = sentinels.iter().map(|s| local(s)).collect();
+    Sentinel::new("mymaster", sentinels, ConnectionInfo::tcp("localhost", 0))
+}
+
+/// Answers a `SENTINEL get-master-addr-by-name` with `server`'s address.
+async fn report(conn: &mut ServerConn, server: &FakeServer) {
+    assert_eq!(
+        recv(conn).await,
+        cmd(&["SENTINEL", "get-master-addr-by-name", "mymaster"])
+    );
+    let port = server.addr().port().to_string();
+    send(conn, cmd(&["127.0.0.1", &port])).await;
+}
+
+async fn role(conn: &mut ServerConn, role: &str) {
+    assert_eq!(recv(conn).await, cmd(&["ROLE"]));
+    let reply = match role {
+        "master" => value::array(vec![value::bulk("master"), value::int(0), cmd(&[])]),
+        _ => value::array(vec![
+            value::bulk(role),
+            value::bulk("127.0.0.1"),
+            value::int(6379),
+            value::bulk("connected"),
+            value::int(0),
+        ]),
+    };
+    send(conn, reply).await;
+}
+
+/// Accepts the connection watching for failovers.
+async fn watched(server: &FakeServer) -> ServerConn {
+    let mut conn = server.accept().await;
+    assert_eq!(recv(&mut conn).await, cmd(&["SUBSCRIBE", "+switch-master"]));
+    let confirm = value::array(vec![
+        value::bulk("subscribe"),
+        value::bulk("+switch-master"),
+        value::int(1),
+    ]);
+    send(&mut conn, confirm).await;
+    conn
+}
+
+#[tokio::test]
+async fn test_discovers_primary() {
+    let (first, second) = (FakeServer::bind().await, FakeServer::bind().await);
+    let (replica, primary) = (FakeServer::bind().await, FakeServer::bind().await);
+    let sentinel = sentinel(&[&first, &second]);
+
+    let handle = tokio::spawn(async move {
+        // neither sentinel knows the service at first
+        for sentinel in [&first, &second] {
+            let mut conn = sentinel.accept().await;
+            assert_eq!(
+                recv(&mut conn).await,
+                cmd(&["SENTINEL", "get-master-addr-by-name", "mymaster"])
+            );
+            send(&mut conn, value::ARRAY_NONE).await;
+        }
+
+        // then it reports a stale address, which ROLE disproves
+        let mut conn = first.accept().await;
+        report(&mut conn, &replica).await;
+        role(&mut replica.accept().await, "slave").await;
+
+        let mut conn = second.accept().await;
+        report(&mut conn, &primary).await;
+
+        let mut conn = primary.accept().await;
+        role(&mut conn, "master").await;
+        assert_eq!(recv(&mut conn).await, cmd(&["GET", "k"]));
+        send(&mut conn, value::bulk("v")).await;
+
+        conn
+    });
+
+    let e = sentinel.primary_addr().await.unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::NotFound);
+
+    let mut sender = sentinel.connect_sender().await.unwrap();
+    assert_eq!(
+        sender.command(cmd(&["GET", "k"])).await.unwrap(),
+        value::bulk("v")
+    );
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_sender_follows_failover() {
+    let server = FakeServer::bind().await;
+    let (old, new) = (FakeServer::bind().await, FakeServer::bind().await);
+    let sentinel = sentinel(&[&server]);
+    let new_port = new.addr().port();
+
+    let handle = tokio::spawn(async move {
+        let mut watch = watched(&server).await;
+
+        report(&mut server.accept().await, &old).await;
+        let mut conn = old.accept().await;
+        role(&mut conn, "master").await;
+        assert_eq!(recv(&mut conn).await, cmd(&["GET", "k"]));
+        send(&mut conn, value::bulk("old")).await;
+
+        let old_port = old.addr().port().to_string();
+        let new_port = new.addr().port().to_string();
+        for service in ["other", "mymaster"] {
+            let payload = [service, "127.0.0.1", &old_port, "127.0.0.1", &new_port].join(" ");
+            send(&mut watch, cmd(&["message", "+switch-master", &payload])).await;
+        }
+
+        report(&mut server.accept().await, &new).await;
+        let mut conn = new.accept().await;
+        role(&mut conn, "master").await;
+        assert_eq!(recv(&mut conn).await, cmd(&["GET", "k"]));
+        send(&mut conn, value::bulk("new")).await;
+
+        (watch, conn)
+    });
+
+    let mut sender = sentinel.sender().await.unwrap();
+    let mut failovers = sender.failovers();
+
+    let reply = sender.command(cmd(&["GET", "k"])).await.unwrap();
+    assert_eq!(reply, value::bulk("old"));
+
+    failovers.changed().await.unwrap();
+    assert_eq!(failovers.primary(), Some(("127.0.0.1".into(), new_port)));
+
+    let reply = sender.command(cmd(&["GET", "k"])).await.unwrap();
+    assert_eq!(reply, value::bulk("new"));
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_receiver_resubscribes() {
+    let server = FakeServer::bind().await;
+    let (old, new) = (FakeServer::bind().await, FakeServer::bind().await);
+    let sentinel = sentinel(&[&server]);
+
+    let handle = tokio::spawn(async move {
+        let watch = watched(&server).await;
+        let confirm = value::array(vec![
+            value::bulk("subscribe"),
+            value::bulk("news"),
+            value::int(1),
+        ]);
+
+        report(&mut server.accept().await, &old).await;
+        role(&mut old.accept().await, "master").await;
+        let mut conn = old.accept().await;
+        assert_eq!(recv(&mut conn).await, cmd(&["SUBSCRIBE", "news"]));
+        send(&mut conn, confirm.clone()).await;
+        send(&mut conn, cmd(&["message", "news", "a"])).await;
+
+        // the primary goes away, and the sentinel points elsewhere
+        drop(conn);
+
+        report(&mut server.accept().await, &new).await;
+        role(&mut new.accept().await, "master").await;
+        let mut conn = new.accept().await;
+        assert_eq!(recv(&mut conn).await, cmd(&["SUBSCRIBE", "news"]));
+        send(&mut conn, confirm).await;
+        send(&mut conn, cmd(&["message", "news", "b"])).await;
+
+        (watch, conn)
+    });
+
+    let mut receiver = sentinel.receiver().await.unwrap();
+    receiver.get().subscribe("news").await.unwrap();
+
+    assert_eq!(receiver.next().await.unwrap().payload, "a");
+    assert_eq!(receiver.next().await.unwrap().payload, "b");
+
+    handle.await.unwrap();
+}
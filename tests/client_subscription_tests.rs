@@ -0,0 +1,198 @@
+mod common;
+
+use common::{pipe_conn, recv, send};
+use redis_proto_parse::client::{Channels, Receiver};
+use redis_proto_parse::resp::value::{self, RespValue};
+
+fn cmd(args: &[&str]) -> RespValue {
+    value::array(args.iter().map(value::bulk).collect())
+}
+
+fn confirmation(ty: &str, channel: Option<&str>, count: i64) -> RespValue {
+    value::array(vec![
+        value::bulk(ty),
+        channel.map(value::bulk).unwrap_or(value::BULK_NONE),
+        value::int(count),
+    ])
+}
+
+fn message(channel: &str, mesg: &str) -> RespValue {
+    value::array(vec![
+        value::bulk("message"),
+        value::bulk(channel),
+        value::bulk(mesg),
+    ])
+}
+
+#[tokio::test]
+async fn test_subscribe_waits_for_confirmation() {
+    let (mut rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        assert_eq!(recv(&mut conn).await, cmd(&["SUBSCRIBE", "a"]));
+        send(&mut conn, confirmation("subscribe", Some("a"), 1)).await;
+
+        assert_eq!(recv(&mut conn).await, cmd(&["SUBSCRIBE", "b"]));
+        // a message on the first channel races the second confirmation
+        send(&mut conn, message("a", "early")).await;
+        send(&mut conn, confirmation("subscribe", Some("b"), 2)).await;
+        send(&mut conn, message("b", "late")).await;
+
+        conn
+    });
+
+    assert_eq!(rx.subscribe("a").await.unwrap(), 1);
+    assert!(rx.subscriptions().channels().contains("a".as_bytes()));
+
+    assert_eq!(rx.subscribe("b").await.unwrap(), 2);
+    assert_eq!(rx.subscriptions().len(), 2);
+
+    // the buffered message is still delivered, in order
+    assert_eq!(rx.next().await.unwrap().payload, "early");
+    assert_eq!(rx.next().await.unwrap().payload, "late");
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_unsubscribe_all() {
+    let (mut rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        for (i, channel) in ["a", "b"].into_iter().enumerate() {
+            assert_eq!(recv(&mut conn).await, cmd(&["SUBSCRIBE", channel]));
+            send(
+                &mut conn,
+                confirmation("subscribe", Some(channel), i as i64 + 1),
+            )
+            .await;
+        }
+
+        assert_eq!(recv(&mut conn).await, cmd(&["PSUBSCRIBE", "c.*"]));
+        send(&mut conn, confirmation("psubscribe", Some("c.*"), 3)).await;
+
+        // one reply per channel, patterns are left alone
+        assert_eq!(recv(&mut conn).await, cmd(&["UNSUBSCRIBE"]));
+        send(&mut conn, confirmation("unsubscribe", Some("b"), 2)).await;
+        send(&mut conn, confirmation("unsubscribe", Some("a"), 1)).await;
+
+        // with nothing subscribed the server replies once with a null channel
+        assert_eq!(recv(&mut conn).await, cmd(&["UNSUBSCRIBE"]));
+        send(&mut conn, confirmation("unsubscribe", None, 1)).await;
+
+        conn
+    });
+
+    rx.subscribe("a").await.unwrap();
+    rx.subscribe("b").await.unwrap();
+    rx.psubscribe("c.*").await.unwrap();
+
+    assert_eq!(rx.unsubscribe_all().await.unwrap(), 1);
+    assert!(rx.subscriptions().channels().is_empty());
+    assert!(rx.subscriptions().patterns().contains("c.*".as_bytes()));
+
+    assert_eq!(rx.unsubscribe_all().await.unwrap(), 1);
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_punsubscribe() {
+    let (mut rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        assert_eq!(recv(&mut conn).await, cmd(&["PSUBSCRIBE", "news.*"]));
+        send(&mut conn, confirmation("psubscribe", Some("news.*"), 1)).await;
+
+        assert_eq!(recv(&mut conn).await, cmd(&["PUNSUBSCRIBE", "news.*"]));
+        send(&mut conn, confirmation("punsubscribe", Some("news.*"), 0)).await;
+
+        conn
+    });
+
+    assert_eq!(rx.psubscribe("news.*").await.unwrap(), 1);
+    assert_eq!(rx.subscriptions().patterns().len(), 1);
+
+    assert_eq!(rx.punsubscribe("news.*").await.unwrap(), 0);
+    assert!(rx.subscriptions().is_empty());
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_subscribe_many_out_of_order() {
+    let (mut rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        assert_eq!(recv(&mut conn).await, cmd(&["PSUBSCRIBE", "a.*", "b.*"]));
+        // confirmations can interleave with messages from earlier patterns
+        send(&mut conn, confirmation("psubscribe", Some("b.*"), 1)).await;
+        send(&mut conn, message("b.1", "first")).await;
+        send(&mut conn, confirmation("psubscribe", Some("a.*"), 2)).await;
+
+        conn
+    });
+
+    assert_eq!(rx.psubscribe(vec!["a.*", "b.*"]).await.unwrap(), 2);
+    assert_eq!(rx.subscriptions().patterns().len(), 2);
+    assert_eq!(rx.next().await.unwrap().payload, "first");
+
+    let subscriptions = rx.subscriptions();
+    let matched: Vec<_> = subscriptions.matching_patterns(b"a.1").collect();
+    assert_eq!(matched, ["a.*"]);
+    assert!(subscriptions.receives(b"b.2"));
+    assert!(!subscriptions.receives(b"c.1"));
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_subscribe_nothing() {
+    let (mut rx, _server) = Receiver::pipe(1024);
+
+    let err = rx.subscribe(Vec::<&str>::new()).await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[tokio::test]
+async fn test_subscribe_from_iter() {
+    let (mut rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        assert_eq!(
+            recv(&mut conn).await,
+            cmd(&["SUBSCRIBE", "room:1", "room:2"])
+        );
+        send(&mut conn, confirmation("subscribe", Some("room:1"), 1)).await;
+        send(&mut conn, confirmation("subscribe", Some("room:2"), 2)).await;
+        conn
+    });
+
+    let rooms: Channels = (1..=2).map(|n| format!("room:{}", n)).collect();
+    assert_eq!(rx.subscribe(rooms).await.unwrap(), 2);
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_subscribe_error_reply() {
+    let (mut rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        assert_eq!(recv(&mut conn).await, cmd(&["SUBSCRIBE", "a"]));
+        send(&mut conn, value::err("NOPERM no permissions to access 'a'")).await;
+        conn
+    });
+
+    let err = rx.subscribe("a").await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::Other);
+    assert_eq!(err.to_string(), "NOPERM no permissions to access 'a'");
+
+    handle.await.unwrap();
+}
@@ -0,0 +1,209 @@
+#![cfg(feature = "tls")]
+
+mod common;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use common::{recv, send};
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, ExtendedKeyUsagePurpose, IsCa, KeyPair,
+};
+use redis_proto_parse::client::{ConnectionAddr, ConnectionInfo, Sender, TlsConfig};
+use redis_proto_parse::resp::{value, RespCodec};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tokio_rustls::rustls::crypto::ring;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{RootCertStore, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+use tokio_util::codec::Framed;
+
+struct Pki {
+    ca: Certificate,
+    ca_key: KeyPair,
+}
+
+struct Issued {
+    cert: Certificate,
+    key: KeyPair,
+}
+
+impl Pki {
+    fn new() -> Self {
+        let ca_key = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = params.self_signed(&ca_key).unwrap();
+
+        Self { ca, ca_key }
+    }
+
+    fn issue(&self, name: &str, usage: ExtendedKeyUsagePurpose) -> Issued {
+        let key = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::new(vec![name.to_string()]).unwrap();
+        params.extended_key_usages = vec![usage];
+        let cert = params.signed_by(&key, &self.ca, &self.ca_key).unwrap();
+
+        Issued { cert, key }
+    }
+
+    fn roots(&self) -> RootCertStore {
+        let mut roots = RootCertStore::empty();
+        roots.add(self.ca.der().clone()).unwrap();
+        roots
+    }
+}
+
+impl Issued {
+    fn pem(&self) -> (Vec<u8>, Vec<u8>) {
+        (
+            self.cert.pem().into_bytes(),
+            self.key.serialize_pem().into_bytes(),
+        )
+    }
+}
+
+/// Terminates TLS for a single connection and answers one PUBLISH.
+async fn tls_server(pki: &Pki, client_auth: bool) -> (SocketAddr, JoinHandle<()>) {
+    let provider = Arc::new(ring::default_provider());
+    let server = pki.issue("redis.test", ExtendedKeyUsagePurpose::ServerAuth);
+
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .unwrap();
+
+    let builder = if client_auth {
+        let verifier = WebPkiClientVerifier::builder_with_provider(pki.roots().into(), provider)
+            .build()
+            .unwrap();
+        builder.with_client_cert_verifier(verifier)
+    } else {
+        builder.with_no_client_auth()
+    };
+
+    let config = builder
+        .with_single_cert(
+            vec![CertificateDer::from(server.cert.der().to_vec())],
+            PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(server.key.serialize_der())),
+        )
+        .unwrap();
+
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let handle = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let Ok(stream) = acceptor.accept(stream).await else {
+            return;
+        };
+        let mut conn = Framed::new(stream, RespCodec::default());
+
+        assert_eq!(
+            recv(&mut conn).await,
+            value::array(vec![
+                value::bulk("PUBLISH"),
+                value::bulk("ch"),
+                value::bulk("hi")
+            ])
+        );
+        send(&mut conn, value::int(1)).await;
+    });
+
+    (addr, handle)
+}
+
+fn tls_info(addr: SocketAddr, tls: TlsConfig) -> ConnectionInfo {
+    ConnectionInfo::new(ConnectionAddr::TcpTls {
+        host: addr.ip().to_string(),
+        port: addr.port(),
+    })
+    .with_tls(tls)
+}
+
+#[tokio::test]
+async fn test_tls_custom_roots_and_client_cert() {
+    let pki = Pki::new();
+    let (addr, handle) = tls_server(&pki, true).await;
+
+    let client = pki.issue("client", ExtendedKeyUsagePurpose::ClientAuth);
+    let info = tls_info(
+        addr,
+        TlsConfig {
+            root_certs_pem: Some(pki.ca.pem().into_bytes()),
+            client_cert_pem: Some(client.pem()),
+            // the certificate is issued for redis.test, not 127.0.0.1
+            server_name: Some("redis.test".into()),
+            insecure: false,
+        },
+    );
+
+    let mut tx = Sender::connect(&info).await.unwrap();
+    assert_eq!(tx.publish("ch", "hi").await.unwrap(), 1);
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_tls_insecure() {
+    let pki = Pki::new();
+    let (addr, handle) = tls_server(&pki, false).await;
+
+    let info = tls_info(
+        addr,
+        TlsConfig {
+            insecure: true,
+            ..Default::default()
+        },
+    );
+
+    let mut tx = Sender::connect(&info).await.unwrap();
+    assert_eq!(tx.publish("ch", "hi").await.unwrap(), 1);
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_tls_untrusted_server() {
+    let pki = Pki::new();
+    let (addr, handle) = tls_server(&pki, false).await;
+
+    // the self-signed CA isn't in the default root store
+    let info = tls_info(
+        addr,
+        TlsConfig {
+            server_name: Some("redis.test".into()),
+            ..Default::default()
+        },
+    );
+
+    assert!(Sender::connect(&info).await.is_err());
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_tls_missing_client_cert() {
+    let pki = Pki::new();
+    let (addr, handle) = tls_server(&pki, true).await;
+
+    let info = tls_info(
+        addr,
+        TlsConfig {
+            root_certs_pem: Some(pki.ca.pem().into_bytes()),
+            server_name: Some("redis.test".into()),
+            ..Default::default()
+        },
+    );
+
+    // TLS 1.3 reports the rejected client cert on first use, not at connect
+    let res = match Sender::connect(&info).await {
+        Ok(mut tx) => tx.publish("ch", "hi").await.map(|_| ()),
+        Err(e) => Err(e),
+    };
+    assert!(res.is_err());
+
+    handle.await.unwrap();
+}
@@ -0,0 +1,191 @@
+mod common;
+
+use std::io;
+
+use common::{pipe_conn, recv, send};
+use redis_proto_parse::client::{Pipeline, Sender};
+use redis_proto_parse::resp::value::{self, RespValue};
+use redis_proto_parse::resp::RespCodec;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::codec::Framed;
+
+fn cmd(args: &[&str]) -> RespValue {
+    value::array(args.iter().map(value::bulk).collect())
+}
+
+#[tokio::test]
+async fn test_exec() {
+    let (mut tx, server) = Sender::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        // everything is sent before the first reply
+        assert_eq!(recv(&mut conn).await, cmd(&["MULTI"]));
+        assert_eq!(recv(&mut conn).await, cmd(&["SET", "a", "1"]));
+        assert_eq!(recv(&mut conn).await, cmd(&["LPUSH", "a", "x"]));
+        assert_eq!(recv(&mut conn).await, cmd(&["EXEC"]));
+
+        send(&mut conn, value::simple("OK")).await;
+        send(&mut conn, value::simple("QUEUED")).await;
+        send(&mut conn, value::simple("QUEUED")).await;
+        send(
+            &mut conn,
+            value::array(vec![
+                value::simple("OK"),
+                value::err("WRONGTYPE Operation against a key holding the wrong kind of value"),
+            ]),
+        )
+        .await;
+
+        conn
+    });
+
+    let mut pipe = Pipeline::new();
+    pipe.cmd(["SET", "a", "1"]).cmd(["LPUSH", "a", "x"]);
+
+    let results = tx.exec(&pipe).await.unwrap().unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].as_ref().unwrap(), &value::simple("OK"));
+    assert!(results[1]
+        .as_ref()
+        .unwrap_err()
+        .to_string()
+        .starts_with("WRONGTYPE"));
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_exec_queue_error() {
+    let (mut tx, server) = Sender::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        for _ in 0..3 {
+            recv(&mut conn).await;
+        }
+
+        send(&mut conn, value::simple("OK")).await;
+        send(
+            &mut conn,
+            value::err("ERR wrong number of arguments for 'get' command"),
+        )
+        .await;
+        send(
+            &mut conn,
+            value::err("EXECABORT Transaction discarded because of previous errors."),
+        )
+        .await;
+
+        conn
+    });
+
+    let mut pipe = Pipeline::new();
+    pipe.cmd(["GET"]);
+
+    let err = tx.exec(&pipe).await.unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "ERR wrong number of arguments for 'get' command"
+    );
+
+    handle.await.unwrap();
+}
+
+/// Plays one WATCH ... EXEC round of the transaction in the tests below,
+/// answering EXEC with `exec`.
+async fn watched_round<T>(conn: &mut Framed<T, RespCodec>, value: &str, exec: RespValue)
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    assert_eq!(recv(conn).await, cmd(&["WATCH", "counter"]));
+    send(conn, value::simple("OK")).await;
+
+    assert_eq!(recv(conn).await, cmd(&["GET", "counter"]));
+    send(conn, value::bulk(value)).await;
+
+    let next = (value.parse::<i64>().unwrap() + 1).to_string();
+    assert_eq!(recv(conn).await, cmd(&["MULTI"]));
+    assert_eq!(recv(conn).await, cmd(&["SET", "counter", &next]));
+    assert_eq!(recv(conn).await, cmd(&["EXEC"]));
+
+    send(conn, value::simple("OK")).await;
+    send(conn, value::simple("QUEUED")).await;
+    send(conn, exec).await;
+}
+
+async fn increment(tx: &mut Sender) -> io::Result<Vec<io::Result<RespValue>>> {
+    tx.transaction("counter", async |conn, pipe| {
+        let reply = conn.command(cmd(&["GET", "counter"])).await?;
+        let n: i64 = reply.as_str().unwrap().parse().unwrap();
+
+        pipe.cmd(["SET", "counter", &(n + 1).to_string()]);
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_transaction_retries_on_conflict() {
+    let (mut tx, server) = Sender::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        // another client changed the key, so EXEC returns a null array
+        watched_round(&mut conn, "1", value::ARRAY_NONE).await;
+        watched_round(&mut conn, "2", value::array(vec![value::simple("OK")])).await;
+
+        conn
+    });
+
+    let results = increment(&mut tx).await.unwrap();
+    assert_eq!(results[0].as_ref().unwrap(), &value::simple("OK"));
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_transaction_retry_limit() {
+    let (mut tx, server) = Sender::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    tx.set_transaction_retries(1);
+
+    let handle = tokio::spawn(async move {
+        watched_round(&mut conn, "1", value::ARRAY_NONE).await;
+        watched_round(&mut conn, "2", value::ARRAY_NONE).await;
+
+        conn
+    });
+
+    let err = increment(&mut tx).await.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_transaction_closure_error_unwatches() {
+    let (mut tx, server) = Sender::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        assert_eq!(recv(&mut conn).await, cmd(&["WATCH", "a", "b"]));
+        send(&mut conn, value::simple("OK")).await;
+
+        assert_eq!(recv(&mut conn).await, cmd(&["UNWATCH"]));
+        send(&mut conn, value::simple("OK")).await;
+
+        conn
+    });
+
+    let err = tx
+        .transaction(["a", "b"], async |_, _| {
+            Err(io::Error::new(io::ErrorKind::InvalidInput, "nope"))
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    handle.await.unwrap();
+}
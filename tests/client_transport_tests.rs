@@ -0,0 +1,89 @@
+mod common;
+
+use std::io;
+
+use common::{pipe_conn, recv, send};
+use redis_proto_parse::client::{ConnectionInfo, Receiver, Sender};
+use redis_proto_parse::resp::value;
+use tokio_util::codec::Framed;
+
+fn message(channel: &str, mesg: &str) -> value::RespValue {
+    value::array(vec![
+        value::bulk("message"),
+        value::bulk(channel),
+        value::bulk(mesg),
+    ])
+}
+
+#[tokio::test]
+async fn test_sender_pipe() {
+    let (mut tx, server) = Sender::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        assert_eq!(
+            recv(&mut conn).await,
+            value::array(vec![
+                value::bulk("PUBLISH"),
+                value::bulk("test_channel_1"),
+                value::bulk("hello")
+            ])
+        );
+        send(&mut conn, value::int(3)).await;
+    });
+
+    assert_eq!(tx.publish("test_channel_1", "hello").await.unwrap(), 3);
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_receiver_pipe() {
+    let (mut rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    send(&mut conn, message("test_channel_1", "hello")).await;
+    let mesg = rx.next().await.unwrap();
+    assert_eq!(mesg.channel, "test_channel_1");
+    assert_eq!(mesg.payload, "hello");
+
+    // closing the server end surfaces as a broken pipe
+    drop(conn);
+    let err = rx.next().await.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_unix_socket() {
+    let path = std::env::temp_dir().join(format!("redis_proto_parse_{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let listener = tokio::net::UnixListener::bind(&path).unwrap();
+
+    let handle = tokio::spawn(async move {
+        for _ in 0..2 {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut conn = Framed::new(stream, redis_proto_parse::resp::RespCodec::default());
+
+            let cmd = recv(&mut conn).await;
+            assert_eq!(
+                cmd,
+                value::array(vec![
+                    value::bulk("PUBLISH"),
+                    value::bulk("ch"),
+                    value::bulk("hi")
+                ])
+            );
+            send(&mut conn, value::int(1)).await;
+        }
+    });
+
+    let mut tx = Sender::connect_unix(&path).await.unwrap();
+    assert_eq!(tx.publish("ch", "hi").await.unwrap(), 1);
+
+    let info = ConnectionInfo::from_url(&format!("unix://{}", path.display())).unwrap();
+    let mut tx = Sender::connect(&info).await.unwrap();
+    assert_eq!(tx.publish("ch", "hi").await.unwrap(), 1);
+
+    handle.await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+}
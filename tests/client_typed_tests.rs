@@ -0,0 +1,148 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use common::{pipe_conn, recv, send};
+use redis_proto_parse::client::{Receiver, Sender, TypedChannel, Utf8Codec};
+use redis_proto_parse::resp::value::{self, RespValue};
+
+fn confirmation(channel: &str) -> RespValue {
+    value::array(vec![
+        value::bulk("subscribe"),
+        value::bulk(channel),
+        value::int(1),
+    ])
+}
+
+fn message(channel: &str, mesg: &[u8]) -> RespValue {
+    value::array(vec![
+        value::bulk("message"),
+        value::bulk(channel),
+        value::bulk(mesg),
+    ])
+}
+
+#[tokio::test]
+async fn test_typed_publish() {
+    let (mut tx, server) = Sender::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        assert_eq!(
+            recv(&mut conn).await,
+            value::array(vec![
+                value::bulk("PUBLISH"),
+                value::bulk("names"),
+                value::bulk("alice"),
+            ])
+        );
+        send(&mut conn, value::int(2)).await;
+    });
+
+    let channel = TypedChannel::new("names", Utf8Codec);
+    assert_eq!(
+        channel
+            .publish(&mut tx, &"alice".to_string())
+            .await
+            .unwrap(),
+        2
+    );
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_typed_receive() {
+    let (mut rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        recv(&mut conn).await;
+        send(&mut conn, confirmation("names")).await;
+        send(&mut conn, message("names", b"\xff\xfe")).await;
+        // other channels are skipped
+        send(&mut conn, message("other", b"bob")).await;
+        send(&mut conn, message("names", b"alice")).await;
+
+        conn
+    });
+
+    let mut channel = TypedChannel::new("names", Utf8Codec);
+    channel.subscribe(&mut rx).await.unwrap();
+
+    let err = channel.next(&mut rx).await.unwrap().unwrap_err();
+    assert_eq!(err.message().payload, &b"\xff\xfe"[..]);
+
+    assert_eq!(channel.next(&mut rx).await.unwrap().unwrap(), "alice");
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_typed_error_callback() {
+    let (mut rx, server) = Receiver::pipe(1024);
+    let mut conn = pipe_conn(server);
+
+    let handle = tokio::spawn(async move {
+        recv(&mut conn).await;
+        send(&mut conn, confirmation("names")).await;
+        send(&mut conn, message("names", b"\xff")).await;
+        send(&mut conn, message("names", b"alice")).await;
+
+        conn
+    });
+
+    let failed = Arc::new(Mutex::new(Vec::new()));
+    let failed_cb = failed.clone();
+
+    let mut channel = TypedChannel::new("names", Utf8Codec)
+        .on_error(move |e| failed_cb.lock().unwrap().push(e.into_message().payload));
+
+    channel.subscribe(&mut rx).await.unwrap();
+    assert_eq!(channel.next(&mut rx).await.unwrap().unwrap(), "alice");
+    assert_eq!(*failed.lock().unwrap(), [&b"\xff"[..]]);
+
+    handle.await.unwrap();
+}
+
+#[cfg(any(feature = "json", feature = "msgpack"))]
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct Event {
+    id: u32,
+    name: String,
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_json_codec() {
+    use redis_proto_parse::client::{JsonCodec, PayloadCodec};
+
+    let event = Event {
+        id: 7,
+        name: "created".into(),
+    };
+
+    let payload = JsonCodec.encode(&event).unwrap();
+    assert_eq!(payload, r#"{"id":7,"name":"created"}"#);
+    assert_eq!(JsonCodec.decode(&payload).ok(), Some(event));
+
+    let bad: Result<Event, _> = JsonCodec.decode(&"{}".into());
+    assert!(bad.is_err());
+}
+
+#[cfg(feature = "msgpack")]
+#[test]
+fn test_msgpack_codec() {
+    use redis_proto_parse::client::{MsgPackCodec, PayloadCodec};
+
+    let event = Event {
+        id: 7,
+        name: "created".into(),
+    };
+
+    let payload = MsgPackCodec.encode(&event).unwrap();
+    assert_eq!(MsgPackCodec.decode(&payload).ok(), Some(event));
+
+    let bad: Result<Event, _> = MsgPackCodec.decode(&"nope".into());
+    assert!(bad.is_err());
+}
@@ -0,0 +1,181 @@
+mod common;
+
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bytes::Bytes;
+use common::streams::FakeStreams;
+use redis_proto_parse::client::{Delivery, Sender, Worker, WorkerConfig};
+
+async fn start_worker(
+    fake: &FakeStreams,
+    consumer: &str,
+    config: impl FnOnce(&mut WorkerConfig),
+) -> Worker {
+    let (tx, server) = Sender::pipe(4096);
+    fake.serve(server);
+
+    let mut config_ = WorkerConfig::new("workers", consumer, ["jobs"]);
+    config(&mut config_);
+
+    let mut worker = Worker::new(tx, config_);
+    worker.create_groups("0").await.unwrap();
+    worker
+}
+
+fn retrying(config: &mut WorkerConfig) {
+    config.max_deliveries = 2;
+    config.dead_letter = Some(Bytes::from("jobs:dead"));
+    config.claim_interval = Duration::ZERO;
+    config.claim_min_idle = Duration::ZERO;
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_worker_acks_handled_entries() {
+    let fake = FakeStreams::new();
+    for n in ["1", "2", "3"] {
+        fake.add("jobs", &[("n", n)]);
+    }
+
+    let mut worker = start_worker(&fake, "w1", |config| config.concurrency = 2).await;
+
+    // creating the groups again is fine
+    worker.create_groups("0").await.unwrap();
+
+    let running = AtomicUsize::new(0);
+    let most = AtomicUsize::new(0);
+    let seen = Mutex::new(Vec::new());
+
+    let handler = async |delivery: &Delivery| -> io::Result<()> {
+        let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+        most.fetch_max(now, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        running.fetch_sub(1, Ordering::SeqCst);
+
+        assert_eq!(delivery.deliveries, 1);
+        seen.lock()
+            .unwrap()
+            .push(delivery.entry.get("n").unwrap().clone());
+        Ok(())
+    };
+
+    assert_eq!(worker.run_once(&handler).await.unwrap(), 2);
+    assert_eq!(worker.run_once(&handler).await.unwrap(), 1);
+    assert_eq!(worker.run_once(&handler).await.unwrap(), 0);
+
+    assert_eq!(most.load(Ordering::SeqCst), 2);
+    assert_eq!(*seen.lock().unwrap(), vec!["1", "2", "3"]);
+    assert!(fake.pending("jobs", "workers").is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_worker_refills_free_slots() {
+    let fake = FakeStreams::new();
+    for n in ["slow", "fast", "next"] {
+        fake.add("jobs", &[("n", n)]);
+    }
+
+    let mut worker = start_worker(&fake, "w1", |config| {
+        config.concurrency = 2;
+        config.block = Duration::from_millis(20);
+    })
+    .await;
+
+    let seen = Mutex::new(Vec::new());
+    let handler = async |delivery: &Delivery| -> io::Result<()> {
+        let n = delivery.entry.get("n").unwrap().clone();
+        let delay = if n == "slow" { 200 } else { 10 };
+        tokio::time::sleep(Duration::from_millis(delay)).await;
+        seen.lock().unwrap().push(n);
+        Ok(())
+    };
+
+    // run never returns on its own
+    let run = tokio::time::timeout(Duration::from_millis(400), worker.run(&handler));
+    assert!(run.await.is_err());
+
+    // the third entry took the slot freed by the fast one, without waiting
+    // for the slow one to finish
+    assert_eq!(*seen.lock().unwrap(), vec!["fast", "next", "slow"]);
+    assert!(fake.pending("jobs", "workers").is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_worker_dead_letters_after_max_deliveries() {
+    let fake = FakeStreams::new();
+    let id = fake.add("jobs", &[("n", "1")]);
+
+    let mut worker = start_worker(&fake, "w1", retrying).await;
+
+    let deliveries = Mutex::new(Vec::new());
+    let handler = async |delivery: &Delivery| -> io::Result<()> {
+        deliveries.lock().unwrap().push(delivery.deliveries);
+        Err(io::Error::other("failed"))
+    };
+
+    assert_eq!(worker.run_once(&handler).await.unwrap(), 1);
+    assert_eq!(
+        fake.pending("jobs", "workers"),
+        vec![(id.clone(), "w1".to_string(), 1)]
+    );
+
+    // the retry is claimed back from the pending list
+    assert_eq!(worker.run_once(&handler).await.unwrap(), 1);
+    assert_eq!(*deliveries.lock().unwrap(), vec![1, 2]);
+    assert!(fake.pending("jobs", "workers").is_empty());
+
+    let dead = fake.entries("jobs:dead");
+    assert_eq!(dead.len(), 1);
+    assert_eq!(
+        dead[0].1,
+        [
+            ("n", "1"),
+            ("stream", "jobs"),
+            ("id", &id),
+            ("deliveries", "2")
+        ]
+        .map(|(k, v)| (Bytes::from(k.to_string()), Bytes::from(v.to_string())))
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_worker_claims_from_crashed_consumer() {
+    let fake = FakeStreams::new();
+    let first = fake.add("jobs", &[("n", "1")]);
+    let second = fake.add("jobs", &[("n", "2")]);
+
+    // w1 reads both entries but never finishes them
+    let mut crashed = start_worker(&fake, "w1", |_| {}).await;
+    let stuck = async |_: &Delivery| -> io::Result<()> { Err(io::Error::other("crashed")) };
+    assert_eq!(crashed.run_once(&stuck).await.unwrap(), 2);
+
+    let mut worker = start_worker(&fake, "w2", retrying).await;
+
+    let seen = Mutex::new(Vec::new());
+    let handler = async |delivery: &Delivery| -> io::Result<()> {
+        let mut seen = seen.lock().unwrap();
+        seen.push((delivery.entry.id.clone(), delivery.deliveries));
+        Ok(())
+    };
+
+    assert_eq!(worker.run_once(&handler).await.unwrap(), 2);
+    assert_eq!(*seen.lock().unwrap(), vec![(first, 2), (second, 2)]);
+    assert!(fake.pending("jobs", "workers").is_empty());
+
+    // entries that already failed too often aren't handled again
+    let third = fake.add("jobs", &[("n", "3")]);
+    assert_eq!(crashed.run_once(&stuck).await.unwrap(), 1);
+
+    let mut worker = start_worker(&fake, "w3", |config| {
+        retrying(config);
+        config.max_deliveries = 1;
+    })
+    .await;
+
+    assert_eq!(worker.run_once(&handler).await.unwrap(), 0);
+    assert_eq!(seen.lock().unwrap().len(), 2);
+    assert!(fake.pending("jobs", "workers").is_empty());
+    assert_eq!(fake.entries("jobs:dead")[0].1[2].1, third);
+}
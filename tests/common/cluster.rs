@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use redis_proto_parse::client::ConnectionInfo;
+use redis_proto_parse::cluster::{key_slot, SLOT_COUNT};
+use redis_proto_parse::resp::value::{self, RespValue};
+use redis_proto_parse::resp::RespCodec;
+use tokio::net::TcpListener;
+use tokio_util::codec::Framed;
+
+struct Node {
+    port: u16,
+    data: HashMap<Bytes, Bytes>,
+}
+
+struct State {
+    nodes: Vec<Node>,
+    /// The node serving each slot.
+    owners: Vec<usize>,
+    /// Slots being migrated, to the node importing them.
+    migrating: HashMap<u16, usize>,
+    shards: bool,
+    moved: usize,
+    asked: usize,
+}
+
+/// An in-process cluster of masters serving GET and SET, the CLUSTER
+/// SHARDS/SLOTS topology commands, and ASKING. Keys sent to the wrong node
+/// get -MOVED, and keys of a migrating slot that already left get -ASK.
+#[derive(Clone)]
+pub struct FakeCluster {
+    state: Arc<Mutex<State>>,
+}
+
+impl FakeCluster {
+    /// Starts `count` nodes, splitting the slots evenly between them.
+    pub async fn start(count: usize) -> Self {
+        let mut listeners = Vec::new();
+        let mut nodes = Vec::new();
+
+        for _ in 0..count {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            nodes.push(Node {
+                port: listener.local_addr().unwrap().port(),
+                data: HashMap::new(),
+            });
+            listeners.push(listener);
+        }
+
+        let per_node = (SLOT_COUNT as usize).div_ceil(count);
+        let state = State {
+            nodes,
+            owners: (0..SLOT_COUNT as usize).map(|s| s / per_node).collect(),
+            migrating: HashMap::new(),
+            shards: true,
+            moved: 0,
+            asked: 0,
+        };
+        let cluster = Self {
+            state: Arc::new(Mutex::new(state)),
+        };
+
+        for (n, listener) in listeners.into_iter().enumerate() {
+            let cluster = cluster.clone();
+            tokio::spawn(async move {
+                loop {
+                    let (stream, _) = listener.accept().await.unwrap();
+                    let cluster = cluster.clone();
+                    tokio::spawn(async move {
+                        let mut conn = Framed::new(stream, RespCodec::default());
+                        let mut asking = false;
+                        while let Some(Ok(cmd)) = conn.next().await {
+                            let reply = cluster.handle(n, &mut asking, cmd);
+                            if conn.send(reply).await.is_err() {
+                                break;
+                            }
+                        }
+                    });
+                }
+            });
+        }
+
+        cluster
+    }
+
+    pub fn port(&self, node: usize) -> u16 {
+        self.state.lock().unwrap().nodes[node].port
+    }
+
+    pub fn info(&self, node: usize) -> ConnectionInfo {
+        ConnectionInfo::tcp("127.0.0.1", self.port(node))
+    }
+
+    /// Whether CLUSTER SHARDS is known, as on Redis 7.0 and later.
+    pub fn set_shards(&self, shards: bool) {
+        self.state.lock().unwrap().shards = shards;
+    }
+
+    pub fn owner(&self, slot: u16) -> usize {
+        self.state.lock().unwrap().owners[slot as usize]
+    }
+
+    /// Hands `slot` over to `to` along with its keys, as when a migration
+    /// completes.
+    pub fn move_slot(&self, slot: u16, to: usize) {
+        let mut state = self.state.lock().unwrap();
+        let from = state.owners[slot as usize];
+
+        let keys = state.nodes[from]
+            .data
+            .keys()
+            .filter(|k| key_slot(k) == slot)
+            .cloned()
+            .collect::<Vec<_>>();
+        for key in keys {
+            let val = state.nodes[from].data.remove(&key).unwrap();
+            state.nodes[to].data.insert(key, val);
+        }
+
+        state.owners[slot as usize] = to;
+        state.migrating.remove(&slot);
+    }
+
+    /// Starts migrating `slot` to `to`. Keys are moved with [`Self::migrate_key`].
+    pub fn migrate_slot(&self, slot: u16, to: usize) {
+        self.state.lock().unwrap().migrating.insert(slot, to);
+    }
+
+    pub fn migrate_key(&self, key: &str) {
+        let mut state = self.state.lock().unwrap();
+        let slot = key_slot(key);
+        let from = state.owners[slot as usize];
+        let to = state.migrating[&slot];
+
+        if let Some(val) = state.nodes[from].data.remove(key.as_bytes()) {
+            state.nodes[to]
+                .data
+                .insert(Bytes::copy_from_slice(key.as_bytes()), val);
+        }
+    }
+
+    pub fn get(&self, node: usize, key: &str) -> Option<Bytes> {
+        self.state.lock().unwrap().nodes[node]
+            .data
+            .get(key.as_bytes())
+            .cloned()
+    }
+
+    /// The number of -MOVED replies sent.
+    pub fn moved(&self) -> usize {
+        self.state.lock().unwrap().moved
+    }
+
+    /// The number of -ASK replies sent.
+    pub fn asked(&self) -> usize {
+        self.state.lock().unwrap().asked
+    }
+
+    fn handle(&self, node: usize, asking: &mut bool, cmd: RespValue) -> RespValue {
+        let args = match cmd {
+            RespValue::Array(Some(items)) => items
+                .into_iter()
+                .map(|item| match item {
+                    RespValue::BulkString(Some(buf)) => Bytes::from(buf),
+                    item => panic!("unexpected argument {:?}", item),
+                })
+                .collect::<Vec<Bytes>>(),
+            cmd => panic!("unexpected command {:?}", cmd),
+        };
+
+        let mut state = self.state.lock().unwrap();
+        let name = String::from_utf8(args[0].to_ascii_uppercase()).unwrap();
+        let was_asking = std::mem::take(asking);
+
+        let key = match (name.as_str(), &args[1..]) {
+            ("ASKING", []) => {
+                *asking = true;
+                return value::simple("OK");
+            }
+            ("CLUSTER", [sub]) if &sub[..] == b"SHARDS" => {
+                if !state.shards {
+                    return value::err("ERR unknown subcommand 'SHARDS'.");
+                }
+                return state.shards();
+            }
+            ("CLUSTER", [sub]) if &sub[..] == b"SLOTS" => return state.slots(),
+            ("GET", [key]) | ("SET", [key, _]) => key.clone(),
+            _ => panic!("unexpected command {:?}", args),
+        };
+
+        let slot = key_slot(&key);
+        let owner = state.owners[slot as usize];
+        let migrating = state.migrating.get(&slot).copied();
+
+        let serve = if owner == node {
+            match migrating {
+                Some(to) if !state.nodes[node].data.contains_key(&key) => {
+                    state.asked += 1;
+                    let port = state.nodes[to].port;
+                    return value::err(format!("ASK {} 127.0.0.1:{}", slot, port));
+                }
+                _ => true,
+            }
+        } else {
+            was_asking && migrating == Some(node)
+        };
+
+        if !serve {
+            state.moved += 1;
+            let port = state.nodes[owner].port;
+            return value::err(format!("MOVED {} 127.0.0.1:{}", slot, port));
+        }
+
+        let data = &mut state.nodes[node].data;
+        match &args[1..] {
+            [_, val] => {
+                data.insert(key, val.clone());
+                value::simple("OK")
+            }
+            _ => match data.get(&key) {
+                Some(val) => value::bulk(val),
+                None => value::BULK_NONE,
+            },
+        }
+    }
+}
+
+impl State {
+    /// The contiguous slot ranges served by each node.
+    fn ranges(&self) -> Vec<(usize, Vec<(u16, u16)>)> {
+        let mut ranges = vec![Vec::<(u16, u16)>::new(); self.nodes.len()];
+
+        for (slot, &owner) in self.owners.iter().enumerate() {
+            let slot = slot as u16;
+            match ranges[owner].last_mut() {
+                Some((_, end)) if *end + 1 == slot => *end = slot,
+                _ => ranges[owner].push((slot, slot)),
+            }
+        }
+
+        ranges.into_iter().enumerate().collect()
+    }
+
+    fn shards(&self) -> RespValue {
+        let shards = self
+            .ranges()
+            .into_iter()
+            .filter(|(_, ranges)| !ranges.is_empty())
+            .map(|(n, ranges)| {
+                let slots = ranges
+                    .iter()
+                    .flat_map(|&(start, end)| [value::int(start as i64), value::int(end as i64)])
+                    .collect();
+                let node = value::array(vec![
+                    value::bulk("id"),
+                    value::bulk(format!("node{}", n)),
+                    value::bulk("port"),
+                    value::int(self.nodes[n].port as i64),
+                    value::bulk("ip"),
+                    value::bulk("127.0.0.1"),
+                    value::bulk("endpoint"),
+                    value::bulk("127.0.0.1"),
+                    value::bulk("role"),
+                    value::bulk("master"),
+                    value::bulk("replication-offset"),
+                    value::int(0),
+                    value::bulk("health"),
+                    value::bulk("online"),
+                ]);
+
+                value::array(vec![
+                    value::bulk("slots"),
+                    value::array(slots),
+                    value::bulk("nodes"),
+                    value::array(vec![node]),
+                ])
+            })
+            .collect();
+
+        value::array(shards)
+    }
+
+    fn slots(&self) -> RespValue {
+        let slots = self
+            .ranges()
+            .into_iter()
+            .flat_map(|(n, ranges)| {
+                let port = self.nodes[n].port;
+                ranges.into_iter().map(move |(start, end)| {
+                    value::array(vec![
+                        value::int(start as i64),
+                        value::int(end as i64),
+                        value::array(vec![
+                            value::bulk("127.0.0.1"),
+                            value::int(port as i64),
+                            value::bulk(format!("node{}", n)),
+                        ]),
+                    ])
+                })
+            })
+            .collect();
+
+        value::array(slots)
+    }
+}
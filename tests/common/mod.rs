@@ -0,0 +1,115 @@
+#![allow(dead_code)]
+
+use std::net::SocketAddr;
+
+use bytes::BytesMut;
+use futures::{SinkExt, StreamExt};
+use redis_proto_parse::resp::{value, value::RespValue, RespCodec};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, DuplexStream};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::codec::{Decoder, Framed};
+
+pub mod cluster;
+pub mod streams;
+
+/// A single-connection stand-in for redis-server, driven by the test.
+pub struct FakeServer {
+    listener: TcpListener,
+}
+
+pub type ServerConn = Framed<TcpStream, RespCodec>;
+
+impl FakeServer {
+    pub async fn bind() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        Self { listener }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.listener.local_addr().unwrap()
+    }
+
+    pub async fn accept(&self) -> ServerConn {
+        let (stream, _) = self.listener.accept().await.unwrap();
+
+        Framed::new(stream, RespCodec::default())
+    }
+}
+
+/// Frames the server end of an in-memory pipe.
+pub fn pipe_conn(stream: DuplexStream) -> Framed<DuplexStream, RespCodec> {
+    Framed::new(stream, RespCodec::default())
+}
+
+/// Reads the next command sent by the client.
+pub async fn recv<T>(conn: &mut Framed<T, RespCodec>) -> RespValue
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    conn.next()
+        .await
+        .expect("client closed the connection")
+        .expect("client sent an invalid frame")
+}
+
+pub async fn send<T>(conn: &mut Framed<T, RespCodec>, val: RespValue)
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    conn.send(val).await.unwrap();
+}
+
+/// Loads the (Rx, Tx) bytes of a capture in `example_test_cases`.
+pub fn fixture(name: &str) -> (Vec<u8>, Vec<u8>) {
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("example_test_cases")
+        .join(name);
+
+    (
+        std::fs::read(dir.join("Rx.bin")).unwrap(),
+        std::fs::read(dir.join("Tx.bin")).unwrap(),
+    )
+}
+
+/// Decodes every frame in a capture.
+pub fn frames(data: &[u8]) -> Vec<RespValue> {
+    let mut data = BytesMut::from(data);
+    let mut codec = RespCodec::default();
+    let mut frames = Vec::new();
+
+    while let Some(frame) = codec.decode(&mut data).unwrap() {
+        frames.push(frame);
+    }
+
+    assert!(data.is_empty(), "trailing bytes in capture");
+    frames
+}
+
+/// Command names are case insensitive, captures from redis-cli use lower case.
+fn normalize(cmd: RespValue) -> RespValue {
+    match cmd {
+        RespValue::Array(Some(mut items)) => {
+            if let Some(name) = items.first().and_then(RespValue::as_str) {
+                items[0] = value::bulk(name.to_ascii_uppercase());
+            }
+            value::array(items)
+        }
+        cmd => cmd,
+    }
+}
+
+/// Plays the server side of a capture: checks that the client sends the
+/// commands in Tx.bin, then answers with the raw bytes of Rx.bin.
+pub async fn replay<T>(conn: &mut Framed<T, RespCodec>, name: &str)
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    let (rx, tx) = fixture(name);
+
+    for expected in frames(&tx) {
+        assert_eq!(normalize(recv(conn).await), normalize(expected));
+    }
+
+    conn.get_mut().write_all(&rx).await.unwrap();
+}
@@ -0,0 +1,385 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use redis_proto_parse::resp::value::{self, RespValue};
+use tokio::io::DuplexStream;
+
+use super::pipe_conn;
+
+type Fields = Vec<(Bytes, Bytes)>;
+
+struct Pending {
+    consumer: Bytes,
+    deliveries: i64,
+    delivered: Instant,
+}
+
+#[derive(Default)]
+struct Group {
+    last: u64,
+    pending: BTreeMap<u64, Pending>,
+}
+
+#[derive(Default)]
+struct Stream {
+    entries: BTreeMap<u64, Fields>,
+    groups: HashMap<Bytes, Group>,
+}
+
+#[derive(Default)]
+struct Store {
+    seq: u64,
+    streams: HashMap<Bytes, Stream>,
+}
+
+/// An in-memory server implementing the stream commands used by the worker
+/// runtime. Several clients can be served at once; a blocking read that
+/// finds nothing waits out its timeout, without noticing entries added
+/// meanwhile.
+#[derive(Clone, Default)]
+pub struct FakeStreams {
+    store: Arc<Mutex<Store>>,
+}
+
+fn id(seq: u64) -> String {
+    format!("{}-0", seq)
+}
+
+fn parse_id(id: &[u8], open: u64) -> u64 {
+    match id {
+        b"-" => 0,
+        b"+" => u64::MAX,
+        b"$" => open,
+        id => std::str::from_utf8(id)
+            .unwrap()
+            .split('-')
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap(),
+    }
+}
+
+/// The BLOCK timeout of an XREADGROUP.
+fn block_timeout(cmd: &RespValue) -> Option<Duration> {
+    let RespValue::Array(Some(items)) = cmd else {
+        return None;
+    };
+
+    let args: Vec<_> = items.iter().filter_map(RespValue::as_str).collect();
+
+    match args.as_slice() {
+        ["XREADGROUP", rest @ ..] => rest
+            .windows(2)
+            .find(|pair| pair[0] == "BLOCK")
+            .map(|pair| Duration::from_millis(pair[1].parse().unwrap())),
+        _ => None,
+    }
+}
+
+fn entry(seq: u64, fields: Option<&Fields>) -> RespValue {
+    let fields = match fields {
+        Some(fields) => value::array(
+            fields
+                .iter()
+                .flat_map(|(k, v)| [value::bulk(k), value::bulk(v)])
+                .collect(),
+        ),
+        None => value::ARRAY_NONE,
+    };
+
+    value::array(vec![value::bulk(id(seq)), fields])
+}
+
+impl FakeStreams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serves one client on the server end of a pipe until it disconnects.
+    pub fn serve(&self, stream: DuplexStream) {
+        let this = self.clone();
+        let mut conn = pipe_conn(stream);
+
+        tokio::spawn(async move {
+            while let Some(Ok(cmd)) = conn.next().await {
+                let block = block_timeout(&cmd);
+                let reply = this.handle(cmd);
+
+                if reply == value::ARRAY_NONE {
+                    if let Some(block) = block {
+                        tokio::time::sleep(block).await;
+                    }
+                }
+
+                conn.send(reply).await.unwrap();
+            }
+        });
+    }
+
+    pub fn add(&self, stream: &str, fields: &[(&str, &str)]) -> String {
+        let mut store = self.store.lock().unwrap();
+        store.seq += 1;
+        let seq = store.seq;
+
+        let fields = fields
+            .iter()
+            .map(|(k, v)| {
+                (
+                    Bytes::copy_from_slice(k.as_bytes()),
+                    Bytes::copy_from_slice(v.as_bytes()),
+                )
+            })
+            .collect();
+
+        store
+            .streams
+            .entry(Bytes::copy_from_slice(stream.as_bytes()))
+            .or_default()
+            .entries
+            .insert(seq, fields);
+
+        id(seq)
+    }
+
+    /// The entries of a stream as (id, fields).
+    pub fn entries(&self, stream: &str) -> Vec<(String, Fields)> {
+        let store = self.store.lock().unwrap();
+
+        store
+            .streams
+            .get(stream.as_bytes())
+            .map(|s| {
+                s.entries
+                    .iter()
+                    .map(|(seq, fields)| (id(*seq), fields.clone()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The pending entries of a group as (id, consumer, deliveries).
+    pub fn pending(&self, stream: &str, group: &str) -> Vec<(String, String, i64)> {
+        let store = self.store.lock().unwrap();
+
+        store.streams[stream.as_bytes()].groups[group.as_bytes()]
+            .pending
+            .iter()
+            .map(|(seq, p)| {
+                (
+                    id(*seq),
+                    String::from_utf8(p.consumer.to_vec()).unwrap(),
+                    p.deliveries,
+                )
+            })
+            .collect()
+    }
+
+    fn handle(&self, cmd: RespValue) -> RespValue {
+        let args = match cmd {
+            RespValue::Array(Some(items)) => items
+                .into_iter()
+                .map(|item| match item {
+                    RespValue::BulkString(Some(buf)) => Bytes::from(buf),
+                    item => panic!("unexpected argument {:?}", item),
+                })
+                .collect::<Vec<Bytes>>(),
+            cmd => panic!("unexpected command {:?}", cmd),
+        };
+
+        let mut store = self.store.lock().unwrap();
+        let name = String::from_utf8(args[0].to_ascii_uppercase()).unwrap();
+
+        match (name.as_str(), &args[1..]) {
+            ("XGROUP", [sub, key, group, start, rest @ ..]) if &sub[..] == b"CREATE" => {
+                if !store.streams.contains_key(key) && rest.is_empty() {
+                    return value::err("ERR The XGROUP subcommand requires the key to exist.");
+                }
+
+                let open = store.seq;
+                let stream = store.streams.entry(key.clone()).or_default();
+
+                if stream.groups.contains_key(group) {
+                    return value::err("BUSYGROUP Consumer Group name already exists");
+                }
+
+                let last = parse_id(start, open);
+                stream.groups.insert(
+                    group.clone(),
+                    Group {
+                        last,
+                        ..Group::default()
+                    },
+                );
+
+                value::simple("OK")
+            }
+            ("XADD", [key, star, fields @ ..]) if &star[..] == b"*" => {
+                store.seq += 1;
+                let seq = store.seq;
+
+                let fields = fields
+                    .chunks(2)
+                    .map(|pair| (pair[0].clone(), pair[1].clone()))
+                    .collect();
+
+                store
+                    .streams
+                    .entry(key.clone())
+                    .or_default()
+                    .entries
+                    .insert(seq, fields);
+
+                value::bulk(id(seq))
+            }
+            ("XREADGROUP", [_, group, consumer, rest @ ..]) => {
+                let mut count = usize::MAX;
+                let mut rest = rest;
+
+                loop {
+                    match rest {
+                        [opt, n, tail @ ..] if &opt[..] == b"COUNT" => {
+                            count = std::str::from_utf8(n).unwrap().parse().unwrap();
+                            rest = tail;
+                        }
+                        [opt, _, tail @ ..] if &opt[..] == b"BLOCK" => rest = tail,
+                        [opt, tail @ ..] if &opt[..] == b"STREAMS" => {
+                            rest = tail;
+                            break;
+                        }
+                        _ => panic!("unexpected XREADGROUP arguments"),
+                    }
+                }
+
+                let (keys, ids) = rest.split_at(rest.len() / 2);
+                assert!(ids.iter().all(|id| &id[..] == b">"));
+
+                let mut reads = Vec::new();
+
+                for key in keys {
+                    let stream = store.streams.get_mut(key).unwrap();
+                    let group = stream.groups.get_mut(group).unwrap();
+
+                    let new = stream
+                        .entries
+                        .range(group.last + 1..)
+                        .take(count)
+                        .map(|(seq, fields)| (*seq, fields.clone()))
+                        .collect::<Vec<_>>();
+
+                    if new.is_empty() {
+                        continue;
+                    }
+
+                    for (seq, _) in &new {
+                        group.last = *seq;
+                        group.pending.insert(
+                            *seq,
+                            Pending {
+                                consumer: consumer.clone(),
+                                deliveries: 1,
+                                delivered: Instant::now(),
+                            },
+                        );
+                    }
+
+                    let entries = new
+                        .iter()
+                        .map(|(seq, fields)| entry(*seq, Some(fields)))
+                        .collect();
+
+                    reads.push(value::array(vec![value::bulk(key), value::array(entries)]));
+                }
+
+                if reads.is_empty() {
+                    value::ARRAY_NONE
+                } else {
+                    value::array(reads)
+                }
+            }
+            ("XACK", [key, group, ids @ ..]) => {
+                let group = store
+                    .streams
+                    .get_mut(key)
+                    .unwrap()
+                    .groups
+                    .get_mut(group)
+                    .unwrap();
+
+                let acked = ids
+                    .iter()
+                    .filter(|id| group.pending.remove(&parse_id(id, 0)).is_some())
+                    .count();
+
+                value::int(acked as i64)
+            }
+            ("XPENDING", [key, group, rest @ ..]) => {
+                let (min_idle, rest) = match rest {
+                    [opt, ms, rest @ ..] if &opt[..] == b"IDLE" => {
+                        let ms = std::str::from_utf8(ms).unwrap().parse().unwrap();
+                        (Duration::from_millis(ms), rest)
+                    }
+                    rest => (Duration::ZERO, rest),
+                };
+
+                let [start, end, count, consumer @ ..] = rest else {
+                    panic!("only the extended form of XPENDING is supported");
+                };
+
+                let count: usize = std::str::from_utf8(count).unwrap().parse().unwrap();
+                let group = &store.streams[key].groups[group];
+
+                let entries = group
+                    .pending
+                    .range(parse_id(start, 0)..=parse_id(end, 0))
+                    .filter(|(_, p)| p.delivered.elapsed() >= min_idle)
+                    .filter(|(_, p)| consumer.first().is_none_or(|c| *c == p.consumer))
+                    .take(count)
+                    .map(|(seq, p)| {
+                        value::array(vec![
+                            value::bulk(id(*seq)),
+                            value::bulk(&p.consumer),
+                            value::int(p.delivered.elapsed().as_millis() as i64),
+                            value::int(p.deliveries),
+                        ])
+                    })
+                    .collect();
+
+                value::array(entries)
+            }
+            ("XAUTOCLAIM", [key, group, consumer, min_idle, start, _, count]) => {
+                let min_idle =
+                    Duration::from_millis(std::str::from_utf8(min_idle).unwrap().parse().unwrap());
+                let count: usize = std::str::from_utf8(count).unwrap().parse().unwrap();
+
+                let stream = store.streams.get_mut(key).unwrap();
+                let group = stream.groups.get_mut(group).unwrap();
+
+                let mut claimed = Vec::new();
+
+                for (seq, p) in group.pending.range_mut(parse_id(start, 0)..) {
+                    if claimed.len() == count {
+                        break;
+                    }
+
+                    if p.delivered.elapsed() >= min_idle {
+                        p.consumer = consumer.clone();
+                        p.deliveries += 1;
+                        p.delivered = Instant::now();
+                        claimed.push(entry(*seq, stream.entries.get(seq)));
+                    }
+                }
+
+                value::array(vec![
+                    value::bulk("0-0"),
+                    value::array(claimed),
+                    value::array(vec![]),
+                ])
+            }
+            _ => value::err(format!("ERR unknown command '{}'", name)),
+        }
+    }
+}
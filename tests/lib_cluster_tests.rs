@@ -0,0 +1,184 @@
+mod common;
+
+use std::io;
+
+use common::cluster::FakeCluster;
+use redis_proto_parse::cluster::{crc16, key_slot, ClusterClient, NodeAddr};
+use redis_proto_parse::resp::value::{self, RespValue};
+
+fn cmd(args: &[&str]) -> RespValue {
+    value::array(args.iter().map(value::bulk).collect())
+}
+
+async fn set(client: &mut ClusterClient, key: &str, val: &str) {
+    let reply = client.command(key, cmd(&["SET", key, val])).await.unwrap();
+    assert_eq!(reply, value::simple("OK"));
+}
+
+async fn get(client: &mut ClusterClient, key: &str) -> RespValue {
+    client.command(key, cmd(&["GET", key])).await.unwrap()
+}
+
+#[test]
+fn test_key_slot() {
+    assert_eq!(crc16(b"123456789"), 0x31c3);
+
+    assert_eq!(key_slot(""), 0);
+    assert_eq!(key_slot("foo"), 12182);
+    assert_eq!(key_slot("bar"), 5061);
+    assert_eq!(key_slot("123456789"), 12739);
+
+    // only the hashtag is hashed
+    assert_eq!(key_slot("{user1000}.following"), key_slot("user1000"));
+    assert_eq!(key_slot("{user1000}.followers"), key_slot("user1000"));
+    assert_eq!(key_slot("foo{bar}{zap}"), key_slot("bar"));
+    assert_eq!(key_slot("foo{{bar}}zap"), key_slot("{bar"));
+
+    // an empty or unterminated tag hashes the whole key
+    assert_ne!(key_slot("foo{}{bar}"), key_slot("bar"));
+    assert_eq!(key_slot("foo{}{bar}"), crc16(b"foo{}{bar}") % 16384);
+    assert_eq!(key_slot("foo{bar"), crc16(b"foo{bar") % 16384);
+}
+
+#[test]
+fn test_node_addr() {
+    assert_eq!(
+        NodeAddr::parse("10.0.0.1:7000"),
+        Some(NodeAddr::new("10.0.0.1", 7000))
+    );
+    assert_eq!(
+        NodeAddr::parse("::1:7000"),
+        Some(NodeAddr::new("::1", 7000))
+    );
+    assert_eq!(NodeAddr::parse("10.0.0.1"), None);
+    assert_eq!(NodeAddr::new("h", 1).to_string(), "h:1");
+}
+
+#[tokio::test]
+async fn test_routes_by_slot() {
+    let cluster = FakeCluster::start(3).await;
+    let mut client = ClusterClient::connect(&[cluster.info(1)]).await.unwrap();
+    assert_eq!(client.masters().len(), 3);
+
+    for key in ["foo", "bar", "baz", "{user}.a", "{user}.b"] {
+        set(&mut client, key, key).await;
+        assert_eq!(get(&mut client, key).await, value::bulk(key));
+
+        let owner = cluster.owner(key_slot(key));
+        assert_eq!(cluster.get(owner, key).unwrap(), key);
+        assert_eq!(
+            client.slot_owner(key_slot(key)).unwrap().port,
+            cluster.port(owner)
+        );
+    }
+
+    assert_eq!(cluster.moved(), 0);
+}
+
+#[tokio::test]
+async fn test_cluster_slots_fallback() {
+    let cluster = FakeCluster::start(2).await;
+    cluster.set_shards(false);
+
+    let mut client = ClusterClient::connect(&[cluster.info(0)]).await.unwrap();
+    assert_eq!(client.masters().len(), 2);
+
+    set(&mut client, "foo", "1").await;
+    assert_eq!(
+        cluster.get(cluster.owner(key_slot("foo")), "foo").unwrap(),
+        "1"
+    );
+    assert_eq!(cluster.moved(), 0);
+}
+
+#[tokio::test]
+async fn test_follows_moved() {
+    let cluster = FakeCluster::start(3).await;
+    let mut client = ClusterClient::connect(&[cluster.info(0)]).await.unwrap();
+
+    set(&mut client, "foo", "1").await;
+
+    let slot = key_slot("foo");
+    let to = (cluster.owner(slot) + 1) % 3;
+    cluster.move_slot(slot, to);
+
+    assert_eq!(get(&mut client, "foo").await, value::bulk("1"));
+    assert_eq!(cluster.moved(), 1);
+    assert_eq!(client.slot_owner(slot).unwrap().port, cluster.port(to));
+
+    // the layout was updated, so there are no more redirects
+    set(&mut client, "foo", "2").await;
+    assert_eq!(cluster.get(to, "foo").unwrap(), "2");
+    assert_eq!(cluster.moved(), 1);
+}
+
+#[tokio::test]
+async fn test_follows_ask() {
+    let cluster = FakeCluster::start(2).await;
+    let mut client = ClusterClient::connect(&[cluster.info(0)]).await.unwrap();
+
+    let slot = key_slot("{tag}a");
+    let from = cluster.owner(slot);
+    let to = 1 - from;
+
+    set(&mut client, "{tag}a", "a").await;
+    set(&mut client, "{tag}b", "b").await;
+
+    cluster.migrate_slot(slot, to);
+    cluster.migrate_key("{tag}a");
+
+    assert_eq!(get(&mut client, "{tag}a").await, value::bulk("a"));
+    assert_eq!(cluster.asked(), 1);
+    assert_eq!(cluster.moved(), 0);
+
+    // ASK doesn't change the layout, keys still there are read from the source
+    assert_eq!(client.slot_owner(slot).unwrap().port, cluster.port(from));
+    assert_eq!(get(&mut client, "{tag}b").await, value::bulk("b"));
+    assert_eq!(cluster.asked(), 1);
+
+    cluster.move_slot(slot, to);
+    assert_eq!(get(&mut client, "{tag}b").await, value::bulk("b"));
+    assert_eq!(cluster.moved(), 1);
+}
+
+#[tokio::test]
+async fn test_route_by_command_keys() {
+    let cluster = FakeCluster::start(3).await;
+    let mut client = ClusterClient::connect(&[cluster.info(0)]).await.unwrap();
+
+    for key in ["foo", "bar", "{user}.a"] {
+        let reply = client.route(cmd(&["SET", key, key])).await.unwrap();
+        assert_eq!(reply, value::simple("OK"));
+        assert_eq!(
+            client.route(cmd(&["GET", key])).await.unwrap(),
+            value::bulk(key)
+        );
+    }
+    assert_eq!(cluster.moved(), 0);
+
+    let e = client
+        .route(cmd(&["MGET", "foo", "bar"]))
+        .await
+        .unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[tokio::test]
+async fn test_route_with_command_table() {
+    let cluster = FakeCluster::start(3).await;
+    let mut client = ClusterClient::connect(&[cluster.info(0)]).await.unwrap();
+
+    // pretend SET's key is its value, so the key and the value below end up
+    // routed to different nodes
+    let mut set = client.commands().get("set").unwrap().clone();
+    set.first_key = 2;
+    set.last_key = 2;
+    client.commands_mut().insert(set);
+
+    let (key, val) = ("foo", "bar");
+    assert_ne!(cluster.owner(key_slot(key)), cluster.owner(key_slot(val)));
+
+    let reply = client.route(cmd(&["SET", key, val])).await.unwrap();
+    assert_eq!(reply, value::simple("OK"));
+    assert_eq!(cluster.moved(), 1);
+}
@@ -0,0 +1,158 @@
+use redis_proto_parse::command::{keys_of, CommandTable};
+use redis_proto_parse::resp::value::{self, RespValue};
+
+fn cmd(args: &[&str]) -> RespValue {
+    value::array(args.iter().map(value::bulk).collect())
+}
+
+fn keys(args: &[&str]) -> Vec<String> {
+    keys_of(&cmd(args))
+        .into_iter()
+        .map(|key| String::from_utf8(key.to_vec()).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_keys_of() {
+    // (command, keys)
+    let cases: &[(&[&str], &[&str])] = &[
+        (&["GET", "k"], &["k"]),
+        (&["set", "k", "v", "EX", "10"], &["k"]),
+        (&["MGET", "a", "b", "c"], &["a", "b", "c"]),
+        (&["MSET", "a", "1", "b", "2"], &["a", "b"]),
+        (&["BLPOP", "a", "b", "0"], &["a", "b"]),
+        (&["BITOP", "AND", "dest", "a", "b"], &["dest", "a", "b"]),
+        (&["RENAME", "a", "b"], &["a", "b"]),
+        (&["OBJECT", "ENCODING", "k"], &["k"]),
+        (&["XINFO", "STREAM", "s", "FULL"], &["s"]),
+        (&["XGROUP", "CREATE", "s", "g", "$"], &["s"]),
+        (&["PING"], &[]),
+        (&["KEYS", "*"], &[]),
+        (&["PUBLISH", "chan", "hi"], &[]),
+        (&["NOSUCHCOMMAND", "k"], &[]),
+        // movable keys
+        (&["EVAL", "return 1", "2", "a", "b", "arg"], &["a", "b"]),
+        (&["EVALSHA", "abc", "0", "arg"], &[]),
+        (&["FCALL_RO", "f", "1", "a"], &["a"]),
+        (
+            &["ZUNIONSTORE", "dest", "2", "a", "b", "WEIGHTS", "1", "2"],
+            &["dest", "a", "b"],
+        ),
+        (&["ZINTER", "2", "a", "b"], &["a", "b"]),
+        (&["BLMPOP", "0.5", "2", "a", "b", "LEFT"], &["a", "b"]),
+        (
+            &["XREAD", "COUNT", "2", "STREAMS", "a", "b", "0", "0"],
+            &["a", "b"],
+        ),
+        (
+            &["XREADGROUP", "GROUP", "g", "c", "streams", "s", ">"],
+            &["s"],
+        ),
+        (
+            &["XREADGROUP", "GROUP", "g", "streams", "STREAMS", "s", ">"],
+            &["s"],
+        ),
+        (&["SORT", "k", "BY", "w_*", "STORE", "dest"], &["k", "dest"]),
+        (&["SORT", "k"], &["k"]),
+        (
+            &["GEORADIUS", "k", "0", "0", "1", "km", "STOREDIST", "d"],
+            &["k", "d"],
+        ),
+        (&["MIGRATE", "h", "6379", "k", "0", "5000"], &["k"]),
+        (
+            &[
+                "MIGRATE", "h", "6379", "", "0", "5000", "COPY", "KEYS", "a", "b",
+            ],
+            &["a", "b"],
+        ),
+        // malformed commands don't panic
+        (&["EVAL", "return 1", "5", "a"], &[]),
+        (&["MGET"], &[]),
+    ];
+
+    for (args, expected) in cases {
+        assert_eq!(keys(args), *expected, "{:?}", args);
+    }
+
+    assert!(keys_of(&value::bulk("GET")).is_empty());
+    assert!(keys_of(&value::array(vec![value::bulk("GET"), value::int(1)])).is_empty());
+}
+
+#[test]
+fn test_command_info() {
+    let table = CommandTable::new();
+
+    let get = table.get("GET").unwrap();
+    assert!(get.readonly && !get.write && !get.blocking);
+    assert!(get.accepts(2));
+    assert!(!get.accepts(3));
+
+    let set = table.get("set").unwrap();
+    assert!(set.write && !set.readonly);
+    assert!(set.accepts(3) && set.accepts(6));
+    assert!(!set.accepts(2));
+
+    let blpop = table.lookup(&cmd(&["BLPOP", "a", "0"])).unwrap();
+    assert!(blpop.blocking);
+
+    let info = table.lookup(&cmd(&["OBJECT", "FREQ", "k"])).unwrap();
+    assert_eq!(info.name, "object|freq");
+
+    assert!(table.get("nosuchcommand").is_none());
+}
+
+fn entry(name: &str, arity: i64, flags: &[&str], keys: (i64, i64, i64)) -> Vec<RespValue> {
+    vec![
+        value::bulk(name),
+        value::int(arity),
+        value::array(flags.iter().map(value::simple).collect()),
+        value::int(keys.0),
+        value::int(keys.1),
+        value::int(keys.2),
+    ]
+}
+
+#[test]
+fn test_update_from_command_info() {
+    let mut table = CommandTable::new();
+    let before = table.len();
+
+    // a 6.x entry, which ends with the ACL categories
+    let mut copy = entry("newcmd", -3, &["write", "denyoom"], (1, 2, 1));
+    copy.push(value::array(vec![value::simple("@keyspace")]));
+
+    // a 7.x container, with tips, key specs and subcommands
+    let mut container = entry("box", -2, &[], (0, 0, 0));
+    container.extend([
+        value::array(vec![]),
+        value::array(vec![]),
+        value::array(vec![]),
+        value::array(vec![value::array(entry(
+            "box|peek",
+            3,
+            &["readonly", "blocking"],
+            (2, 2, 1),
+        ))]),
+    ]);
+
+    let reply = value::array(vec![
+        value::array(copy),
+        value::ARRAY_NONE,
+        value::array(container),
+    ]);
+    assert_eq!(table.update(reply).unwrap(), 3);
+    assert_eq!(table.len(), before + 3);
+
+    assert_eq!(table.keys_of(&cmd(&["NEWCMD", "a", "b"])), [b"a", b"b"]);
+    assert!(table.get("newcmd").unwrap().write);
+
+    let peek = table.lookup(&cmd(&["BOX", "PEEK", "k"])).unwrap();
+    assert!(peek.readonly && peek.blocking);
+    assert_eq!(table.keys_of(&cmd(&["box", "peek", "k"])), [b"k"]);
+
+    // the built-in table is unaffected
+    assert!(keys_of(&cmd(&["NEWCMD", "a", "b"])).is_empty());
+
+    let bad = value::array(vec![value::array(vec![value::bulk("x")])]);
+    assert!(table.update(bad).is_err());
+}
@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+
+use bytes::Bytes;
+use redis_proto_parse::resp::convert::{FromResp, ToArg};
+use redis_proto_parse::resp::value::{self, RespValue};
+
+#[test]
+fn test_to_arg() {
+    assert_eq!("key".to_arg(), value::bulk("key"));
+    assert_eq!(String::from("key").to_arg(), value::bulk("key"));
+    assert_eq!(b"\x00\xff".to_arg(), value::bulk(b"\x00\xff"));
+    assert_eq!(Bytes::from_static(b"b").to_arg(), value::bulk("b"));
+    assert_eq!((-42i64).to_arg(), value::bulk("-42"));
+    assert_eq!(7usize.to_arg(), value::bulk("7"));
+    assert_eq!(1.5f64.to_arg(), value::bulk("1.5"));
+    assert_eq!((&&"nested").to_arg(), value::bulk("nested"));
+}
+
+#[test]
+fn test_from_resp_scalars() {
+    assert_eq!(i64::from_resp(value::int(3)).unwrap(), 3);
+    assert_eq!(i64::from_resp(value::bulk("-12")).unwrap(), -12);
+    assert!(i64::from_resp(value::bulk("x")).is_err());
+
+    assert_eq!(f64::from_resp(RespValue::Double(0.5)).unwrap(), 0.5);
+    assert_eq!(f64::from_resp(value::bulk("2.25")).unwrap(), 2.25);
+
+    assert!(bool::from_resp(value::int(1)).unwrap());
+    assert!(!bool::from_resp(value::BULK_NONE).unwrap());
+    assert!(bool::from_resp(RespValue::Boolean(true)).unwrap());
+
+    assert_eq!(String::from_resp(value::simple("OK")).unwrap(), "OK");
+    assert_eq!(String::from_resp(value::bulk("hi")).unwrap(), "hi");
+    assert!(String::from_resp(value::bulk(b"\xff")).is_err());
+    assert_eq!(Vec::<u8>::from_resp(value::bulk(b"\xff")).unwrap(), b"\xff");
+}
+
+#[test]
+fn test_from_resp_collections() {
+    assert_eq!(Option::<i64>::from_resp(value::BULK_NONE).unwrap(), None);
+    assert_eq!(Option::<i64>::from_resp(RespValue::Null).unwrap(), None);
+    assert_eq!(Option::<i64>::from_resp(value::int(1)).unwrap(), Some(1));
+
+    let items = value::array(vec![value::bulk("1"), value::int(2)]);
+    assert_eq!(Vec::<i64>::from_resp(items).unwrap(), [1, 2]);
+    assert!(Vec::<i64>::from_resp(value::ARRAY_NONE).unwrap().is_empty());
+
+    let flat = value::array(vec![
+        value::bulk("a"),
+        value::bulk("1"),
+        value::bulk("b"),
+        value::bulk("2"),
+    ]);
+    let map = HashMap::<String, i64>::from_resp(flat).unwrap();
+    assert_eq!(map["a"], 1);
+    assert_eq!(map["b"], 2);
+
+    let resp3 = value::map(vec![(value::bulk("a"), value::int(1))]);
+    let map = HashMap::<String, i64>::from_resp(resp3).unwrap();
+    assert_eq!(map["a"], 1);
+
+    let odd = value::array(vec![value::bulk("a")]);
+    assert!(HashMap::<String, i64>::from_resp(odd).is_err());
+}
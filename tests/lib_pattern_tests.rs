@@ -0,0 +1,124 @@
+use redis_proto_parse::pattern::{matches, matches_nocase};
+
+#[test]
+fn test_glob_table() {
+    // (pattern, string, expected)
+    let cases: &[(&str, &str, bool)] = &[
+        // literals
+        ("", "", true),
+        ("a", "a", true),
+        ("a", "b", false),
+        ("abc", "ab", false),
+        ("ab", "abc", false),
+        // star
+        // like Redis, an empty string never matches a non-empty pattern
+        ("*", "", false),
+        ("*", "anything", true),
+        ("a*", "a", true),
+        ("a*", "abc", true),
+        ("*c", "abc", true),
+        ("*c", "abd", false),
+        ("a*c", "ac", true),
+        ("a*c", "abbbc", true),
+        ("a*b*c", "axxbyyc", true),
+        ("a*b*c", "axxcyyb", false),
+        ("a**c", "abc", true),
+        ("h*llo", "hllo", true),
+        ("h*llo", "heeeello", true),
+        ("*a*a*a*", "aaa", true),
+        ("*a*a*a*", "aa", false),
+        // question mark
+        ("?", "", false),
+        ("?", "a", true),
+        ("h?llo", "hello", true),
+        ("h?llo", "hllo", false),
+        ("??", "ab", true),
+        ("*?", "", false),
+        // sets
+        ("h[ae]llo", "hello", true),
+        ("h[ae]llo", "hallo", true),
+        ("h[ae]llo", "hillo", false),
+        ("h[^e]llo", "hallo", true),
+        ("h[^e]llo", "hello", false),
+        ("h[a-b]llo", "hallo", true),
+        ("h[a-b]llo", "hbllo", true),
+        ("h[a-b]llo", "hcllo", false),
+        // reversed ranges are swapped
+        ("[z-a]", "m", true),
+        ("[^a-c]", "d", true),
+        ("[^a-c]", "b", false),
+        ("[a-cx]", "x", true),
+        // escapes
+        ("\\*", "*", true),
+        ("\\*", "a", false),
+        ("\\?", "?", true),
+        ("a\\[b", "a[b", true),
+        ("[\\]]", "]", true),
+        ("[\\^a]", "^", true),
+        ("[a\\-z]", "-", true),
+        ("[a\\-z]", "m", false),
+        // a trailing backslash matches itself
+        ("a\\", "a\\", true),
+        // an unterminated set still matches one byte
+        ("[abc", "a", true),
+        ("[abc", "d", false),
+        ("[a-", "a", true),
+        // a dash at the end of a set is a range up to ']'
+        ("[a-]", "b", false),
+        ("[a-]", "^", true),
+        // empty set never matches, negated empty set matches anything
+        ("[]", "a", false),
+        ("[^]", "a", true),
+        // pub/sub style patterns
+        ("news.*", "news.art.figurative", true),
+        ("news.*", "news", false),
+        ("__keyspace@0__:*", "__keyspace@0__:user:1", true),
+        ("__keyspace@0__:*", "__keyspace@1__:user:1", false),
+    ];
+
+    for (pattern, string, expected) in cases {
+        assert_eq!(
+            matches(pattern, string),
+            *expected,
+            "pattern {:?} against {:?}",
+            pattern,
+            string
+        );
+    }
+}
+
+#[test]
+fn test_glob_nocase() {
+    assert!(matches_nocase("HeLLo", "hello"));
+    assert!(matches_nocase("h[A-Z]llo", "hello"));
+    assert!(matches_nocase("h[E]llo", "hello"));
+    assert!(!matches("h[E]llo", "hello"));
+    // escaped bytes in a set are compared exactly
+    assert!(!matches_nocase("h[\\E]llo", "hello"));
+}
+
+#[test]
+fn test_glob_binary() {
+    assert!(matches(b"\x00*\xff", b"\x00abc\xff"));
+    assert!(matches(b"[\x80-\xff]", b"\x90"));
+    assert!(!matches(b"?", b""));
+}
+
+#[test]
+fn test_glob_long_nested_stars() {
+    // without pruning this takes exponential time
+    let pattern = "a*".repeat(30) + "b";
+    let string = "a".repeat(50);
+
+    assert!(!matches(&pattern, &string));
+    assert!(matches(&pattern, string + "b"));
+}
+
+#[test]
+fn test_glob_nesting_limit() {
+    // Redis gives up rather than recursing this deep
+    let pattern = "*a".repeat(2000);
+    let string = "a".repeat(2000);
+
+    assert!(!matches(pattern, string));
+}
@@ -0,0 +1,139 @@
+use bytes::BytesMut;
+use redis_proto_parse::resp::{encoder, value, value::RespValue, RespCodec};
+use tokio_util::codec::Decoder;
+
+fn decode(data: &[u8]) -> RespValue {
+    let mut rx = BytesMut::from(data);
+    let mut codec = RespCodec::default();
+
+    match codec.decode(&mut rx) {
+        Ok(Some(v)) => {
+            assert!(rx.is_empty(), "trailing bytes: {:?}", rx);
+            v
+        }
+        Ok(None) => panic!("Decode returned None, but a value was expected."),
+        Err(e) => panic!("An error occurred while decoding: {:?}", e),
+    }
+}
+
+fn roundtrip(v: RespValue) {
+    let mut data = BytesMut::new();
+    encoder::resp_encode(v.clone(), &mut data);
+    assert_eq!(decode(&data), v);
+}
+
+#[test]
+fn test_op_scalars() {
+    assert_eq!(decode(b"_\r\n"), RespValue::Null);
+    assert_eq!(decode(b"#t\r\n"), RespValue::Boolean(true));
+    assert_eq!(decode(b"#f\r\n"), RespValue::Boolean(false));
+    assert_eq!(decode(b",1.5\r\n"), RespValue::Double(1.5));
+    assert_eq!(decode(b",-inf\r\n"), RespValue::Double(f64::NEG_INFINITY));
+    assert_eq!(
+        decode(b"(3492890328409238509324850943850943825024385\r\n"),
+        RespValue::BigNumber("3492890328409238509324850943850943825024385".into())
+    );
+    assert_eq!(
+        decode(b"!21\r\nSYNTAX invalid syntax\r\n"),
+        RespValue::BlobError(b"SYNTAX invalid syntax"[..].into())
+    );
+    assert_eq!(
+        decode(b"=15\r\ntxt:Some string\r\n"),
+        RespValue::VerbatimString("txt".into(), b"Some string"[..].into())
+    );
+}
+
+#[test]
+fn test_op_aggregates() {
+    assert_eq!(
+        decode(b"%2\r\n+first\r\n:1\r\n+second\r\n:2\r\n"),
+        value::map(vec![
+            (value::simple("first"), value::int(1)),
+            (value::simple("second"), value::int(2)),
+        ])
+    );
+    assert_eq!(
+        decode(b"~2\r\n$1\r\na\r\n#t\r\n"),
+        RespValue::Set(vec![value::bulk("a"), RespValue::Boolean(true)])
+    );
+    assert_eq!(
+        decode(b">3\r\n$7\r\nmessage\r\n$2\r\nch\r\n$2\r\nhi\r\n"),
+        value::push(vec![
+            value::bulk("message"),
+            value::bulk("ch"),
+            value::bulk("hi")
+        ])
+    );
+    assert_eq!(decode(b"%0\r\n"), value::map(vec![]));
+}
+
+#[test]
+fn test_attribute_dropped() {
+    // attribute before a top level reply
+    assert_eq!(
+        decode(b"|1\r\n+key-popularity\r\n%1\r\n$1\r\na\r\n,0.1923\r\n*1\r\n:2039123\r\n"),
+        value::array(vec![value::int(2039123)])
+    );
+
+    // attribute on an item inside an array
+    assert_eq!(
+        decode(b"*2\r\n:1\r\n|1\r\n+ttl\r\n:3600\r\n:2\r\n"),
+        value::array(vec![value::int(1), value::int(2)])
+    );
+}
+
+#[test]
+fn test_partial_map() {
+    let data = b"%1\r\n$5\r\nhello\r\n*1\r\n_\r\n";
+    let mut rx = BytesMut::new();
+    let mut codec = RespCodec::default();
+
+    // feed the frame one byte at a time
+    for (i, b) in data.iter().enumerate() {
+        rx.extend_from_slice(&[*b]);
+
+        match codec.decode(&mut rx) {
+            Ok(Some(v)) => {
+                assert_eq!(i, data.len() - 1);
+                assert_eq!(
+                    v,
+                    value::map(vec![(
+                        value::bulk("hello"),
+                        value::array(vec![RespValue::Null])
+                    )])
+                );
+                return;
+            }
+            Ok(None) => continue,
+            Err(e) => panic!("An error occurred while decoding: {:?}", e),
+        }
+    }
+
+    panic!("frame was never decoded");
+}
+
+#[test]
+fn test_oversized_map() {
+    for data in [
+        &b"%9223372036854775807\r\n"[..],
+        b"|9223372036854775807\r\n",
+    ] {
+        let mut rx = BytesMut::from(data);
+        let err = RespCodec::default().decode(&mut rx).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
+
+#[test]
+fn test_resp3_roundtrip() {
+    roundtrip(RespValue::Null);
+    roundtrip(RespValue::Boolean(true));
+    roundtrip(RespValue::Double(3.25));
+    roundtrip(RespValue::Double(f64::INFINITY));
+    roundtrip(RespValue::Double(f64::NAN));
+    roundtrip(RespValue::BigNumber("-12345678901234567890".into()));
+    roundtrip(RespValue::BlobError(b"ERR oops"[..].into()));
+    roundtrip(RespValue::VerbatimString("mkd".into(), b"# hi"[..].into()));
+    roundtrip(value::map(vec![(value::bulk("k"), value::push(vec![]))]));
+    roundtrip(RespValue::Set(vec![value::int(1), value::int(2)]));
+}
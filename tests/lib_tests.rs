@@ -117,6 +117,189 @@ fn test_subscribe_multiple_channels() {
     );
 }
 
+#[test]
+fn test_ssubscribe_single_channel() {
+    let (mut rx, mut tx) = prepare_data!("ssubscribe_single_channel");
+
+    test_generic(
+        &mut rx,
+        value::array(vec![
+            value::bulk("ssubscribe"),
+            value::bulk("test_channel_1"),
+            value::int(1),
+        ]),
+    );
+
+    test_generic(
+        &mut tx,
+        value::array(vec![
+            value::bulk("ssubscribe"),
+            value::bulk("test_channel_1"),
+        ]),
+    );
+}
+
+#[test]
+fn test_ssubscribe_multiple_channels() {
+    let (mut rx, mut tx) = prepare_data!("ssubscribe_multiple_channels");
+
+    test_generic_multiple(
+        &mut rx,
+        vec![
+            value::array(vec![
+                value::bulk("ssubscribe"),
+                value::bulk("{test}_channel_1"),
+                value::int(1),
+            ]),
+            value::array(vec![
+                value::bulk("ssubscribe"),
+                value::bulk("{test}_channel_2"),
+                value::int(2),
+            ]),
+            value::array(vec![
+                value::bulk("ssubscribe"),
+                value::bulk("{test}_channel_3"),
+                value::int(3),
+            ]),
+        ],
+    );
+
+    test_generic_multiple(
+        &mut tx,
+        vec![value::array(vec![
+            value::bulk("ssubscribe"),
+            value::bulk("{test}_channel_1"),
+            value::bulk("{test}_channel_2"),
+            value::bulk("{test}_channel_3"),
+        ])],
+    );
+}
+
+#[test]
+fn test_sunsubscribe_single_channel() {
+    let (mut rx, mut tx) = prepare_data!("sunsubscribe_single_channel");
+
+    test_generic(
+        &mut rx,
+        value::array(vec![
+            value::bulk("sunsubscribe"),
+            value::bulk("test_channel_1"),
+            value::int(0),
+        ]),
+    );
+
+    test_generic(
+        &mut tx,
+        value::array(vec![
+            value::bulk("sunsubscribe"),
+            value::bulk("test_channel_1"),
+        ]),
+    );
+}
+
+#[test]
+fn test_sunsubscribe_multiple_channels() {
+    let (mut rx, mut tx) = prepare_data!("sunsubscribe_multiple_channels");
+
+    test_generic_multiple(
+        &mut rx,
+        vec![
+            value::array(vec![
+                value::bulk("sunsubscribe"),
+                value::bulk("{test}_channel_1"),
+                value::int(2),
+            ]),
+            value::array(vec![
+                value::bulk("sunsubscribe"),
+                value::bulk("{test}_channel_2"),
+                value::int(1),
+            ]),
+            value::array(vec![
+                value::bulk("sunsubscribe"),
+                value::bulk("{test}_channel_3"),
+                value::int(0),
+            ]),
+        ],
+    );
+
+    test_generic_multiple(
+        &mut tx,
+        vec![value::array(vec![
+            value::bulk("sunsubscribe"),
+            value::bulk("{test}_channel_1"),
+            value::bulk("{test}_channel_2"),
+            value::bulk("{test}_channel_3"),
+        ])],
+    );
+}
+
+#[test]
+fn test_spublish() {
+    let (mut rx, mut tx) = prepare_data!("spublish");
+
+    test_generic(&mut rx, value::int(1));
+
+    test_generic(
+        &mut tx,
+        value::array(vec![
+            value::bulk("spublish"),
+            value::bulk("test_channel_1"),
+            value::bulk("hello world"),
+        ]),
+    );
+}
+
+#[test]
+fn test_smessage() {
+    let (mut rx, mut tx) = prepare_data!("smessage");
+
+    test_generic_multiple(
+        &mut rx,
+        vec![
+            value::array(vec![
+                value::bulk("ssubscribe"),
+                value::bulk("test_channel_1"),
+                value::int(1),
+            ]),
+            value::array(vec![
+                value::bulk("smessage"),
+                value::bulk("test_channel_1"),
+                value::bulk("hello world"),
+            ]),
+        ],
+    );
+
+    test_generic(
+        &mut tx,
+        value::array(vec![
+            value::bulk("ssubscribe"),
+            value::bulk("test_channel_1"),
+        ]),
+    );
+
+    // the capture holds nothing beyond the frames above
+    assert!(rx.is_empty());
+    assert!(tx.is_empty());
+}
+
+#[test]
+fn test_reset() {
+    let (mut rx, mut tx) = prepare_data!("reset");
+
+    test_generic(&mut rx, value::simple("RESET"));
+
+    test_generic(&mut tx, value::array(vec![value::bulk("reset")]));
+}
+
+#[test]
+fn test_quit() {
+    let (mut rx, mut tx) = prepare_data!("quit");
+
+    test_generic(&mut rx, value::simple("OK"));
+
+    test_generic(&mut tx, value::array(vec![value::bulk("quit")]));
+}
+
 #[test]
 fn test_debug_fmt() {
     let v = value::array(vec![